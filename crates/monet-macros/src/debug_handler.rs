@@ -0,0 +1,164 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    FnArg, GenericArgument, ItemFn, PathArguments, ReturnType, Token, Type,
+    parse::{Parse, ParseStream},
+    spanned::Spanned,
+};
+
+/// The arguments of the attribute.
+pub(crate) struct Args {
+    state: Option<Type>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut state = None;
+        if !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            if key != "state" {
+                return Err(syn::Error::new_spanned(key, "expected `state`"));
+            }
+            input.parse::<Token![=]>()?;
+            state = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error("unexpected argument"));
+        }
+        Ok(Self { state })
+    }
+}
+
+/// Handlers can't take more extractors than `all_the_tuples!` covers.
+const MAX_EXTRACTORS: usize = 16;
+
+pub(crate) fn expand(args: Args, item: ItemFn) -> TokenStream {
+    let checks = match checks(args, &item) {
+        Ok(checks) => checks,
+        Err(err) => err.into_compile_error(),
+    };
+    quote! {
+        #item
+        #checks
+    }
+}
+
+fn checks(args: Args, item: &ItemFn) -> syn::Result<TokenStream> {
+    let sig = &item.sig;
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            sig.fn_token,
+            "handlers must be `async fn`s",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "`#[debug_handler]` doesn't support generic handlers",
+        ));
+    }
+    if sig.inputs.len() > MAX_EXTRACTORS {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            format!("handlers can take at most {MAX_EXTRACTORS} arguments"),
+        ));
+    }
+
+    let mut types = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "handlers can't take `self`",
+                ));
+            }
+            FnArg::Typed(arg) => types.push(&*arg.ty),
+        }
+    }
+
+    let state = match args.state {
+        Some(state) => quote!(#state),
+        None => match types.iter().find_map(|ty| state_type(ty)) {
+            Some(state) => quote!(#state),
+            None => quote!(()),
+        },
+    };
+
+    let name = &sig.ident;
+    let mut checks = TokenStream::new();
+    let last = types.len().saturating_sub(1);
+    for (index, ty) in types.iter().enumerate() {
+        let check = format_ident!("__check_argument_{}", index + 1);
+        if index < last {
+            checks.extend(quote_spanned! {ty.span()=>
+                fn #check()
+                where
+                    #ty: ::monet::extract::FromRequestParts<#state>,
+                {
+                }
+            });
+        } else {
+            // The last argument may consume the body, with either trait.
+            checks.extend(quote_spanned! {ty.span()=>
+                fn #check() {
+                    fn last_argument<T, M>()
+                    where
+                        T: ::monet::extract::FromRequest<#state, M>,
+                    {
+                    }
+                    last_argument::<#ty, _>();
+                }
+            });
+        }
+    }
+
+    if let ReturnType::Type(_, ty) = &sig.output
+        && !matches!(&**ty, Type::ImplTrait(_))
+    {
+        checks.extend(quote_spanned! {ty.span()=>
+            fn __check_return_type()
+            where
+                #ty: ::monet::IntoResponse,
+            {
+            }
+        });
+    }
+
+    checks.extend(quote_spanned! {name.span()=>
+        fn __check_handler() {
+            fn handler<H, X>(_: H)
+            where
+                H: ::monet::handler::Handler<X, #state>,
+            {
+            }
+            handler(#name);
+        }
+    });
+
+    Ok(quote! {
+        #[allow(warnings, clippy::all)]
+        const _: () = {
+            #checks
+        };
+    })
+}
+
+/// `T` if `ty` is `State<T>`.
+fn state_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "State" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
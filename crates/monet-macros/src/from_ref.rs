@@ -0,0 +1,64 @@
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+use syn::{Data, DeriveInput, Field, Index, Member, spanned::Spanned};
+
+pub(crate) fn expand(item: DeriveInput) -> syn::Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "`#[derive(FromRef)]` doesn't support generics",
+        ));
+    }
+
+    let fields = match &item.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &item.ident,
+                "`#[derive(FromRef)]` only supports structs",
+            ));
+        }
+    };
+
+    let state = &item.ident;
+    let mut impls = TokenStream::new();
+    for (index, field) in fields.iter().enumerate() {
+        if skipped(field)? {
+            continue;
+        }
+
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(index)),
+        };
+        let ty = &field.ty;
+        impls.extend(quote_spanned! {ty.span()=>
+            #[allow(clippy::clone_on_copy, clippy::clone_on_ref_ptr)]
+            impl ::monet::extract::from_ref::FromRef<#state> for #ty {
+                fn from_ref(state: &#state) -> Self {
+                    ::std::clone::Clone::clone(&state.#member)
+                }
+            }
+        });
+    }
+    Ok(impls)
+}
+
+fn skipped(field: &Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("from_ref"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
@@ -0,0 +1,247 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Attribute, Data, DeriveInput, Expr, Fields, Lit, LitStr, Meta, Token, parse_quote};
+
+pub(crate) fn expand(mut item: DeriveInput) -> syn::Result<TokenStream> {
+    let container = SerdeAttrs::parse(&item.attrs)?;
+    let schema = match &item.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let mut properties = Vec::new();
+                for field in &fields.named {
+                    let attrs = SerdeAttrs::parse(&field.attrs)?;
+                    if attrs.skip {
+                        continue;
+                    }
+                    let ident = field.ident.as_ref().expect("named fields have names");
+                    let name = attrs.rename.unwrap_or_else(|| {
+                        container.rename_field(ident.to_string().trim_start_matches("r#"))
+                    });
+                    let ty = &field.ty;
+                    let required = if attrs.default || container.default {
+                        quote!(false)
+                    } else {
+                        quote!(!<#ty as ::monet::openapi::JsonSchema>::optional())
+                    };
+                    let schema = with_description(
+                        quote!(<#ty as ::monet::openapi::JsonSchema>::schema()),
+                        &field.attrs,
+                    );
+                    properties.push(quote!(.property(#name, #schema, #required)));
+                }
+                quote!(::monet::openapi::Schema::object() #(#properties)*)
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                quote!(<#ty as ::monet::openapi::JsonSchema>::schema())
+            }
+            Fields::Unnamed(fields) => {
+                let items = fields.unnamed.iter().map(|field| {
+                    let ty = &field.ty;
+                    quote!(<#ty as ::monet::openapi::JsonSchema>::schema().into_value())
+                });
+                let len = fields.unnamed.len();
+                quote!({
+                    let mut schema = ::monet::openapi::Schema::of_type("array");
+                    schema.set("prefixItems", ::std::vec![#(#items),*].into());
+                    schema.set("minItems", #len.into());
+                    schema.set("maxItems", #len.into());
+                    schema
+                })
+            }
+            Fields::Unit => quote!(::monet::openapi::Schema::of_type("null")),
+        },
+        Data::Enum(data) => {
+            let mut values = Vec::new();
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(syn::Error::new_spanned(
+                        &variant.fields,
+                        "`#[derive(JsonSchema)]` only supports enums without fields",
+                    ));
+                }
+                let attrs = SerdeAttrs::parse(&variant.attrs)?;
+                if attrs.skip {
+                    continue;
+                }
+                values.push(
+                    attrs
+                        .rename
+                        .unwrap_or_else(|| container.rename_variant(&variant.ident.to_string())),
+                );
+            }
+            quote!(::monet::openapi::Schema::string_enum([#(#values),*]))
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &item.ident,
+                "`#[derive(JsonSchema)]` doesn't support unions",
+            ));
+        }
+    };
+    let schema = with_description(schema, &item.attrs);
+
+    let type_params: Vec<_> = item
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let where_clause = item.generics.make_where_clause();
+    for param in type_params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::monet::openapi::JsonSchema));
+    }
+    let ident = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::monet::openapi::JsonSchema for #ident #ty_generics #where_clause {
+            fn schema() -> ::monet::openapi::Schema {
+                #schema
+            }
+        }
+    })
+}
+
+/// Add the doc comments in `attrs`, if any, as the description of `schema`.
+fn with_description(schema: TokenStream, attrs: &[Attribute]) -> TokenStream {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(doc) => Some(doc.value().trim().to_owned()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let description = lines.join("\n");
+    let description = description.trim();
+    if description.is_empty() {
+        schema
+    } else {
+        quote!(#schema.description(#description))
+    }
+}
+
+/// The `#[serde(..)]` attributes that change the serialized form.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    skip: bool,
+    default: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut this = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+                if path.is_ident("rename") || path.is_ident("rename_all") {
+                    // Only the serialized name matters to the schema.
+                    let value = if meta.input.peek(Token![=]) {
+                        Some(meta.value()?.parse::<LitStr>()?.value())
+                    } else {
+                        let mut serialize = None;
+                        meta.parse_nested_meta(|inner| {
+                            let value = inner.value()?.parse::<LitStr>()?.value();
+                            if inner.path.is_ident("serialize") {
+                                serialize = Some(value);
+                            }
+                            Ok(())
+                        })?;
+                        serialize
+                    };
+                    if path.is_ident("rename") {
+                        this.rename = value;
+                    } else {
+                        if let Some(rule) = &value
+                            && convert_case(rule, &[]).is_none()
+                        {
+                            return Err(meta.error(format!("unknown `rename_all` rule {rule:?}")));
+                        }
+                        this.rename_all = value;
+                    }
+                } else if path.is_ident("skip") || path.is_ident("skip_serializing") {
+                    this.skip = true;
+                } else if path.is_ident("default") {
+                    this.default = true;
+                    if meta.input.peek(Token![=]) {
+                        meta.value()?.parse::<Expr>()?;
+                    }
+                } else if meta.input.peek(Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|inner| {
+                        if inner.input.peek(Token![=]) {
+                            inner.value()?.parse::<Expr>()?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(this)
+    }
+
+    /// The serialized name of the snake_case field `name`.
+    fn rename_field(&self, name: &str) -> String {
+        let words: Vec<&str> = name.split('_').filter(|word| !word.is_empty()).collect();
+        self.rename_all
+            .as_deref()
+            .and_then(|rule| convert_case(rule, &words))
+            .unwrap_or_else(|| name.to_owned())
+    }
+
+    /// The serialized name of the PascalCase variant `name`.
+    fn rename_variant(&self, name: &str) -> String {
+        let mut words = Vec::new();
+        let mut start = 0;
+        for (index, c) in name.char_indices().skip(1) {
+            if c.is_uppercase() {
+                words.push(&name[start..index]);
+                start = index;
+            }
+        }
+        words.push(&name[start..]);
+        self.rename_all
+            .as_deref()
+            .and_then(|rule| convert_case(rule, &words))
+            .unwrap_or_else(|| name.to_owned())
+    }
+}
+
+/// Join `words` following one of serde's `rename_all` rules.
+fn convert_case(rule: &str, words: &[&str]) -> Option<String> {
+    let lower = || words.iter().map(|word| word.to_lowercase());
+    let upper = || words.iter().map(|word| word.to_uppercase());
+    let capitalized = || {
+        words.iter().map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect()
+            })
+        })
+    };
+    Some(match rule {
+        "lowercase" => lower().collect(),
+        "UPPERCASE" => upper().collect(),
+        "PascalCase" => capitalized().collect(),
+        "camelCase" => lower().take(1).chain(capitalized().skip(1)).collect(),
+        "snake_case" => lower().collect::<Vec<_>>().join("_"),
+        "SCREAMING_SNAKE_CASE" => upper().collect::<Vec<_>>().join("_"),
+        "kebab-case" => lower().collect::<Vec<_>>().join("-"),
+        "SCREAMING-KEBAB-CASE" => upper().collect::<Vec<_>>().join("-"),
+        _ => return None,
+    })
+}
@@ -0,0 +1,94 @@
+//! Macros for [monet](https://docs.rs/monet), re-exported from there with the
+//! `macros` feature.
+
+use proc_macro::TokenStream;
+use syn::{DeriveInput, ItemFn, parse_macro_input};
+
+mod debug_handler;
+mod from_ref;
+mod json_schema;
+
+/// Derive `FromRef` for every field of a state struct, so handlers can extract
+/// each of them with `State<Field>`.
+///
+/// Fields are cloned out of the state. Fields marked `#[from_ref(skip)]` get no
+/// implementation, which is needed when two fields share a type. Generic structs
+/// are not supported.
+///
+/// ```no_run
+/// # use std::rc::Rc;
+/// # use monet::FromRef;
+/// # #[derive(Clone)]
+/// # struct DbPool;
+/// # struct Config;
+/// #[derive(Clone, FromRef)]
+/// struct AppState {
+///     db: DbPool,
+///     config: Rc<Config>,
+///     #[from_ref(skip)]
+///     secret: String,
+/// }
+/// ```
+#[proc_macro_derive(FromRef, attributes(from_ref))]
+pub fn derive_from_ref(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
+    from_ref::expand(item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `JsonSchema`, describing the type's serialized form for OpenAPI
+/// documents.
+///
+/// Supports structs and enums without fields. Follows the serde attributes
+/// `rename`, `rename_all`, `skip` and `default`, and uses doc comments as
+/// descriptions. Type parameters must implement `JsonSchema` too.
+///
+/// ```no_run
+/// # use monet::openapi::JsonSchema;
+/// # use serde::Serialize;
+/// #[derive(Serialize, JsonSchema)]
+/// #[serde(rename_all = "camelCase")]
+/// struct User {
+///     /// The user's unique id.
+///     user_id: u64,
+///     display_name: Option<String>,
+/// }
+/// ```
+#[proc_macro_derive(JsonSchema, attributes(serde))]
+pub fn derive_json_schema(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
+    json_schema::expand(item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Check a handler's extractors and return type one by one, for errors that
+/// point at the argument or type at fault.
+///
+/// Without it, a handler that can't be called with extracted values only fails
+/// to implement `Handler`, in an error that lists the whole signature. With it,
+/// each argument but the last must implement `FromRequestParts`, the last one
+/// `FromRequest`, and the return type `IntoResponse`. The checks are compiled
+/// separately from the handler, which is left unchanged.
+///
+/// The state type defaults to that of a `State<T>` argument, or `()`, and can be
+/// given with `#[debug_handler(state = AppState)]`. Generic handlers are not
+/// supported.
+///
+/// ```compile_fail
+/// # use monet::{debug_handler, extract::{multipart::Multipart, query::Query}};
+/// # #[derive(serde::Deserialize)]
+/// # struct Params;
+/// #[debug_handler]
+/// async fn upload(multipart: Multipart, Query(params): Query<Params>) -> String {
+///     // error: the trait bound `Multipart: FromRequestParts<()>` is not satisfied
+///     todo!()
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn debug_handler(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as debug_handler::Args);
+    let item = parse_macro_input!(item as ItemFn);
+    debug_handler::expand(args, item).into()
+}
@@ -0,0 +1,6 @@
+#[test]
+fn debug_handler() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/debug_handler/pass/*.rs");
+    cases.compile_fail("tests/debug_handler/fail/*.rs");
+}
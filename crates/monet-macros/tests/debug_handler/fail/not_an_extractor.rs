@@ -0,0 +1,10 @@
+use monet::debug_handler;
+
+struct Config;
+
+#[debug_handler]
+async fn handler(_config: Config) -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use monet::debug_handler;
+
+#[debug_handler]
+fn handler() -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use monet::debug_handler;
+
+struct Report;
+
+#[debug_handler]
+async fn handler() -> Report {
+    Report
+}
+
+fn main() {}
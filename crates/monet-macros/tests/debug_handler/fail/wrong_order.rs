@@ -0,0 +1,12 @@
+use monet::{debug_handler, extract::query::Query};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Params {}
+
+#[debug_handler]
+async fn handler(body: String, Query(_): Query<Params>) -> String {
+    body
+}
+
+fn main() {}
@@ -0,0 +1,30 @@
+use monet::{
+    State, debug_handler,
+    extract::{json::Json, query::Query},
+};
+use serde::Deserialize;
+
+#[derive(Clone)]
+struct AppState;
+
+#[derive(Deserialize)]
+struct Params {}
+
+#[derive(Deserialize)]
+struct Payload {}
+
+#[debug_handler]
+async fn create(
+    State(_): State<AppState>,
+    Query(_): Query<Params>,
+    Json(_): Json<Payload>,
+) -> String {
+    String::new()
+}
+
+#[debug_handler(state = AppState)]
+async fn health() -> &'static str {
+    "ok"
+}
+
+fn main() {}
@@ -5,14 +5,41 @@ use http::{Method, Uri, request::Parts};
 
 pub mod state;
 
+pub mod auth;
+
+pub mod from_ref;
+
 pub mod query;
 
+pub(crate) mod body;
+
+pub mod json;
+
+pub mod form;
+
+pub mod path;
+
+pub mod validated;
+
+pub mod task_scope;
+
+pub mod connect_info;
+
+pub mod matched_path;
+
+pub mod multipart;
+
+pub mod ws;
+
 #[derive(Debug, Clone, Copy)]
 pub enum ViaParts {}
 
 #[derive(Debug, Clone, Copy)]
 pub enum ViaRequest {}
 
+// Extractors run on the core that accepted the request, so their futures don't
+// need to be `Send`.
+#[allow(async_fn_in_trait)]
 pub trait FromRequest<S, M = ViaRequest>: Sized {
     /// If the extractor fails it'll use this "rejection" type. A rejection is
     /// a kind of error that can be converted into a response.
@@ -34,6 +61,14 @@ where
     }
 }
 
+impl<S> FromRequest<S> for HttpRequest {
+    type Rejection = Infallible;
+
+    async fn from_request(req: HttpRequest, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(req)
+    }
+}
+
 impl<S, T> FromRequest<S> for Result<T, T::Rejection>
 where
     T: FromRequest<S>,
@@ -45,6 +80,7 @@ where
     }
 }
 
+#[allow(async_fn_in_trait)]
 pub trait FromRequestParts<S>: Sized {
     /// If the extractor fails it'll use this "rejection" type. A rejection is
     /// a kind of error that can be converted into a response.
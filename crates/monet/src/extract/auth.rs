@@ -0,0 +1,359 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use http::{
+    HeaderName, HeaderValue, StatusCode,
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    request::Parts,
+};
+
+use crate::{HttpResponse, IntoResponse, Problem, extract::FromRequestParts};
+
+/// The header [`ApiKey`] is read from.
+pub const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Extractor for credentials sent with HTTP Basic authentication, i.e. an
+/// `Authorization: Basic <base64 of username:password>` header.
+///
+/// Requests without valid Basic credentials are rejected with `401 Unauthorized`
+/// and a `WWW-Authenticate: Basic` challenge. The credentials are not checked
+/// against anything, that is up to the handler, or an
+/// [`AuthLayer`](crate::middleware::AuthLayer).
+#[derive(Clone, PartialEq, Eq)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for BasicAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicAuth")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> FromRequestParts<S> for BasicAuth {
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let credentials = authorization(parts, "Basic").map_err(|reason| AuthRejection {
+            scheme: Scheme::Basic,
+            reason,
+        })?;
+
+        let invalid = || AuthRejection {
+            scheme: Scheme::Basic,
+            reason: Reason::Malformed,
+        };
+        let decoded = STANDARD.decode(credentials).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (username, password) = decoded.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        })
+    }
+}
+
+/// Extractor for a bearer token, from an `Authorization: Bearer <token>` header.
+///
+/// Requests without a bearer token are rejected with `401 Unauthorized` and a
+/// `WWW-Authenticate: Bearer` challenge.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BearerToken(pub String);
+
+impl BearerToken {
+    pub fn token(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for BearerToken {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BearerToken").finish_non_exhaustive()
+    }
+}
+
+impl<S> FromRequestParts<S> for BearerToken {
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        authorization(parts, "Bearer")
+            .map(|token| Self(token.to_owned()))
+            .map_err(|reason| AuthRejection {
+                scheme: Scheme::Bearer,
+                reason,
+            })
+    }
+}
+
+/// Extractor for an API key, from the [`X-API-Key`](X_API_KEY) header.
+///
+/// Requests without a key are rejected with `401 Unauthorized` and a
+/// `WWW-Authenticate: ApiKey header="X-API-Key"` challenge.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey(pub String);
+
+impl ApiKey {
+    pub fn key(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for ApiKey {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ApiKey").finish_non_exhaustive()
+    }
+}
+
+impl<S> FromRequestParts<S> for ApiKey {
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let rejection = |reason| AuthRejection {
+            scheme: Scheme::ApiKey,
+            reason,
+        };
+        let value = parts
+            .headers
+            .get(X_API_KEY)
+            .ok_or(rejection(Reason::Missing))?;
+        let key = value
+            .to_str()
+            .map_err(|_| rejection(Reason::Malformed))?
+            .trim();
+        if key.is_empty() {
+            return Err(rejection(Reason::Malformed));
+        }
+        Ok(Self(key.to_owned()))
+    }
+}
+
+/// The credentials of the `Authorization` header, if it uses `scheme`.
+fn authorization<'a>(parts: &'a Parts, scheme: &str) -> Result<&'a str, Reason> {
+    let value = parts.headers.get(AUTHORIZATION).ok_or(Reason::Missing)?;
+    let value = value.to_str().map_err(|_| Reason::Malformed)?;
+    let (actual, credentials) = value.split_once(' ').ok_or(Reason::Malformed)?;
+    if !actual.eq_ignore_ascii_case(scheme) {
+        return Err(Reason::OtherScheme);
+    }
+    let credentials = credentials.trim();
+    if credentials.is_empty() {
+        return Err(Reason::Malformed);
+    }
+    Ok(credentials)
+}
+
+/// The principal inserted into the request extensions by
+/// [`AuthLayer`](crate::middleware::AuthLayer), i.e. whoever the request was
+/// authenticated as.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Authenticated<P>(pub P);
+
+impl<P> Deref for Authenticated<P> {
+    type Target = P;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P> DerefMut for Authenticated<P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S, P> FromRequestParts<S> for Authenticated<P>
+where
+    P: Clone + Send + Sync + 'static,
+{
+    type Rejection = MissingPrincipal;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or(MissingPrincipal(std::any::type_name::<P>()))
+    }
+}
+
+/// Rejection used by [`Authenticated`] when the request wasn't authenticated by an
+/// [`AuthLayer`](crate::middleware::AuthLayer) producing that principal type.
+#[derive(Debug, Clone, Copy)]
+pub struct MissingPrincipal(&'static str);
+
+impl IntoResponse for MissingPrincipal {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .detail(format!(
+                "Missing request extension: `Authenticated<{}>`. Is the route behind an `AuthLayer`?",
+                self.0
+            ))
+            .into_rejection()
+    }
+}
+
+/// Rejection used by [`BasicAuth`], [`BearerToken`] and [`ApiKey`].
+///
+/// Responds with `401 Unauthorized` and a `WWW-Authenticate` challenge for the
+/// scheme. Validators can also return it to reject credentials that were sent,
+/// but are not accepted.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthRejection {
+    scheme: Scheme,
+    reason: Reason,
+}
+
+impl AuthRejection {
+    /// Reject Basic credentials.
+    pub fn basic() -> Self {
+        Self {
+            scheme: Scheme::Basic,
+            reason: Reason::Invalid,
+        }
+    }
+
+    /// Reject a bearer token, adding `error="invalid_token"` to the challenge.
+    pub fn bearer() -> Self {
+        Self {
+            scheme: Scheme::Bearer,
+            reason: Reason::Invalid,
+        }
+    }
+
+    /// Reject an API key.
+    pub fn api_key() -> Self {
+        Self {
+            scheme: Scheme::ApiKey,
+            reason: Reason::Invalid,
+        }
+    }
+
+    fn challenge(&self) -> HeaderValue {
+        HeaderValue::from_static(match (self.scheme, self.reason) {
+            (Scheme::Basic, _) => "Basic realm=\"Restricted\", charset=\"UTF-8\"",
+            (Scheme::Bearer, Reason::Invalid) => "Bearer error=\"invalid_token\"",
+            (Scheme::Bearer, _) => "Bearer",
+            (Scheme::ApiKey, _) => "ApiKey header=\"X-API-Key\"",
+        })
+    }
+
+    fn detail(&self) -> &'static str {
+        match (self.scheme, self.reason) {
+            (Scheme::ApiKey, Reason::Missing) => "Missing `X-API-Key` header",
+            (Scheme::ApiKey, Reason::Malformed) => "Invalid `X-API-Key` header",
+            (_, Reason::Missing) => "Missing `Authorization` header",
+            (_, Reason::OtherScheme) => "Unsupported authentication scheme",
+            (_, Reason::Malformed) => "Invalid `Authorization` header",
+            (_, Reason::Invalid) => "Invalid credentials",
+        }
+    }
+}
+
+impl IntoResponse for AuthRejection {
+    fn into_response(self) -> HttpResponse {
+        let mut res = Problem::new(StatusCode::UNAUTHORIZED)
+            .detail(self.detail())
+            .into_rejection();
+        res.headers_mut().insert(WWW_AUTHENTICATE, self.challenge());
+        res
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Basic,
+    Bearer,
+    ApiKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reason {
+    Missing,
+    OtherScheme,
+    Malformed,
+    Invalid,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    #[monoio::test]
+    async fn extracts_credentials() {
+        let app = Router::new()
+            .route(
+                "/basic",
+                get(
+                    |auth: BasicAuth| async move { format!("{}:{}", auth.username, auth.password) },
+                ),
+            )
+            .route(
+                "/bearer",
+                get(|BearerToken(token): BearerToken| async move { token }),
+            )
+            .route("/key", get(|ApiKey(key): ApiKey| async move { key }));
+        let client = TestClient::new(app);
+
+        client
+            .get("/basic")
+            .header(AUTHORIZATION, "Basic ZmVycmlzOmNyYWI=")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("ferris:crab");
+        client
+            .get("/basic")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED)
+            .assert_header(
+                WWW_AUTHENTICATE,
+                "Basic realm=\"Restricted\", charset=\"UTF-8\"",
+            );
+        client
+            .get("/bearer")
+            .header(AUTHORIZATION, "Bearer abc")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("abc");
+        client
+            .get("/bearer")
+            .header(AUTHORIZATION, "Basic ZmVycmlzOmNyYWI=")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+        client
+            .get("/key")
+            .header(X_API_KEY, "k1")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("k1");
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode, header};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+
+use crate::{
+    BoxError, HttpRequest, HttpResponse, IntoResponse, Problem, middleware::body_limit::BodyLimit,
+};
+
+/// Read the whole request body into memory, up to the limit set with
+/// [`DefaultBodyLimit`](crate::middleware::DefaultBodyLimit).
+pub(crate) async fn buffer(req: HttpRequest) -> Result<Bytes, BodyRejection> {
+    let Some(limit) = BodyLimit::of(&req) else {
+        return collect(req.into_body()).await;
+    };
+
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+    if content_length.is_some_and(|length| length > limit as u64) {
+        return Err(BodyRejection::TooLarge { limit });
+    }
+
+    collect(Limited::new(req.into_body(), limit))
+        .await
+        .map_err(|rejection| match rejection {
+            BodyRejection::Failed(err) if err.is::<LengthLimitError>() => {
+                BodyRejection::TooLarge { limit }
+            }
+            rejection => rejection,
+        })
+}
+
+async fn collect<B>(body: B) -> Result<Bytes, BodyRejection>
+where
+    B: http_body::Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    body.collect()
+        .await
+        .map(|collected| collected.to_bytes())
+        .map_err(|err| BodyRejection::Failed(err.into()))
+}
+
+/// Whether the `Content-Type` of the request is `{type_}/{subtype}`, or
+/// `{type_}/*+{subtype}` such as `application/problem+json`, ignoring parameters
+/// like the charset.
+pub(crate) fn has_content_type(headers: &HeaderMap, type_: &str, subtype: &str) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    let Some((actual_type, actual_subtype)) = essence.split_once('/') else {
+        return false;
+    };
+
+    actual_type.eq_ignore_ascii_case(type_)
+        && (actual_subtype.eq_ignore_ascii_case(subtype)
+            || actual_subtype
+                .rsplit_once('+')
+                .is_some_and(|(_, suffix)| suffix.eq_ignore_ascii_case(subtype)))
+}
+
+/// Rejection used by extractors that read the whole request body, when it can't
+/// be read.
+#[derive(Debug)]
+pub enum BodyRejection {
+    /// The body failed while it was being read, e.g. the client disconnected.
+    Failed(BoxError),
+    /// The body is larger than the limit set with
+    /// [`DefaultBodyLimit`](crate::middleware::DefaultBodyLimit).
+    TooLarge { limit: usize },
+}
+
+impl fmt::Display for BodyRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(err) => write!(f, "Failed to buffer the request body: {err}"),
+            Self::TooLarge { limit } => {
+                write!(
+                    f,
+                    "The request body is larger than the limit of {limit} bytes"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BodyRejection {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Failed(err) => Some(&**err),
+            Self::TooLarge { .. } => None,
+        }
+    }
+}
+
+impl IntoResponse for BodyRejection {
+    fn into_response(self) -> HttpResponse {
+        let status = match &self {
+            Self::Failed(_) => StatusCode::BAD_REQUEST,
+            Self::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        };
+        Problem::new(status)
+            .detail(self.to_string())
+            .into_rejection()
+    }
+}
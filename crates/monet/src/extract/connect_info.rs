@@ -0,0 +1,273 @@
+use std::{
+    convert::Infallible,
+    marker::PhantomData,
+    net::SocketAddr,
+    ops::{Deref, DerefMut},
+    task::{Context, Poll},
+};
+
+use http::{StatusCode, request::Parts};
+
+use crate::{
+    HttpRequest, HttpResponse, IntoResponse, Problem, TowerService,
+    extract::FromRequestParts,
+    serve::{IncomingStream, Listener, UnixPeer},
+};
+
+/// Extractor for information about the connection a request came in on, such as
+/// the client's [`SocketAddr`] or, over Unix domain sockets, its
+/// [`UCred`](monoio::net::unix::UCred).
+///
+/// Requires the app to be served with
+/// [`Router::into_make_service_with_connect_info`](crate::Router::into_make_service_with_connect_info).
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectInfo<T>(pub T);
+
+impl<T> Deref for ConnectInfo<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for ConnectInfo<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S, T> FromRequestParts<S> for ConnectInfo<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    type Rejection = MissingConnectInfo;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or(MissingConnectInfo(std::any::type_name::<T>()))
+    }
+}
+
+/// Rejection used by [`ConnectInfo`] when no connection info of the requested type
+/// was recorded for the connection.
+#[derive(Debug, Clone, Copy)]
+pub struct MissingConnectInfo(pub(crate) &'static str);
+
+impl IntoResponse for MissingConnectInfo {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .detail(format!(
+                "Missing request extension: `ConnectInfo<{}>`. Is the app served with `Router::into_make_service_with_connect_info`?",
+                self.0
+            ))
+            .into_rejection()
+    }
+}
+
+/// Types that can be derived from an accepted connection and handed to handlers
+/// through [`ConnectInfo`].
+pub trait Connected<T>: Clone + Send + Sync + 'static {
+    fn connect_info(target: T) -> Self;
+}
+
+impl<L> Connected<IncomingStream<'_, L>> for SocketAddr
+where
+    L: Listener<Addr = SocketAddr>,
+{
+    fn connect_info(target: IncomingStream<'_, L>) -> Self {
+        *target.remote_addr()
+    }
+}
+
+impl<L> Connected<IncomingStream<'_, L>> for UnixPeer
+where
+    L: Listener<Addr = UnixPeer>,
+{
+    fn connect_info(target: IncomingStream<'_, L>) -> Self {
+        target.remote_addr().clone()
+    }
+}
+
+impl<L> Connected<IncomingStream<'_, L>> for monoio::net::unix::UCred
+where
+    L: Listener<Addr = UnixPeer>,
+{
+    fn connect_info(target: IncomingStream<'_, L>) -> Self {
+        target
+            .remote_addr()
+            .cred
+            .expect("accepted unix connections always carry peer credentials")
+    }
+}
+
+impl Connected<IncomingStream<'_, monoio::net::UnixListener>> for monoio::net::unix::SocketAddr {
+    fn connect_info(target: IncomingStream<'_, monoio::net::UnixListener>) -> Self {
+        target.remote_addr().clone()
+    }
+}
+
+/// A make-service that records [`ConnectInfo`] for every accepted connection.
+pub struct IntoMakeServiceWithConnectInfo<S, C> {
+    svc: S,
+    _connect_info: PhantomData<fn() -> C>,
+}
+
+impl<S, C> IntoMakeServiceWithConnectInfo<S, C> {
+    pub(crate) fn new(svc: S) -> Self {
+        Self {
+            svc,
+            _connect_info: PhantomData,
+        }
+    }
+}
+
+impl<S, C> Clone for IntoMakeServiceWithConnectInfo<S, C>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.svc.clone())
+    }
+}
+
+impl<S, C> std::fmt::Debug for IntoMakeServiceWithConnectInfo<S, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntoMakeServiceWithConnectInfo")
+            .field("connect_info", &std::any::type_name::<C>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, L, S, C> TowerService<IncomingStream<'a, L>> for IntoMakeServiceWithConnectInfo<S, C>
+where
+    L: Listener,
+    S: Clone,
+    C: Connected<IncomingStream<'a, L>>,
+{
+    type Response = AddConnectInfo<S, C>;
+
+    type Error = Infallible;
+
+    type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, target: IncomingStream<'a, L>) -> Self::Future {
+        let connect_info = ConnectInfo(C::connect_info(target));
+        std::future::ready(Ok(AddConnectInfo {
+            inner: self.svc.clone(),
+            connect_info,
+        }))
+    }
+}
+
+/// Inserts the [`ConnectInfo`] of the connection into every request.
+#[derive(Debug, Clone)]
+pub struct AddConnectInfo<S, C> {
+    inner: S,
+    connect_info: ConnectInfo<C>,
+}
+
+impl<S, C, B> TowerService<HttpRequest<B>> for AddConnectInfo<S, C>
+where
+    S: TowerService<HttpRequest<B>>,
+    C: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+
+    type Error = S::Error;
+
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: HttpRequest<B>) -> Self::Future {
+        req.extensions_mut().insert(self.connect_info.clone());
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use monoio::{
+        io::{AsyncReadRent, AsyncWriteRentExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+    use crate::{Router, get, serve, test::TestClient};
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|ConnectInfo(addr): ConnectInfo<SocketAddr>| async move { addr.to_string() }),
+            )
+            .route(
+                "/optional",
+                get(
+                    |info: Result<ConnectInfo<SocketAddr>, MissingConnectInfo>| async move {
+                        match info {
+                            Ok(ConnectInfo(addr)) => addr.to_string(),
+                            Err(_) => "unknown".to_owned(),
+                        }
+                    },
+                ),
+            )
+    }
+
+    #[monoio::test]
+    async fn is_missing_without_connect_info_service() {
+        let client = TestClient::new(app());
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        client
+            .get("/optional")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("unknown");
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn records_the_peer_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        monoio::spawn(
+            serve(
+                listener,
+                app().into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .into_future(),
+        );
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let local_addr = stream.local_addr().unwrap();
+        let request = "GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+        let (res, _) = stream.write_all(request.as_bytes().to_vec()).await;
+        res.unwrap();
+
+        let mut response = Vec::new();
+        loop {
+            let (res, buf) = stream.read(Vec::with_capacity(1024)).await;
+            if res.unwrap() == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf);
+        }
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with(&local_addr.to_string()), "{response}");
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use http::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    HttpRequest, HttpResponse, IntoResponse, Problem,
+    extract::{
+        FromRequest,
+        body::{self, BodyRejection},
+    },
+};
+
+/// URL encoded form extractor.
+///
+/// `GET` and `HEAD` requests are deserialized from the query string, any other
+/// request from its body, which must have `Content-Type:
+/// application/x-www-form-urlencoded` or is rejected with `415 Unsupported Media
+/// Type`. A body that doesn't deserialize into `T` is rejected with `422
+/// Unprocessable Content`, a query string with `400 Bad Request`.
+///
+/// ```no_run
+/// # use monet::extract::form::Form;
+/// #[derive(serde::Deserialize)]
+/// struct SignUp {
+///     username: String,
+///     password: String,
+/// }
+///
+/// async fn sign_up(Form(form): Form<SignUp>) -> String {
+///     format!("welcome, {}", form.username)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Form<T>(pub T);
+
+impl<T> Deref for Form<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Form<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S, T> FromRequest<S> for Form<T>
+where
+    T: DeserializeOwned,
+{
+    type Rejection = FormRejection;
+
+    async fn from_request(req: HttpRequest, _state: &S) -> Result<Self, Self::Rejection> {
+        if req.method() == Method::GET || req.method() == Method::HEAD {
+            let query = req.uri().query().unwrap_or_default();
+            return deserialize(query.as_bytes())
+                .map(Self)
+                .map_err(FormRejection::FailedToDeserializeForm);
+        }
+
+        if !body::has_content_type(req.headers(), "application", "x-www-form-urlencoded") {
+            return Err(FormRejection::InvalidFormContentType);
+        }
+        let bytes = body::buffer(req).await?;
+        deserialize(&bytes)
+            .map(Self)
+            .map_err(FormRejection::FailedToDeserializeFormBody)
+    }
+}
+
+fn deserialize<T: DeserializeOwned>(input: &[u8]) -> Result<T, String> {
+    let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(input));
+    serde_path_to_error::deserialize(deserializer).map_err(|err| err.to_string())
+}
+
+/// Rejection used by [`Form`].
+#[derive(Debug)]
+pub enum FormRejection {
+    /// The request doesn't have `Content-Type: application/x-www-form-urlencoded`.
+    InvalidFormContentType,
+    /// The body couldn't be read.
+    Body(BodyRejection),
+    /// The query string of a `GET` or `HEAD` request doesn't deserialize into the
+    /// expected type.
+    FailedToDeserializeForm(String),
+    /// The body doesn't deserialize into the expected type.
+    FailedToDeserializeFormBody(String),
+}
+
+impl From<BodyRejection> for FormRejection {
+    fn from(rejection: BodyRejection) -> Self {
+        Self::Body(rejection)
+    }
+}
+
+impl fmt::Display for FormRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFormContentType => f.write_str(
+                "Form requests must have `Content-Type: application/x-www-form-urlencoded`",
+            ),
+            Self::Body(rejection) => rejection.fmt(f),
+            Self::FailedToDeserializeForm(err) => write!(f, "Failed to deserialize form: {err}"),
+            Self::FailedToDeserializeFormBody(err) => {
+                write!(f, "Failed to deserialize form body: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormRejection {}
+
+impl IntoResponse for FormRejection {
+    fn into_response(self) -> HttpResponse {
+        let status = match self {
+            Self::InvalidFormContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Body(rejection) => return rejection.into_response(),
+            Self::FailedToDeserializeForm(_) => StatusCode::BAD_REQUEST,
+            Self::FailedToDeserializeFormBody(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        Problem::new(status)
+            .detail(self.to_string())
+            .into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{Router, test::TestClient};
+
+    #[derive(Deserialize)]
+    struct User {
+        name: String,
+        age: u8,
+    }
+
+    fn client() -> TestClient {
+        TestClient::new(Router::new().fallback(|Form(user): Form<User>| async move {
+            format!("{} {}", user.name, user.age)
+        }))
+    }
+
+    #[monoio::test]
+    async fn reads_the_query_of_get_requests() {
+        let client = client();
+        client
+            .get("/?name=ferris&age=7")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("ferris 7");
+        client
+            .get("/?name=ferris&age=old")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[monoio::test]
+    async fn reads_the_body_of_other_requests() {
+        let client = client();
+        client
+            .post("/?name=ignored&age=0")
+            .form(&[("name", "ferris"), ("age", "7")])
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("ferris 7");
+        client
+            .post("/")
+            .form(&[("name", "ferris"), ("age", "old")])
+            .send()
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        client
+            .post("/")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body("name=ferris&age=7")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,45 @@
+/// Produce a value from a reference to another, typically a part of the
+/// application state from the whole of it.
+///
+/// [`State<T>`](crate::State) extracts any `T: FromRef<S>` from a router with
+/// state `S`, so handlers can ask for only the part they need. Every `Clone` type
+/// can be produced from itself; implementations for the fields of a state struct
+/// can be derived with `#[derive(FromRef)]`, which needs the `macros` feature.
+///
+/// ```no_run
+/// # use std::rc::Rc;
+/// # use monet::{FromRef, Router, State, get};
+/// # #[derive(Clone)]
+/// # struct DbPool;
+/// # struct Config;
+/// #[derive(Clone, FromRef)]
+/// struct AppState {
+///     db: DbPool,
+///     config: Rc<Config>,
+///     #[from_ref(skip)]
+///     secret: String,
+/// }
+///
+/// async fn handler(State(config): State<Rc<Config>>) {}
+///
+/// # let state = AppState { db: DbPool, config: Rc::new(Config), secret: String::new() };
+/// let app: Router = Router::new()
+///     .route("/", get(handler))
+///     .with_state(state);
+/// ```
+pub trait FromRef<T> {
+    /// Converts to this type from a reference to the input type.
+    fn from_ref(input: &T) -> Self;
+}
+
+impl<T> FromRef<T> for T
+where
+    T: Clone,
+{
+    fn from_ref(input: &T) -> Self {
+        input.clone()
+    }
+}
+
+#[cfg(feature = "macros")]
+pub use monet_macros::FromRef;
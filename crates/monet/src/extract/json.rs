@@ -0,0 +1,241 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use http::{HeaderValue, StatusCode, header};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    HttpRequest, HttpResponse, IntoResponse, Problem,
+    extract::{
+        FromRequest,
+        body::{self, BodyRejection},
+    },
+};
+
+/// JSON extractor and response.
+///
+/// As an extractor, it reads the whole request body and deserializes it into `T`.
+/// Requests are rejected with `415 Unsupported Media Type` unless their
+/// `Content-Type` is `application/json`, or another JSON type such as
+/// `application/merge-patch+json`, with `400 Bad Request` when the body isn't
+/// valid JSON, and with `422 Unprocessable Content` when it is, but doesn't match
+/// `T`.
+///
+/// As a response, it serializes `T` with `Content-Type: application/json`.
+///
+/// ```no_run
+/// # use monet::Json;
+/// # #[derive(serde::Deserialize, serde::Serialize)]
+/// # struct User { name: String }
+/// async fn rename(Json(mut user): Json<User>) -> Json<User> {
+///     user.name = user.name.to_uppercase();
+///     Json(user)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S, T> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+{
+    type Rejection = JsonRejection;
+
+    async fn from_request(req: HttpRequest, _state: &S) -> Result<Self, Self::Rejection> {
+        if !body::has_content_type(req.headers(), "application", "json") {
+            return Err(JsonRejection::MissingJsonContentType);
+        }
+        let bytes = body::buffer(req).await?;
+
+        let deserializer = &mut serde_json::Deserializer::from_slice(&bytes);
+        let value = serde_path_to_error::deserialize(deserializer).map_err(|err| {
+            let message = err.to_string();
+            match err.inner().classify() {
+                serde_json::error::Category::Data => JsonRejection::JsonDataError(message),
+                _ => JsonRejection::JsonSyntaxError(message),
+            }
+        })?;
+        Ok(Self(value))
+    }
+}
+
+impl<T> IntoResponse for Json<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> HttpResponse {
+        match serde_json::to_string(&self.0) {
+            Ok(json) => {
+                let mut res = json.into_response();
+                res.headers_mut().insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+                res
+            }
+            Err(err) => Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .detail(format!("Failed to serialize the JSON response: {err}"))
+                .into_rejection(),
+        }
+    }
+}
+
+/// Rejection used by [`Json`].
+#[derive(Debug)]
+pub enum JsonRejection {
+    /// The request doesn't have a JSON `Content-Type`.
+    MissingJsonContentType,
+    /// The body couldn't be read.
+    Body(BodyRejection),
+    /// The body isn't syntactically valid JSON.
+    JsonSyntaxError(String),
+    /// The body is valid JSON, but doesn't deserialize into the expected type.
+    JsonDataError(String),
+}
+
+impl From<BodyRejection> for JsonRejection {
+    fn from(rejection: BodyRejection) -> Self {
+        Self::Body(rejection)
+    }
+}
+
+impl fmt::Display for JsonRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingJsonContentType => {
+                f.write_str("Expected request with `Content-Type: application/json`")
+            }
+            Self::Body(rejection) => rejection.fmt(f),
+            Self::JsonSyntaxError(err) => {
+                write!(f, "Failed to parse the request body as JSON: {err}")
+            }
+            Self::JsonDataError(err) => {
+                write!(
+                    f,
+                    "Failed to deserialize the JSON body into the target type: {err}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonRejection {}
+
+impl IntoResponse for JsonRejection {
+    fn into_response(self) -> HttpResponse {
+        let status = match self {
+            Self::MissingJsonContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Body(rejection) => return rejection.into_response(),
+            Self::JsonSyntaxError(_) => StatusCode::BAD_REQUEST,
+            Self::JsonDataError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        Problem::new(status)
+            .detail(self.to_string())
+            .into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Router, test::TestClient};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct User {
+        name: String,
+        age: u8,
+    }
+
+    fn client() -> TestClient {
+        TestClient::new(Router::new().fallback(|Json(user): Json<User>| async move { Json(user) }))
+    }
+
+    #[monoio::test]
+    async fn round_trips() {
+        let user = User {
+            name: "ferris".to_owned(),
+            age: 7,
+        };
+        client()
+            .post("/")
+            .json(&user)
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_header(header::CONTENT_TYPE, "application/json")
+            .assert_json(&user);
+    }
+
+    #[monoio::test]
+    async fn accepts_json_subtypes() {
+        client()
+            .post("/")
+            .header(
+                header::CONTENT_TYPE,
+                "application/merge-patch+json; charset=utf-8",
+            )
+            .body(r#"{"name":"ferris","age":7}"#)
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+    }
+
+    #[monoio::test]
+    async fn rejects_other_content_types() {
+        let client = client();
+        for content_type in [None, Some("text/plain"), Some("application/jsonx")] {
+            let mut req = client.post("/").body(r#"{"name":"ferris","age":7}"#);
+            if let Some(content_type) = content_type {
+                req = req.header(header::CONTENT_TYPE, content_type);
+            }
+            req.send()
+                .await
+                .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+    }
+
+    #[monoio::test]
+    async fn tells_syntax_errors_from_data_errors() {
+        let client = client();
+        client
+            .post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body("{")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        let res = client
+            .post("/")
+            .json(&json!({ "name": "ferris", "age": "seven" }))
+            .send()
+            .await;
+        res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(res.text().contains("age"), "{}", res.text());
+    }
+
+    #[test]
+    fn serializes_responses() {
+        let res = Json(json!({ "a": [1, 2] })).into_response();
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/json");
+    }
+}
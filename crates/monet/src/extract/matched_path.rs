@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use http::{StatusCode, request::Parts};
+
+use crate::{HttpResponse, IntoResponse, Problem, extract::FromRequestParts};
+
+/// The route template the request matched, such as `/users/{id}`, as opposed to
+/// the concrete path in the URI.
+///
+/// Recorded by the [`Router`](crate::Router) before a route is called, so
+/// middleware added with [`Router::layer`](crate::Router::layer) can read it from
+/// the extensions as well. Services mounted with
+/// [`Router::nest_service`](crate::Router::nest_service) see the path they are
+/// nested at, and routers nested in them report the joined path.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MatchedPath(pub(crate) Arc<str>);
+
+impl MatchedPath {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for MatchedPath {
+    type Rejection = MatchedPathMissing;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or(MatchedPathMissing)
+    }
+}
+
+/// Rejection used by [`MatchedPath`] when the request wasn't routed by a
+/// [`Router`](crate::Router), for example in a fallback.
+#[derive(Debug, Clone, Copy)]
+pub struct MatchedPathMissing;
+
+impl IntoResponse for MatchedPathMissing {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .detail("No matched path found")
+            .into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    #[monoio::test]
+    async fn reports_the_route_template() {
+        let app = Router::new()
+            .route(
+                "/users/{id}",
+                get(|path: MatchedPath| async move { path.as_str().to_owned() }),
+            )
+            .fallback(|path: Result<MatchedPath, MatchedPathMissing>| async move {
+                path.map(|path| path.as_str().to_owned())
+                    .unwrap_or_else(|_| "none".to_owned())
+            });
+        let client = TestClient::new(app);
+
+        client
+            .get("/users/42")
+            .send()
+            .await
+            .assert_text("/users/{id}");
+        client.get("/missing").send().await.assert_text("none");
+    }
+
+    #[monoio::test]
+    async fn is_missing_in_the_fallback() {
+        let app = Router::new().fallback(|_: MatchedPath| async {});
+
+        TestClient::new(app)
+            .get("/anything")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,878 @@
+use std::{error::Error, io, path::Path};
+
+use bytes::{Buf, Bytes, BytesMut};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
+use http_body_util::BodyExt;
+use memchr::memmem;
+use monoio::fs::File;
+
+use crate::{
+    Body, BoxError, HttpRequest, HttpResponse, IntoResponse, Problem,
+    extract::FromRequest,
+    middleware::{DEFAULT_BODY_LIMIT, body_limit::BodyLimit},
+};
+
+/// Part headers larger than this are rejected.
+const MAX_HEADERS_SIZE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 32;
+/// Whitespace allowed after a boundary, before its line break.
+const MAX_TRANSPORT_PADDING: usize = 1024;
+
+/// Extractor that parses `multipart/form-data` requests, as sent by HTML forms
+/// with file inputs.
+///
+/// The body is parsed as it arrives: fields are handed out one at a time, and
+/// their data in chunks as they come off the wire, so a field can be streamed to
+/// disk with [`Field::save`] without ever holding the whole upload in memory.
+///
+/// The whole body is limited to the size set with
+/// [`DefaultBodyLimit`](crate::middleware::DefaultBodyLimit), which can be changed
+/// for one handler with [`Multipart::max_total_size`], and single fields with
+/// [`Multipart::max_field_size`]. Without a field limit, [`Field::bytes`] and
+/// [`Field::text`] still refuse to buffer more than [`DEFAULT_BODY_LIMIT`] bytes.
+///
+/// ```no_run
+/// # use monet::extract::multipart::{Multipart, MultipartError};
+/// async fn upload(multipart: Multipart) -> Result<String, MultipartError> {
+///     let mut multipart = multipart.max_field_size(1 << 30);
+///     let mut saved = 0;
+///     while let Some(field) = multipart.next_field().await? {
+///         if field.name() == Some("file") {
+///             saved += field.save("/tmp/upload").await?;
+///         }
+///     }
+///     Ok(format!("saved {saved} bytes"))
+/// }
+/// ```
+pub struct Multipart {
+    body: Body,
+    buf: BytesMut,
+    /// `--boundary`, which opens the first part.
+    dash_boundary: Box<[u8]>,
+    /// `\r\n--boundary`, which ends a part.
+    delimiter: Box<[u8]>,
+    state: State,
+    max_field_size: Option<u64>,
+    max_total_size: Option<u64>,
+    total: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Preamble,
+    /// Right after a boundary, either the closing `--` or a new part follows.
+    Boundary,
+    Headers,
+    Body {
+        read: u64,
+    },
+    Done,
+    Failed,
+}
+
+impl<S> FromRequest<S> for Multipart {
+    type Rejection = MultipartRejection;
+
+    async fn from_request(req: HttpRequest, _state: &S) -> Result<Self, Self::Rejection> {
+        let boundary = boundary(req.headers()).ok_or(MultipartRejection::InvalidBoundary)?;
+        let limit = BodyLimit::of(&req);
+        let mut multipart = Self::new(req.into_body(), &boundary);
+        multipart.max_total_size = limit.map(|limit| limit as u64);
+        Ok(multipart)
+    }
+}
+
+impl Multipart {
+    fn new(body: Body, boundary: &str) -> Self {
+        let dash_boundary = [b"--", boundary.as_bytes()].concat();
+        let delimiter = [b"\r\n", &dash_boundary[..]].concat();
+        Self {
+            body,
+            buf: BytesMut::new(),
+            dash_boundary: dash_boundary.into(),
+            delimiter: delimiter.into(),
+            state: State::Preamble,
+            max_field_size: None,
+            max_total_size: Some(DEFAULT_BODY_LIMIT as u64),
+            total: 0,
+        }
+    }
+
+    /// Fail with [`MultipartError::FieldTooLarge`] once a single field's data
+    /// exceeds `limit` bytes.
+    pub fn max_field_size(mut self, limit: u64) -> Self {
+        self.max_field_size = Some(limit);
+        self
+    }
+
+    /// Fail with [`MultipartError::StreamTooLarge`] once the request body
+    /// exceeds `limit` bytes, instead of the limit set with
+    /// [`DefaultBodyLimit`](crate::middleware::DefaultBodyLimit).
+    pub fn max_total_size(mut self, limit: u64) -> Self {
+        self.max_total_size = Some(limit);
+        self
+    }
+
+    /// The next field, or `None` once all have been read. Whatever is left of the
+    /// previous field's data is skipped.
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_>>, MultipartError> {
+        match self.next_headers().await {
+            Ok(Some(headers)) => Ok(Some(Field::new(self, headers))),
+            Ok(None) => Ok(None),
+            Err(err) => {
+                self.state = State::Failed;
+                Err(err)
+            }
+        }
+    }
+
+    async fn next_headers(&mut self) -> Result<Option<HeaderMap>, MultipartError> {
+        loop {
+            match self.state {
+                State::Body { .. } => while self.read_chunk().await?.is_some() {},
+                State::Preamble => {
+                    if let Some(pos) = memmem::find(&self.buf, &self.dash_boundary) {
+                        self.buf.advance(pos + self.dash_boundary.len());
+                        self.state = State::Boundary;
+                        continue;
+                    }
+                    // Only a partial boundary at the very end can matter.
+                    let keep = self.dash_boundary.len() - 1;
+                    if self.buf.len() > keep {
+                        self.buf.advance(self.buf.len() - keep);
+                    }
+                    self.fill_or_incomplete().await?;
+                }
+                State::Boundary => {
+                    if self.buf.starts_with(b"--") {
+                        // Anything after the closing boundary is an epilogue to ignore.
+                        self.state = State::Done;
+                        return Ok(None);
+                    }
+                    let Some(pos) = memmem::find(&self.buf, b"\r\n") else {
+                        if self.buf.len() > MAX_TRANSPORT_PADDING {
+                            return Err(MultipartError::Malformed("invalid boundary line"));
+                        }
+                        self.fill_or_incomplete().await?;
+                        continue;
+                    };
+                    if !self.buf[..pos].iter().all(|b| *b == b' ' || *b == b'\t') {
+                        return Err(MultipartError::Malformed("invalid boundary line"));
+                    }
+                    self.buf.advance(pos + 2);
+                    self.state = State::Headers;
+                }
+                State::Headers => {
+                    let end = if self.buf.starts_with(b"\r\n") {
+                        Some(2)
+                    } else {
+                        memmem::find(&self.buf, b"\r\n\r\n").map(|pos| pos + 4)
+                    };
+                    let Some(end) = end else {
+                        if self.buf.len() > MAX_HEADERS_SIZE {
+                            return Err(MultipartError::HeadersTooLarge);
+                        }
+                        self.fill_or_incomplete().await?;
+                        continue;
+                    };
+                    if end > MAX_HEADERS_SIZE {
+                        return Err(MultipartError::HeadersTooLarge);
+                    }
+
+                    let raw = self.buf.split_to(end);
+                    let headers = parse_headers(&raw)?;
+                    self.state = State::Body { read: 0 };
+                    return Ok(Some(headers));
+                }
+                State::Done => return Ok(None),
+                State::Failed => {
+                    return Err(MultipartError::Malformed(
+                        "the multipart stream already failed",
+                    ));
+                }
+            }
+        }
+    }
+
+    /// The next chunk of the current field's data.
+    async fn read_chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        loop {
+            let State::Body { read } = self.state else {
+                return Ok(None);
+            };
+
+            let chunk = match memmem::find(&self.buf, &self.delimiter) {
+                Some(0) => {
+                    self.buf.advance(self.delimiter.len());
+                    self.state = State::Boundary;
+                    return Ok(None);
+                }
+                Some(pos) => self.buf.split_to(pos).freeze(),
+                None => {
+                    // Hold back what could be the start of a delimiter.
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() <= keep {
+                        self.fill_or_incomplete().await?;
+                        continue;
+                    }
+                    self.buf.split_to(self.buf.len() - keep).freeze()
+                }
+            };
+
+            let read = read + chunk.len() as u64;
+            if let Some(limit) = self.max_field_size.filter(|limit| read > *limit) {
+                return Err(MultipartError::FieldTooLarge { limit });
+            }
+            self.state = State::Body { read };
+            return Ok(Some(chunk));
+        }
+    }
+
+    /// Read more of the body into the buffer, failing if it ended.
+    async fn fill_or_incomplete(&mut self) -> Result<(), MultipartError> {
+        loop {
+            let Some(frame) = self.body.frame().await else {
+                return Err(MultipartError::Incomplete);
+            };
+            let Ok(data) = frame.map_err(MultipartError::Body)?.into_data() else {
+                continue;
+            };
+
+            self.total += data.len() as u64;
+            if let Some(limit) = self.max_total_size.filter(|limit| self.total > *limit) {
+                return Err(MultipartError::StreamTooLarge { limit });
+            }
+            self.buf.extend_from_slice(&data);
+            return Ok(());
+        }
+    }
+}
+
+impl std::fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Multipart")
+            .field("state", &self.state)
+            .field("max_field_size", &self.max_field_size)
+            .field("max_total_size", &self.max_total_size)
+            .finish_non_exhaustive()
+    }
+}
+
+fn parse_headers(raw: &[u8]) -> Result<HeaderMap, MultipartError> {
+    let mut parsed = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let headers = match httparse::parse_headers(raw, &mut parsed) {
+        Ok(httparse::Status::Complete((_, headers))) => headers,
+        Ok(httparse::Status::Partial) | Err(_) => {
+            return Err(MultipartError::Malformed("invalid part headers"));
+        }
+    };
+
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for header in headers {
+        let name = HeaderName::from_bytes(header.name.as_bytes())
+            .map_err(|_| MultipartError::Malformed("invalid part header name"))?;
+        let value = HeaderValue::from_bytes(header.value)
+            .map_err(|_| MultipartError::Malformed("invalid part header value"))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+/// The boundary of a `multipart/form-data` content type.
+fn boundary(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let (essence, params) = content_type.split_once(';')?;
+    if !essence.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    param(params, "boundary").filter(|boundary| (1..=70).contains(&boundary.len()))
+}
+
+/// Look up `name` in a `; key=value; key="quoted value"` parameter list.
+fn param(params: &str, name: &str) -> Option<String> {
+    let mut rest = params;
+    loop {
+        rest = rest.trim_start_matches([';', ' ', '\t']);
+        if rest.is_empty() {
+            return None;
+        }
+        let (key, after) = rest.split_once('=')?;
+        let after = after.trim_start();
+
+        let value = if let Some(quoted) = after.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                    '"' => {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            rest = &quoted[end?..];
+            value
+        } else {
+            let end = after.find(';').unwrap_or(after.len());
+            rest = &after[end..];
+            after[..end].trim_end().to_owned()
+        };
+
+        if key.trim().eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+}
+
+/// A single field of a [`Multipart`] body.
+#[derive(Debug)]
+pub struct Field<'a> {
+    multipart: &'a mut Multipart,
+    headers: HeaderMap,
+    name: Option<String>,
+    file_name: Option<String>,
+}
+
+impl<'a> Field<'a> {
+    fn new(multipart: &'a mut Multipart, headers: HeaderMap) -> Self {
+        let disposition = headers
+            .get(header::CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(';'))
+            .map(|(_, params)| params)
+            .unwrap_or_default();
+
+        let name = param(disposition, "name");
+        let file_name = param(disposition, "filename*")
+            .and_then(|value| decode_ext_value(&value))
+            .or_else(|| param(disposition, "filename"));
+
+        Self {
+            multipart,
+            headers,
+            name,
+            file_name,
+        }
+    }
+
+    /// The `name` the form gave the field.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The file name the client sent for a file field. It comes straight from the
+    /// client; don't use it as a path without sanitizing it.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The next chunk of the field's data, `None` at its end.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        let chunk = self.multipart.read_chunk().await;
+        if chunk.is_err() {
+            self.multipart.state = State::Failed;
+        }
+        chunk
+    }
+
+    /// Buffer the whole field in memory, up to the limit set with
+    /// [`Multipart::max_field_size`], or [`DEFAULT_BODY_LIMIT`] bytes.
+    pub async fn bytes(mut self) -> Result<Bytes, MultipartError> {
+        let limit = self
+            .multipart
+            .max_field_size
+            .unwrap_or(DEFAULT_BODY_LIMIT as u64);
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            if (buf.len() + chunk.len()) as u64 > limit {
+                return Err(MultipartError::FieldTooLarge { limit });
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf.freeze())
+    }
+
+    /// Buffer the whole field in memory as UTF-8 text, with the same limit as
+    /// [`Field::bytes`].
+    pub async fn text(self) -> Result<String, MultipartError> {
+        let bytes = self.bytes().await?;
+        String::from_utf8(bytes.into()).map_err(|_| MultipartError::InvalidUtf8)
+    }
+
+    /// Stream the field into `file` with positioned writes, starting at offset
+    /// `pos`. Returns the number of bytes written.
+    pub async fn write_to_file(mut self, file: &File, pos: u64) -> Result<u64, MultipartError> {
+        let mut written = 0;
+        while let Some(chunk) = self.chunk().await? {
+            let len = chunk.len() as u64;
+            let (res, _) = file.write_all_at(chunk, pos + written).await;
+            if let Err(err) = res {
+                self.multipart.state = State::Failed;
+                return Err(MultipartError::Io(err));
+            }
+            written += len;
+        }
+        Ok(written)
+    }
+
+    /// Stream the field into a new file at `path`, truncating any existing one.
+    /// Returns the number of bytes written.
+    pub async fn save(self, path: impl AsRef<Path>) -> Result<u64, MultipartError> {
+        let file = File::create(path).await.map_err(MultipartError::Io)?;
+        let written = self.write_to_file(&file, 0).await;
+        let closed = file.close().await.map_err(MultipartError::Io);
+        let written = written?;
+        closed?;
+        Ok(written)
+    }
+}
+
+/// Decode an RFC 8187 `UTF-8''percent%20encoded` parameter value.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let (charset, rest) = value.split_once('\'')?;
+    let (_language, encoded) = rest.split_once('\'')?;
+    if !charset.eq_ignore_ascii_case("utf-8") {
+        return None;
+    }
+    percent_encoding::percent_decode_str(encoded)
+        .decode_utf8()
+        .ok()
+        .map(Into::into)
+}
+
+/// Errors from reading a [`Multipart`] body.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MultipartError {
+    /// The request body failed.
+    Body(BoxError),
+    /// The body ended before the closing boundary.
+    Incomplete,
+    Malformed(&'static str),
+    HeadersTooLarge,
+    FieldTooLarge {
+        limit: u64,
+    },
+    StreamTooLarge {
+        limit: u64,
+    },
+    InvalidUtf8,
+    /// Writing a field to disk failed.
+    Io(io::Error),
+}
+
+impl std::fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Body(err) => write!(f, "Failed to read the request body: {err}"),
+            Self::Incomplete => f.write_str("Incomplete multipart body"),
+            Self::Malformed(reason) => write!(f, "Malformed multipart body: {reason}"),
+            Self::HeadersTooLarge => f.write_str("Multipart field headers are too large"),
+            Self::FieldTooLarge { limit } => {
+                write!(
+                    f,
+                    "Multipart field is larger than the limit of {limit} bytes"
+                )
+            }
+            Self::StreamTooLarge { limit } => {
+                write!(
+                    f,
+                    "Multipart body is larger than the limit of {limit} bytes"
+                )
+            }
+            Self::InvalidUtf8 => f.write_str("Multipart field is not valid UTF-8"),
+            Self::Io(err) => write!(f, "Failed to write multipart field: {err}"),
+        }
+    }
+}
+
+impl Error for MultipartError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Body(err) => Some(&**err),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl MultipartError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::FieldTooLarge { .. } | Self::StreamTooLarge { .. } => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            Self::HeadersTooLarge => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for MultipartError {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(self.status())
+            .detail(self.to_string())
+            .into_rejection()
+    }
+}
+
+/// Rejection used by [`Multipart`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum MultipartRejection {
+    /// The request isn't `multipart/form-data` or has no usable boundary.
+    InvalidBoundary,
+}
+
+impl IntoResponse for MultipartRejection {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::BAD_REQUEST)
+            .detail("Invalid `boundary` for `multipart/form-data` request")
+            .into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use futures_util::stream;
+    use http_body_util::StreamBody;
+
+    use super::*;
+    use crate::{Router, middleware::DefaultBodyLimit, test::TestClient};
+
+    const BOUNDARY: &str = "X-BOUNDARY";
+
+    const BODY: &[u8] = b"preamble, ignored\r\n\
+        --X-BOUNDARY\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        a\r\n--X-BOUNDAR lookalike\r\n\
+        --X-BOUNDARY  \r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a b.txt\"; filename*=UTF-8''%C3%A9t%C3%A9.txt\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        file\r\ncontents\r\n\
+        --X-BOUNDARY\r\n\
+        Content-Disposition: form-data; name=\"empty\"\r\n\
+        \r\n\
+        \r\n\
+        --X-BOUNDARY--\r\n\
+        epilogue, ignored";
+
+    /// A multipart body delivered in chunks of `chunk_size` bytes.
+    fn multipart(body: impl Into<Bytes>, chunk_size: usize) -> Multipart {
+        let body = body.into();
+        let chunks: Vec<_> = (0..body.len())
+            .step_by(chunk_size)
+            .map(|start| body.slice(start..body.len().min(start + chunk_size)))
+            .map(|chunk| Ok::<_, Infallible>(http_body::Frame::data(chunk)))
+            .collect();
+        Multipart::new(Body::new(StreamBody::new(stream::iter(chunks))), BOUNDARY)
+    }
+
+    async fn fields(mut multipart: Multipart) -> Result<Vec<(String, Bytes)>, MultipartError> {
+        let mut fields = Vec::new();
+        while let Some(field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or_default().to_owned();
+            fields.push((name, field.bytes().await?));
+        }
+        Ok(fields)
+    }
+
+    #[monoio::test]
+    async fn parses_fields_across_any_chunking() {
+        for chunk_size in 1..=BODY.len() {
+            let fields = fields(multipart(BODY, chunk_size)).await.unwrap();
+            assert_eq!(
+                fields,
+                [
+                    (
+                        "title".to_owned(),
+                        Bytes::from_static(b"a\r\n--X-BOUNDAR lookalike")
+                    ),
+                    ("file".to_owned(), Bytes::from_static(b"file\r\ncontents")),
+                    ("empty".to_owned(), Bytes::new()),
+                ],
+                "chunk size {chunk_size}"
+            );
+        }
+    }
+
+    #[monoio::test]
+    async fn reads_field_metadata() {
+        let mut multipart = multipart(BODY, 7);
+        let title = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(title.name(), Some("title"));
+        assert_eq!(title.file_name(), None);
+        assert_eq!(title.content_type(), None);
+
+        let file = multipart.next_field().await.unwrap().unwrap();
+        assert_eq!(file.name(), Some("file"));
+        // The extended `filename*` wins over the plain one.
+        assert_eq!(file.file_name(), Some("été.txt"));
+        assert_eq!(file.content_type(), Some("text/plain"));
+        assert_eq!(file.text().await.unwrap(), "file\r\ncontents");
+    }
+
+    #[monoio::test]
+    async fn skips_unread_field_data() {
+        let mut multipart = multipart(BODY, 3);
+        let mut names = Vec::new();
+        while let Some(field) = multipart.next_field().await.unwrap() {
+            names.push(field.name().unwrap().to_owned());
+        }
+        assert_eq!(names, ["title", "file", "empty"]);
+        assert!(multipart.next_field().await.unwrap().is_none());
+    }
+
+    #[monoio::test]
+    async fn fails_without_the_closing_boundary() {
+        let body = &b"--X-BOUNDARY\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\ndata"[..];
+        let err = fields(multipart(body, 4)).await.unwrap_err();
+        assert!(matches!(err, MultipartError::Incomplete), "{err:?}");
+
+        let err = fields(multipart(&b"no boundary at all"[..], 4))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MultipartError::Incomplete), "{err:?}");
+    }
+
+    #[monoio::test]
+    async fn fails_on_malformed_bodies() {
+        let body = &b"--X-BOUNDARY garbage\r\n\r\n\r\n--X-BOUNDARY--"[..];
+        let err = fields(multipart(body, 5)).await.unwrap_err();
+        assert!(matches!(err, MultipartError::Malformed(_)), "{err:?}");
+
+        let body = &b"--X-BOUNDARY\r\nnot a header\r\n\r\n\r\n--X-BOUNDARY--"[..];
+        let err = fields(multipart(body, 5)).await.unwrap_err();
+        assert!(matches!(err, MultipartError::Malformed(_)), "{err:?}");
+    }
+
+    #[monoio::test]
+    async fn stays_failed_after_an_error() {
+        let body = &b"--X-BOUNDARY garbage\r\n\r\n\r\n--X-BOUNDARY--"[..];
+        let mut multipart = multipart(body, 64);
+        assert!(multipart.next_field().await.is_err());
+        assert!(matches!(
+            multipart.next_field().await,
+            Err(MultipartError::Malformed(_))
+        ));
+    }
+
+    #[monoio::test]
+    async fn enforces_size_limits() {
+        let err = fields(multipart(BODY, 16).max_field_size(10))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, MultipartError::FieldTooLarge { limit: 10 }),
+            "{err:?}"
+        );
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let err = fields(multipart(BODY, 16).max_total_size(100))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, MultipartError::StreamTooLarge { limit: 100 }),
+            "{err:?}"
+        );
+
+        assert!(fields(multipart(BODY, 16).max_field_size(24)).await.is_ok());
+
+        let mut body = b"--X-BOUNDARY\r\n".to_vec();
+        body.extend(std::iter::repeat_n(b'x', MAX_HEADERS_SIZE + 1));
+        let err = fields(multipart(body, 1024)).await.unwrap_err();
+        assert!(matches!(err, MultipartError::HeadersTooLarge), "{err:?}");
+    }
+
+    #[test]
+    fn boundary_from_content_type() {
+        let boundary_of = |content_type: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            boundary(&headers)
+        };
+
+        assert_eq!(
+            boundary_of("multipart/form-data; boundary=abc").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            boundary_of("Multipart/Form-Data; charset=utf-8; BOUNDARY=\"a \\\"b\\\" c\"")
+                .as_deref(),
+            Some("a \"b\" c")
+        );
+        assert_eq!(boundary_of("multipart/mixed; boundary=abc"), None);
+        assert_eq!(boundary_of("multipart/form-data"), None);
+        assert_eq!(boundary_of("multipart/form-data; boundary="), None);
+        assert_eq!(
+            boundary_of("multipart/form-data; boundary=\"unterminated"),
+            None
+        );
+        let too_long = format!("multipart/form-data; boundary={}", "a".repeat(71));
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, too_long.parse().unwrap());
+        assert_eq!(boundary(&headers), None);
+    }
+
+    #[test]
+    fn decodes_extended_values() {
+        assert_eq!(decode_ext_value("UTF-8''a%20b").as_deref(), Some("a b"));
+        assert_eq!(decode_ext_value("utf-8'en'%E2%82%AC").as_deref(), Some("€"));
+        assert_eq!(decode_ext_value("ISO-8859-1''a"), None);
+        assert_eq!(decode_ext_value("UTF-8''%FF"), None);
+        assert_eq!(decode_ext_value("no quotes"), None);
+    }
+
+    /// A body with a single field `big` of `len` bytes.
+    fn big_field(len: usize) -> Vec<u8> {
+        let mut body = b"--X-BOUNDARY\r\n\
+            Content-Disposition: form-data; name=\"big\"\r\n\r\n"
+            .to_vec();
+        body.extend(std::iter::repeat_n(b'x', len));
+        body.extend_from_slice(b"\r\n--X-BOUNDARY--\r\n");
+        body
+    }
+
+    #[monoio::test]
+    async fn caps_buffered_fields_without_a_field_limit() {
+        let body = big_field(DEFAULT_BODY_LIMIT + 1);
+        let limit = DEFAULT_BODY_LIMIT as u64;
+
+        let mut buffered = multipart(body.clone(), 64 * 1024).max_total_size(u64::MAX);
+        let field = buffered.next_field().await.unwrap().unwrap();
+        let err = field.bytes().await.unwrap_err();
+        assert!(
+            matches!(err, MultipartError::FieldTooLarge { limit: l } if l == limit),
+            "{err:?}"
+        );
+
+        // Streaming isn't capped, and the cap can be raised.
+        let mut streamed = multipart(body.clone(), 64 * 1024).max_total_size(u64::MAX);
+        let mut field = streamed.next_field().await.unwrap().unwrap();
+        let mut read = 0;
+        while let Some(chunk) = field.chunk().await.unwrap() {
+            read += chunk.len();
+        }
+        assert_eq!(read, DEFAULT_BODY_LIMIT + 1);
+
+        let fields = fields(
+            multipart(body, 64 * 1024)
+                .max_total_size(u64::MAX)
+                .max_field_size(u64::MAX),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fields[0].1.len(), DEFAULT_BODY_LIMIT + 1);
+    }
+
+    #[monoio::test]
+    async fn limits_the_body_to_the_default_body_limit() {
+        async fn count(mut multipart: Multipart) -> Result<String, MultipartError> {
+            let mut read = 0;
+            while let Some(mut field) = multipart.next_field().await? {
+                while let Some(chunk) = field.chunk().await? {
+                    read += chunk.len();
+                }
+            }
+            Ok(read.to_string())
+        }
+
+        let send = |client: TestClient, len| async move {
+            client
+                .post("/")
+                .header(
+                    header::CONTENT_TYPE,
+                    "multipart/form-data; boundary=X-BOUNDARY",
+                )
+                .body(big_field(len))
+                .send()
+                .await
+        };
+        let client = || TestClient::new(Router::new().fallback(count));
+        let limited = || {
+            TestClient::new(
+                Router::new()
+                    .fallback(count)
+                    .layer(DefaultBodyLimit::max(1024)),
+            )
+        };
+        let unlimited = || {
+            TestClient::new(
+                Router::new()
+                    .fallback(count)
+                    .layer(DefaultBodyLimit::disable()),
+            )
+        };
+
+        send(limited(), 900)
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("900");
+        send(limited(), 1024)
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        send(client(), DEFAULT_BODY_LIMIT)
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        send(unlimited(), DEFAULT_BODY_LIMIT)
+            .await
+            .assert_status(StatusCode::OK);
+    }
+
+    #[monoio::test]
+    async fn extracts_from_requests() {
+        let app = Router::new().fallback(|mut multipart: Multipart| async move {
+            let mut out = Vec::new();
+            while let Some(field) = multipart.next_field().await.unwrap() {
+                let name = field.name().unwrap_or_default().to_owned();
+                let file_name = field.file_name().map(str::to_owned);
+                let text = field.text().await.unwrap();
+                out.push(format!("{name}:{file_name:?}:{text}"));
+            }
+            out.join(",")
+        });
+        let client = TestClient::new(app);
+
+        let body = "--XYZ\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            hello\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            world\r\n\
+            --XYZ--\r\n";
+        client
+            .post("/")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=XYZ")
+            .body(body)
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text(r#"title:None:hello,file:Some("a.txt"):world"#);
+        client
+            .post("/")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(body)
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use http::{StatusCode, request::Parts};
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
+
+use crate::{HttpResponse, IntoResponse, Problem, extract::FromRequestParts};
+
+mod de;
+
+pub use self::de::PathDeserializationError;
+
+/// Extractor for the parameters captured by the route, such as `{id}` in
+/// `/users/{id}`, percent-decoded.
+///
+/// A single parameter deserializes into any type parsed from a string, several of
+/// them into a tuple in the order they appear in the route, or into a struct or map
+/// by name:
+///
+/// ```no_run
+/// # use monet::extract::path::Path;
+/// // Routed at `/users/{id}/posts/{slug}`.
+/// async fn post(Path((id, slug)): Path<(u32, String)>) -> String {
+///     format!("post {slug} of user {id}")
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct PostPath {
+///     id: u32,
+///     slug: String,
+/// }
+///
+/// async fn post_by_name(Path(path): Path<PostPath>) -> String {
+///     format!("post {} of user {}", path.slug, path.id)
+/// }
+/// ```
+///
+/// Parameters that don't parse into the expected type are rejected with `400 Bad
+/// Request`. Asking for more parameters than the route captures is a bug in the
+/// handler and is rejected with `500 Internal Server Error`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Path<T>(pub T);
+
+impl<T> Deref for Path<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Path<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S, T> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned,
+{
+    type Rejection = PathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let params = match parts.extensions.get::<UrlParams>() {
+            Some(UrlParams::Params(params)) => params,
+            Some(UrlParams::InvalidUtf8InPathParam { key }) => {
+                return Err(PathRejection::InvalidUtf8InPathParam {
+                    key: key.to_string(),
+                });
+            }
+            None => return Err(PathRejection::MissingPathParams),
+        };
+
+        T::deserialize(de::PathDeserializer::new(params))
+            .map(Self)
+            .map_err(PathRejection::FailedToDeserializePathParams)
+    }
+}
+
+/// The parameters captured by the routes that matched the request, recorded by
+/// the [`Router`](crate::Router).
+#[derive(Debug, Clone)]
+pub(crate) enum UrlParams {
+    Params(Vec<(Arc<str>, String)>),
+    InvalidUtf8InPathParam { key: Arc<str> },
+}
+
+/// Record the parameters of a match, after the ones of the routers this router is
+/// nested in, if any. `skip` filters out the parameters used by the router itself.
+pub(crate) fn insert_url_params<'k, 'v>(
+    extensions: &mut http::Extensions,
+    params: impl Iterator<Item = (&'k str, &'v str)>,
+    skip: &str,
+) {
+    let mut decoded = match extensions.remove::<UrlParams>() {
+        Some(UrlParams::Params(outer)) => outer,
+        Some(invalid @ UrlParams::InvalidUtf8InPathParam { .. }) => {
+            extensions.insert(invalid);
+            return;
+        }
+        None => Vec::new(),
+    };
+
+    for (key, value) in params.filter(|(key, _)| *key != skip) {
+        match percent_decode_str(value).decode_utf8() {
+            Ok(value) => decoded.push((key.into(), value.into_owned())),
+            Err(_) => {
+                extensions.insert(UrlParams::InvalidUtf8InPathParam { key: key.into() });
+                return;
+            }
+        }
+    }
+    extensions.insert(UrlParams::Params(decoded));
+}
+
+/// Rejection used by [`Path`].
+#[derive(Debug)]
+pub enum PathRejection {
+    /// The request wasn't routed by a [`Router`](crate::Router), for example in a
+    /// fallback.
+    MissingPathParams,
+    /// A parameter isn't valid UTF-8 once percent-decoded.
+    InvalidUtf8InPathParam { key: String },
+    /// The parameters don't deserialize into the expected type.
+    FailedToDeserializePathParams(PathDeserializationError),
+}
+
+impl fmt::Display for PathRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPathParams => f.write_str(
+                "No paths parameters found for matched route. Is the request routed by a `Router`?",
+            ),
+            Self::InvalidUtf8InPathParam { key } => {
+                write!(f, "Invalid UTF-8 in path parameter `{key}`")
+            }
+            Self::FailedToDeserializePathParams(err) => {
+                write!(f, "Failed to deserialize path parameters: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PathRejection {}
+
+impl IntoResponse for PathRejection {
+    fn into_response(self) -> HttpResponse {
+        let status = match &self {
+            Self::MissingPathParams => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidUtf8InPathParam { .. } => StatusCode::BAD_REQUEST,
+            Self::FailedToDeserializePathParams(err) => err.status(),
+        };
+        Problem::new(status)
+            .detail(self.to_string())
+            .into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    #[derive(Deserialize)]
+    struct Post {
+        user: String,
+        id: u64,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Order {
+        Asc,
+        Desc,
+    }
+
+    #[monoio::test]
+    async fn deserializes_params() {
+        let app = Router::new()
+            .route(
+                "/tuple/{user}/{id}",
+                get(|Path((user, id)): Path<(String, u64)>| async move { format!("{user} {id}") }),
+            )
+            .route(
+                "/struct/{user}/{id}",
+                get(|Path(post): Path<Post>| async move { format!("{} {}", post.user, post.id) }),
+            )
+            .route(
+                "/single/{id}",
+                get(|Path(id): Path<u64>| async move { id.to_string() }),
+            )
+            .route(
+                "/enum/{order}",
+                get(|Path(order): Path<Order>| async move {
+                    match order {
+                        Order::Asc => "up",
+                        Order::Desc => "down",
+                    }
+                }),
+            );
+        let client = TestClient::new(app);
+
+        client
+            .get("/tuple/ferris%20crab/42")
+            .send()
+            .await
+            .assert_text("ferris crab 42");
+        client
+            .get("/struct/ferris/42")
+            .send()
+            .await
+            .assert_text("ferris 42");
+        client.get("/single/42").send().await.assert_text("42");
+        client.get("/enum/desc").send().await.assert_text("down");
+    }
+
+    #[monoio::test]
+    async fn rejects_invalid_params() {
+        let app = Router::new()
+            .route(
+                "/id/{id}",
+                get(|Path(id): Path<u64>| async move { id.to_string() }),
+            )
+            .route(
+                "/pair/{id}",
+                get(|Path((a, b)): Path<(u64, u64)>| async move { format!("{a} {b}") }),
+            )
+            .route(
+                "/name/{name}",
+                get(|Path(name): Path<String>| async move { name }),
+            );
+        let client = TestClient::new(app);
+
+        client
+            .get("/id/latest")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        client
+            .get("/name/%FF")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        // A route that doesn't match the extractor is a bug in the app.
+        client
+            .get("/pair/1")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[monoio::test]
+    async fn needs_a_router() {
+        let app = Router::new().fallback(|Path(id): Path<u64>| async move { id.to_string() });
+        TestClient::new(app)
+            .get("/1")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,483 @@
+use std::{fmt, sync::Arc};
+
+use http::StatusCode;
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+    value::BorrowedStrDeserializer,
+};
+
+/// Error deserializing the parameters of a route with [`Path`](super::Path).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathDeserializationError {
+    kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ErrorKind {
+    /// The type asks for a different number of parameters than the route has.
+    WrongNumberOfParameters {
+        got: usize,
+        expected: usize,
+    },
+    /// A parameter doesn't parse into the type asked for.
+    ParseError {
+        key: String,
+        value: String,
+        expected_type: &'static str,
+    },
+    /// The type can't be deserialized from path parameters at all.
+    UnsupportedType {
+        name: &'static str,
+    },
+    Message(String),
+}
+
+impl PathDeserializationError {
+    fn new(kind: ErrorKind) -> Self {
+        Self { kind }
+    }
+
+    fn parse(key: &str, value: &str, expected_type: &'static str) -> Self {
+        Self::new(ErrorKind::ParseError {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            expected_type,
+        })
+    }
+
+    fn unsupported(name: &'static str) -> Self {
+        Self::new(ErrorKind::UnsupportedType { name })
+    }
+
+    /// Mismatches between the handler and its route are server errors, anything
+    /// else is the client's.
+    pub(crate) fn status(&self) -> StatusCode {
+        match self.kind {
+            ErrorKind::WrongNumberOfParameters { .. } | ErrorKind::UnsupportedType { .. } => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            ErrorKind::ParseError { .. } | ErrorKind::Message(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl fmt::Display for PathDeserializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::WrongNumberOfParameters { got, expected } => write!(
+                f,
+                "Wrong number of path arguments for `Path`. Expected {expected} but got {got}"
+            ),
+            ErrorKind::ParseError {
+                key,
+                value,
+                expected_type,
+            } => write!(
+                f,
+                "Cannot parse `{key}` with value `{value}` to a `{expected_type}`"
+            ),
+            ErrorKind::UnsupportedType { name } => {
+                write!(f, "Unsupported type `{name}` for path parameters")
+            }
+            ErrorKind::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for PathDeserializationError {}
+
+impl de::Error for PathDeserializationError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::new(ErrorKind::Message(msg.to_string()))
+    }
+}
+
+type Params = [(Arc<str>, String)];
+
+/// Deserializes all the parameters of a route, as a whole.
+pub(super) struct PathDeserializer<'de> {
+    params: &'de Params,
+}
+
+impl<'de> PathDeserializer<'de> {
+    pub(super) fn new(params: &'de Params) -> Self {
+        Self { params }
+    }
+
+    /// The only parameter, for types that are made of a single value.
+    fn single(&self) -> Result<ValueDeserializer<'de>, PathDeserializationError> {
+        match self.params {
+            [(key, value)] => Ok(ValueDeserializer { key, value }),
+            params => Err(PathDeserializationError::new(
+                ErrorKind::WrongNumberOfParameters {
+                    got: params.len(),
+                    expected: 1,
+                },
+            )),
+        }
+    }
+
+    fn check_len(&self, expected: usize) -> Result<(), PathDeserializationError> {
+        if self.params.len() == expected {
+            Ok(())
+        } else {
+            Err(PathDeserializationError::new(
+                ErrorKind::WrongNumberOfParameters {
+                    got: self.params.len(),
+                    expected,
+                },
+            ))
+        }
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for PathDeserializer<'de> {
+    type Error = PathDeserializationError;
+
+    forward_to_single! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_option
+        deserialize_identifier
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(ParamsAccess::new(self.params))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.check_len(len)?;
+        visitor.visit_seq(ParamsAccess::new(self.params))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(ParamsAccess::new(self.params))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Walks the parameters, as a sequence of values or as a map from their names.
+struct ParamsAccess<'de> {
+    params: std::slice::Iter<'de, (Arc<str>, String)>,
+    value: Option<ValueDeserializer<'de>>,
+}
+
+impl<'de> ParamsAccess<'de> {
+    fn new(params: &'de Params) -> Self {
+        Self {
+            params: params.iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for ParamsAccess<'de> {
+    type Error = PathDeserializationError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.params
+            .next()
+            .map(|(key, value)| seed.deserialize(ValueDeserializer { key, value }))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.params.len())
+    }
+}
+
+impl<'de> MapAccess<'de> for ParamsAccess<'de> {
+    type Error = PathDeserializationError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.params.next() else {
+            return Ok(None);
+        };
+        self.value = Some(ValueDeserializer { key, value });
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .expect("`next_value_seed` called before `next_key_seed`");
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.params.len())
+    }
+}
+
+/// Deserializes the value of a single parameter.
+#[derive(Clone, Copy)]
+struct ValueDeserializer<'de> {
+    key: &'de str,
+    value: &'de str,
+}
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident($ty:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                let value = self.value.parse::<$ty>().map_err(|_| {
+                    PathDeserializationError::parse(self.key, self.value, stringify!($ty))
+                })?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+macro_rules! unsupported {
+    ($($method:ident => $name:literal,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+                Err(PathDeserializationError::unsupported($name))
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = PathDeserializationError;
+
+    parse_value! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_i128 => visit_i128(i128),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_u128 => visit_u128(u128),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_char => visit_char(char),
+    }
+
+    unsupported! {
+        deserialize_seq => "sequence",
+        deserialize_map => "map",
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_bytes(self.value.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_bytes(self.value.as_bytes())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(PathDeserializationError::unsupported("tuple"))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(PathDeserializationError::unsupported(name))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(PathDeserializationError::unsupported(name))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(UnitVariant(self))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Enums in paths can only be unit variants named by the parameter.
+struct UnitVariant<'de>(ValueDeserializer<'de>);
+
+impl<'de> EnumAccess<'de> for UnitVariant<'de> {
+    type Error = PathDeserializationError;
+
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(self.0)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for UnitVariant<'de> {
+    type Error = PathDeserializationError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        _seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        Err(PathDeserializationError::unsupported(
+            "newtype enum variant",
+        ))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(PathDeserializationError::unsupported("tuple enum variant"))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(PathDeserializationError::unsupported("struct enum variant"))
+    }
+}
@@ -1,9 +1,9 @@
-use std::convert::Infallible;
+use std::fmt;
 
-use http::request::Parts;
+use http::{StatusCode, request::Parts};
 use serde_core::de::DeserializeOwned;
 
-use crate::extract::FromRequestParts;
+use crate::{HttpResponse, IntoResponse, Problem, extract::FromRequestParts};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Query<T>(pub T);
@@ -12,14 +12,41 @@ impl<T, S> FromRequestParts<S> for Query<T>
 where
     T: DeserializeOwned,
 {
-    type Rejection = Infallible; // Todo handle invalid query string
+    type Rejection = FailedToDeserializeQueryString;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         let query = parts.uri.query().unwrap_or_default();
         let deserializer =
             serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
-        let params = serde_path_to_error::deserialize(deserializer).unwrap();
-        // .map_err(FailedToDeserializeQueryString::from_err)?;
+        let params = serde_path_to_error::deserialize(deserializer)
+            .map_err(FailedToDeserializeQueryString::from_err)?;
         Ok(Self(params))
     }
 }
+
+/// Rejection used by [`Query`] when the query string doesn't deserialize into
+/// the expected type.
+#[derive(Debug, Clone)]
+pub struct FailedToDeserializeQueryString(String);
+
+impl FailedToDeserializeQueryString {
+    fn from_err(err: serde_path_to_error::Error<serde_urlencoded::de::Error>) -> Self {
+        Self(err.to_string())
+    }
+}
+
+impl fmt::Display for FailedToDeserializeQueryString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to deserialize query string: {}", self.0)
+    }
+}
+
+impl std::error::Error for FailedToDeserializeQueryString {}
+
+impl IntoResponse for FailedToDeserializeQueryString {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::BAD_REQUEST)
+            .detail(self.to_string())
+            .into_rejection()
+    }
+}
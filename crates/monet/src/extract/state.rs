@@ -3,7 +3,7 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use crate::extract::FromRequestParts;
+use crate::extract::{FromRequestParts, from_ref::FromRef};
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct State<S>(pub S);
@@ -22,16 +22,16 @@ impl<S> DerefMut for State<S> {
     }
 }
 
-impl<S> FromRequestParts<S> for State<S>
+impl<OuterState, InnerState> FromRequestParts<OuterState> for State<InnerState>
 where
-    S: Clone,
+    InnerState: FromRef<OuterState>,
 {
     type Rejection = Infallible;
 
     async fn from_request_parts(
         _parts: &mut http::request::Parts,
-        state: &S,
+        state: &OuterState,
     ) -> Result<Self, Self::Rejection> {
-        Ok(Self(state.clone()))
+        Ok(Self(InnerState::from_ref(state)))
     }
 }
@@ -0,0 +1,462 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker, ready},
+    time::Duration,
+};
+
+use futures_util::future::{AbortHandle, Abortable};
+use http::{StatusCode, request::Parts};
+use pin_project_lite::pin_project;
+
+use crate::{
+    HttpBody, HttpResponse, IntoResponse, Problem, TowerService, extract::FromRequestParts,
+    util::ThreadBound,
+};
+
+/// Spawns tasks that live no longer than the request that created them.
+///
+/// Every request served by [`serve`](crate::serve::serve) gets its own scope. Tasks
+/// spawned through it are cancelled once the response has been sent, or as soon as
+/// the client disconnects and the request is dropped, so fan-out handlers don't
+/// keep working for nobody.
+///
+/// ```no_run
+/// # use monet::TaskScope;
+/// # async fn fetch(_: &str) -> usize { 0 }
+/// async fn fan_out(scope: TaskScope) -> String {
+///     let a = scope.spawn(fetch("a"));
+///     let b = scope.spawn(fetch("b"));
+///     format!("{:?} {:?}", a.await, b.await)
+/// }
+/// ```
+#[derive(Clone)]
+pub struct TaskScope {
+    inner: Rc<ScopeInner>,
+}
+
+#[derive(Default)]
+struct ScopeInner {
+    cancelled: Cell<bool>,
+    running: Cell<usize>,
+    next_id: Cell<u64>,
+    /// The tasks that haven't finished yet, by id.
+    abort_handles: RefCell<HashMap<u64, AbortHandle>>,
+    /// The pending [`TaskScope::join_all`] calls, by id.
+    idle_waiters: RefCell<HashMap<u64, Waker>>,
+}
+
+impl ScopeInner {
+    fn next_id(&self) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+}
+
+impl TaskScope {
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Rc::new(ScopeInner::default()),
+        }
+    }
+
+    /// Spawn a task on the current core, tied to this scope.
+    ///
+    /// If the scope is already cancelled the task never runs and its handle
+    /// resolves to [`Cancelled`].
+    pub fn spawn<F>(&self, future: F) -> ScopedJoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let (abort_handle, registration) = AbortHandle::new_pair();
+        if self.is_cancelled() {
+            abort_handle.abort();
+        }
+
+        let id = self.inner.next_id();
+        self.inner
+            .abort_handles
+            .borrow_mut()
+            .insert(id, abort_handle.clone());
+        self.inner.running.set(self.inner.running.get() + 1);
+
+        let running = RunningGuard {
+            scope: self.inner.clone(),
+            id,
+        };
+        let future = Abortable::new(future, registration);
+        let join = monoio::spawn(async move {
+            let output = future.await;
+            drop(running);
+            output
+        });
+
+        ScopedJoinHandle { join, abort_handle }
+    }
+
+    /// Wait for every task spawned so far to finish. When `deadline` passes first,
+    /// the scope is cancelled and [`DeadlineElapsed`] is returned.
+    pub async fn join_all(&self, deadline: Duration) -> Result<(), DeadlineElapsed> {
+        let waiter = IdleWaiter {
+            scope: &self.inner,
+            id: self.inner.next_id(),
+        };
+        let idle = std::future::poll_fn(|cx| {
+            if self.inner.running.get() == 0 {
+                return Poll::Ready(());
+            }
+            let mut waiters = self.inner.idle_waiters.borrow_mut();
+            match waiters.get_mut(&waiter.id) {
+                Some(waker) => waker.clone_from(cx.waker()),
+                None => {
+                    waiters.insert(waiter.id, cx.waker().clone());
+                }
+            }
+            Poll::Pending
+        });
+
+        match monoio::time::timeout(deadline, idle).await {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                self.cancel();
+                Err(DeadlineElapsed)
+            }
+        }
+    }
+
+    /// Cancel every task of the scope. Tasks spawned afterwards are cancelled
+    /// right away.
+    pub fn cancel(&self) {
+        self.inner.cancelled.set(true);
+        // Aborting only wakes the tasks, they unregister themselves once polled.
+        for handle in self.inner.abort_handles.borrow().values() {
+            handle.abort();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.get()
+    }
+
+    /// The number of tasks of this scope that haven't finished yet.
+    pub fn running(&self) -> usize {
+        self.inner.running.get()
+    }
+}
+
+impl fmt::Debug for TaskScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskScope")
+            .field("cancelled", &self.inner.cancelled.get())
+            .field("running", &self.inner.running.get())
+            .finish()
+    }
+}
+
+/// Marks a scoped task as finished, whether it completed or was cancelled.
+struct RunningGuard {
+    scope: Rc<ScopeInner>,
+    id: u64,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        let scope = &self.scope;
+        scope.abort_handles.borrow_mut().remove(&self.id);
+        scope.running.set(scope.running.get() - 1);
+        if scope.running.get() == 0 {
+            for waker in scope.idle_waiters.borrow().values() {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+/// Unregisters the waker of a [`TaskScope::join_all`] call once it returns or is
+/// dropped.
+struct IdleWaiter<'a> {
+    scope: &'a ScopeInner,
+    id: u64,
+}
+
+impl Drop for IdleWaiter<'_> {
+    fn drop(&mut self) {
+        self.scope.idle_waiters.borrow_mut().remove(&self.id);
+    }
+}
+
+impl<S> FromRequestParts<S> for TaskScope {
+    type Rejection = MissingTaskScope;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ThreadBound<TaskScope>>()
+            .and_then(ThreadBound::get)
+            .cloned()
+            .ok_or(MissingTaskScope)
+    }
+}
+
+/// Rejection used by [`TaskScope`] when the request was not served through
+/// [`serve`](crate::serve::serve).
+#[derive(Debug, Clone, Copy)]
+pub struct MissingTaskScope;
+
+impl IntoResponse for MissingTaskScope {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .detail(
+                "Missing request extension: `TaskScope`. Is the app served with `monet::serve`?",
+            )
+            .into_rejection()
+    }
+}
+
+/// Handle to a task spawned with [`TaskScope::spawn`]. Dropping the handle
+/// detaches it, the task keeps running until it finishes or the scope is cancelled.
+pub struct ScopedJoinHandle<T> {
+    join: monoio::task::JoinHandle<Result<T, futures_util::future::Aborted>>,
+    abort_handle: AbortHandle,
+}
+
+impl<T> ScopedJoinHandle<T> {
+    /// Cancel only this task.
+    pub fn cancel(&self) {
+        self.abort_handle.abort();
+    }
+}
+
+impl<T> Future for ScopedJoinHandle<T> {
+    type Output = Result<T, Cancelled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let output = ready!(Pin::new(&mut self.join).poll(cx));
+        Poll::Ready(output.map_err(|_| Cancelled))
+    }
+}
+
+impl<T> fmt::Debug for ScopedJoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedJoinHandle").finish_non_exhaustive()
+    }
+}
+
+/// The task was cancelled before it could finish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("task was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// [`TaskScope::join_all`] ran out of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineElapsed;
+
+impl fmt::Display for DeadlineElapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline elapsed before all scoped tasks finished")
+    }
+}
+
+impl std::error::Error for DeadlineElapsed {}
+
+/// Cancels the scope it owns when dropped.
+struct ScopeGuard(TaskScope);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Gives every request a fresh [`TaskScope`] that is cancelled once the response
+/// body is done, or when the request is dropped halfway.
+#[derive(Debug, Clone)]
+pub(crate) struct TaskScopeService<S> {
+    inner: S,
+}
+
+impl<S> TaskScopeService<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, ReqBody, B> TowerService<http::Request<ReqBody>> for TaskScopeService<S>
+where
+    S: TowerService<http::Request<ReqBody>, Response = HttpResponse<B>>,
+{
+    type Response = HttpResponse<ScopedBody<B>>;
+
+    type Error = S::Error;
+
+    type Future = TaskScopeFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let scope = TaskScope::new();
+        req.extensions_mut().insert(ThreadBound::new(scope.clone()));
+
+        TaskScopeFuture {
+            future: self.inner.call(req),
+            guard: Some(ScopeGuard(scope)),
+        }
+    }
+}
+
+pin_project! {
+    pub(crate) struct TaskScopeFuture<F> {
+        #[pin]
+        future: F,
+        guard: Option<ScopeGuard>,
+    }
+}
+
+impl<F, B, E> Future for TaskScopeFuture<F>
+where
+    F: Future<Output = Result<HttpResponse<B>, E>>,
+{
+    type Output = Result<HttpResponse<ScopedBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.future.poll(cx))?;
+        let guard = this.guard.take();
+
+        Poll::Ready(Ok(res.map(|body| ScopedBody { body, guard })))
+    }
+}
+
+pin_project! {
+    /// Response body that keeps the request's [`TaskScope`] alive until the last
+    /// frame has been sent.
+    pub(crate) struct ScopedBody<B> {
+        #[pin]
+        body: B,
+        guard: Option<ScopeGuard>,
+    }
+}
+
+impl<B> HttpBody for ScopedBody<B>
+where
+    B: HttpBody,
+{
+    type Data = B::Data;
+
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.body.poll_frame(cx));
+        if frame.is_none() {
+            this.guard.take();
+        }
+        Poll::Ready(frame)
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.body.size_hint()
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::channel::oneshot;
+    use monoio::io::AsyncWriteRentExt;
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    /// Reports when the task holding it is dropped.
+    struct DropSignal(Option<oneshot::Sender<()>>);
+
+    impl Drop for DropSignal {
+        fn drop(&mut self) {
+            let _ = self.0.take().unwrap().send(());
+        }
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn joins_spawned_tasks() {
+        let app = Router::new().route(
+            "/",
+            get(|scope: TaskScope| async move {
+                let task = scope.spawn(async {
+                    monoio::time::sleep(Duration::from_millis(5)).await;
+                    21 * 2
+                });
+                task.await.unwrap().to_string()
+            }),
+        );
+
+        TestClient::new(app)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("42");
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn cancels_tasks_when_the_client_disconnects() {
+        let (started, handler_started) = oneshot::channel();
+        let (dropped, task_dropped) = oneshot::channel();
+        let signals = Rc::new(RefCell::new(Some((started, dropped))));
+
+        let app = Router::new().route(
+            "/",
+            get(move |scope: TaskScope| {
+                let (started, dropped) = signals.borrow_mut().take().unwrap();
+                async move {
+                    let signal = DropSignal(Some(dropped));
+                    let _task = scope.spawn(async move {
+                        let _signal = signal;
+                        std::future::pending::<()>().await;
+                    });
+                    started.send(()).unwrap();
+                    std::future::pending::<()>().await;
+                }
+            }),
+        );
+        let client = TestClient::loopback(app);
+
+        let mut stream = monoio::net::TcpStream::connect(client.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (written, _) = stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n".to_vec())
+            .await;
+        written.unwrap();
+        handler_started.await.unwrap();
+
+        drop(stream);
+        monoio::time::timeout(Duration::from_secs(1), task_dropped)
+            .await
+            .expect("the spawned task outlived the request")
+            .unwrap();
+    }
+}
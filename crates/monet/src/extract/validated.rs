@@ -0,0 +1,375 @@
+use std::{
+    error::Error,
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use http::{StatusCode, request::Parts};
+use serde_json::json;
+
+use crate::{
+    HttpRequest, HttpResponse, IntoResponse, Problem,
+    extract::{FromRequest, FromRequestParts, form::Form, json::Json, path::Path, query::Query},
+};
+
+/// Values that can check themselves once extracted, see [`Validated`].
+///
+/// ```no_run
+/// # use monet::extract::validated::{Validate, ValidationErrors};
+/// # struct Address;
+/// # impl Validate for Address {
+/// #     fn validate(&self) -> Result<(), ValidationErrors> { Ok(()) }
+/// # }
+/// # struct NewUser { email: String, address: Address }
+/// impl Validate for NewUser {
+///     fn validate(&self) -> Result<(), ValidationErrors> {
+///         let mut errors = ValidationErrors::new();
+///         if !self.email.contains('@') {
+///             errors.add("email", "must be an email address");
+///         }
+///         errors.nest("address", self.address.validate());
+///         errors.into_result()
+///     }
+/// }
+/// ```
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Extractors wrapping a value that [`Validated`] can check.
+pub trait ExtractedValue {
+    type Value;
+
+    fn value(&self) -> &Self::Value;
+}
+
+macro_rules! impl_extracted_value {
+    ($($extractor:ident),*) => {
+        $(
+            impl<T> ExtractedValue for $extractor<T> {
+                type Value = T;
+
+                fn value(&self) -> &T {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+impl_extracted_value!(Query, Json, Form, Path);
+
+/// Extractor running `E`, then validating the value it extracted with
+/// [`Validate`].
+///
+/// Values that fail validation are rejected with `422 Unprocessable Content` and
+/// an `application/problem+json` body listing each invalid field, see
+/// [`ValidationErrors`]. Rejections of `E` itself are passed on unchanged.
+///
+/// ```no_run
+/// # use monet::extract::{query::Query, validated::{Validate, Validated, ValidationErrors}};
+/// # #[derive(serde::Deserialize)]
+/// # struct Search { q: String }
+/// # impl Validate for Search {
+/// #     fn validate(&self) -> Result<(), ValidationErrors> { Ok(()) }
+/// # }
+/// async fn search(Validated(Query(params)): Validated<Query<Search>>) -> String {
+///     // `params` is known to be valid here.
+///     params.q
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Validated<E>(pub E);
+
+impl<E> Deref for Validated<E> {
+    type Target = E;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<E> DerefMut for Validated<E> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<S, E> FromRequestParts<S> for Validated<E>
+where
+    E: FromRequestParts<S> + ExtractedValue,
+    E::Value: Validate,
+{
+    type Rejection = ValidationRejection<E::Rejection>;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let extracted = E::from_request_parts(parts, state)
+            .await
+            .map_err(ValidationRejection::Extractor)?;
+        extracted.value().validate()?;
+        Ok(Self(extracted))
+    }
+}
+
+impl<S, E> FromRequest<S> for Validated<E>
+where
+    E: FromRequest<S> + ExtractedValue,
+    E::Value: Validate,
+{
+    type Rejection = ValidationRejection<E::Rejection>;
+
+    async fn from_request(req: HttpRequest, state: &S) -> Result<Self, Self::Rejection> {
+        let extracted = E::from_request(req, state)
+            .await
+            .map_err(ValidationRejection::Extractor)?;
+        extracted.value().validate()?;
+        Ok(Self(extracted))
+    }
+}
+
+/// The fields that failed validation, each with a message saying why.
+///
+/// Paths name fields the way they are serialized, with nested fields joined by
+/// `.` and list items by their index, such as `address.zip` or `tags.2`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+/// A field that failed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the field at `path` is invalid.
+    pub fn add(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Record the errors of the value at `path`, typically from validating a
+    /// nested struct or a list item, with their paths prefixed by `path`.
+    pub fn nest(&mut self, path: &str, result: Result<(), ValidationErrors>) {
+        let Err(nested) = result else {
+            return;
+        };
+        self.errors
+            .extend(nested.errors.into_iter().map(|error| FieldError {
+                path: if error.path.is_empty() {
+                    path.to_owned()
+                } else {
+                    format!("{path}.{}", error.path)
+                },
+                message: error.message,
+            }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FieldError> {
+        self.errors.iter()
+    }
+
+    /// `Ok` if nothing was recorded.
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("validation failed")?;
+        for (index, error) in self.errors.iter().enumerate() {
+            let separator = if index == 0 { ": " } else { ", " };
+            write!(f, "{separator}`{}` {}", error.path, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationErrors {}
+
+/// `422 Unprocessable Content` with an RFC 9457 problem details body, which
+/// lists the invalid fields under `errors`:
+///
+/// ```json
+/// {
+///   "type": "about:blank",
+///   "title": "Unprocessable Content",
+///   "status": 422,
+///   "detail": "The request failed validation",
+///   "errors": [{ "path": "email", "message": "must be an email address" }]
+/// }
+/// ```
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> HttpResponse {
+        let errors: Vec<_> = self
+            .errors
+            .iter()
+            .map(|error| json!({ "path": error.path, "message": error.message }))
+            .collect();
+        Problem::new(StatusCode::UNPROCESSABLE_ENTITY)
+            .detail("The request failed validation")
+            .extension("errors", errors)
+            .into_response()
+    }
+}
+
+/// Rejection used by [`Validated`].
+#[derive(Debug)]
+pub enum ValidationRejection<R> {
+    /// The wrapped extractor failed.
+    Extractor(R),
+    /// The extracted value is invalid.
+    Invalid(ValidationErrors),
+}
+
+impl<R> From<ValidationErrors> for ValidationRejection<R> {
+    fn from(errors: ValidationErrors) -> Self {
+        Self::Invalid(errors)
+    }
+}
+
+impl<R> IntoResponse for ValidationRejection<R>
+where
+    R: IntoResponse,
+{
+    fn into_response(self) -> HttpResponse {
+        match self {
+            Self::Extractor(rejection) => rejection.into_response(),
+            Self::Invalid(errors) => errors.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header;
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    #[derive(Deserialize)]
+    struct Address {
+        city: String,
+    }
+
+    impl Validate for Address {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if self.city.is_empty() {
+                errors.add("city", "must not be empty");
+            }
+            errors.into_result()
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct User {
+        name: String,
+        address: Address,
+    }
+
+    impl Validate for User {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if self.name.is_empty() {
+                errors.add("name", "must not be empty");
+            }
+            errors.nest("address", self.address.validate());
+            errors.into_result()
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Search {
+        q: String,
+    }
+
+    impl Validate for Search {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if self.q.len() < 3 {
+                errors.add("q", "must be at least 3 characters");
+            }
+            errors.into_result()
+        }
+    }
+
+    #[monoio::test]
+    async fn lists_invalid_fields() {
+        let app = Router::new()
+            .fallback(|Validated(Json(user)): Validated<Json<User>>| async move { user.name });
+        let client = TestClient::new(app);
+
+        client
+            .post("/")
+            .json(&json!({ "name": "ferris", "address": { "city": "Berlin" } }))
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("ferris");
+
+        let res = client
+            .post("/")
+            .json(&json!({ "name": "", "address": { "city": "" } }))
+            .send()
+            .await;
+        res.assert_status(StatusCode::UNPROCESSABLE_ENTITY)
+            .assert_header(header::CONTENT_TYPE, "application/problem+json");
+        assert_eq!(
+            res.json::<Value>()["errors"],
+            json!([
+                { "path": "name", "message": "must not be empty" },
+                { "path": "address.city", "message": "must not be empty" },
+            ])
+        );
+    }
+
+    #[monoio::test]
+    async fn validates_parts_extractors() {
+        let app = Router::new().route(
+            "/",
+            get(|Validated(Query(search)): Validated<Query<Search>>| async move { search.q }),
+        );
+        let client = TestClient::new(app);
+
+        client
+            .get("/?q=crab")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("crab");
+        client
+            .get("/?q=a")
+            .send()
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[monoio::test]
+    async fn passes_on_extractor_rejections() {
+        let app = Router::new()
+            .fallback(|Validated(Json(user)): Validated<Json<User>>| async move { user.name });
+
+        TestClient::new(app)
+            .post("/")
+            .body("{}")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
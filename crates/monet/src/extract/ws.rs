@@ -0,0 +1,1005 @@
+//! WebSocket support, see [`WebSocketUpgrade`].
+
+use std::{fmt, future::Future, io, marker::PhantomData, rc::Rc};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Version, header, request::Parts,
+};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper_util::rt::TokioIo;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{HttpResponse, IntoResponse, Problem, extract::FromRequestParts};
+
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+const DEFAULT_MAX_FRAME_SIZE: usize = 16 << 20;
+const WEBSOCKET_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Extractor for establishing WebSocket connections.
+///
+/// Validates the HTTP/1.1 upgrade handshake. Call [`WebSocketUpgrade::on_upgrade`]
+/// with the function that talks to the client and return the response it builds.
+///
+/// ```no_run
+/// # use monet::{HttpResponse, extract::ws::WebSocketUpgrade};
+/// async fn echo(ws: WebSocketUpgrade) -> HttpResponse {
+///     ws.on_upgrade(|mut socket| async move {
+///         while let Some(Ok(msg)) = socket.recv().await {
+///             if socket.send(msg).await.is_err() {
+///                 break;
+///             }
+///         }
+///     })
+/// }
+/// ```
+pub struct WebSocketUpgrade {
+    config: WebSocketConfig,
+    sec_websocket_key: HeaderValue,
+    sec_websocket_protocol: Option<HeaderValue>,
+    protocol: Option<HeaderValue>,
+    on_upgrade: OnUpgrade,
+}
+
+impl fmt::Debug for WebSocketUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketUpgrade")
+            .field("config", &self.config)
+            .field("sec_websocket_key", &self.sec_websocket_key)
+            .field("sec_websocket_protocol", &self.sec_websocket_protocol)
+            .field("protocol", &self.protocol)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WebSocketConfig {
+    max_message_size: usize,
+    max_frame_size: usize,
+    write_frame_size: Option<usize>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            write_frame_size: None,
+        }
+    }
+}
+
+impl WebSocketUpgrade {
+    /// The largest message the client may send, after fragments are joined.
+    /// Defaults to 64 MiB.
+    pub fn max_message_size(mut self, max: usize) -> Self {
+        self.config.max_message_size = max;
+        self
+    }
+
+    /// The largest single frame the client may send. Defaults to 16 MiB.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.config.max_frame_size = max;
+        self
+    }
+
+    /// Split outgoing text and binary messages into frames of at most `size` bytes.
+    /// By default every message is sent as a single frame.
+    pub fn write_frame_size(mut self, size: usize) -> Self {
+        self.config.write_frame_size = Some(size.max(1));
+        self
+    }
+
+    /// Set the subprotocols the server supports, in order of preference. The first
+    /// one also requested by the client is selected and returned in the
+    /// `Sec-WebSocket-Protocol` response header.
+    pub fn protocols<I>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<std::borrow::Cow<'static, str>>,
+    {
+        let Some(requested) = self
+            .sec_websocket_protocol
+            .as_ref()
+            .and_then(|value| value.to_str().ok())
+        else {
+            return self;
+        };
+
+        self.protocol = protocols
+            .into_iter()
+            .map(Into::into)
+            .find(|protocol| {
+                requested
+                    .split(',')
+                    .any(|requested| requested.trim() == protocol)
+            })
+            .and_then(|protocol| HeaderValue::from_str(&protocol).ok());
+        self
+    }
+
+    /// The subprotocol selected by [`WebSocketUpgrade::protocols`].
+    pub fn selected_protocol(&self) -> Option<&HeaderValue> {
+        self.protocol.as_ref()
+    }
+
+    /// Finish the handshake with a `101 Switching Protocols` response and run
+    /// `callback` with the established [`WebSocket`] once hyper hands over the
+    /// connection. The callback runs as a task on the current core.
+    pub fn on_upgrade<C, Fut>(self, callback: C) -> HttpResponse
+    where
+        C: FnOnce(WebSocket) -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let Self {
+            config,
+            sec_websocket_key,
+            protocol,
+            on_upgrade,
+            ..
+        } = self;
+
+        let socket_protocol = protocol.clone();
+        monoio::spawn(async move {
+            let Ok(upgraded) = on_upgrade.await else {
+                return;
+            };
+            let socket = WebSocket::new(upgraded, config, socket_protocol);
+            callback(socket).await;
+        });
+
+        let mut res = StatusCode::SWITCHING_PROTOCOLS.into_response();
+        let headers = res.headers_mut();
+        headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(
+            header::SEC_WEBSOCKET_ACCEPT,
+            sign(sec_websocket_key.as_bytes()),
+        );
+        if let Some(protocol) = protocol {
+            headers.insert(header::SEC_WEBSOCKET_PROTOCOL, protocol);
+        }
+        res
+    }
+}
+
+fn sign(key: &[u8]) -> HeaderValue {
+    let mut sha1 = Sha1::default();
+    sha1.update(key);
+    sha1.update(WEBSOCKET_GUID);
+    let accept = STANDARD.encode(sha1.finalize());
+    HeaderValue::from_str(&accept).expect("base64 is a valid header value")
+}
+
+impl<S> FromRequestParts<S> for WebSocketUpgrade {
+    type Rejection = WebSocketUpgradeRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if parts.method != Method::GET {
+            return Err(WebSocketUpgradeRejection::MethodNotGet);
+        }
+        if parts.version > Version::HTTP_11 {
+            return Err(WebSocketUpgradeRejection::UnsupportedHttpVersion);
+        }
+        if !header_contains(&parts.headers, header::CONNECTION, "upgrade") {
+            return Err(WebSocketUpgradeRejection::InvalidConnectionHeader);
+        }
+        if !header_eq(&parts.headers, header::UPGRADE, "websocket") {
+            return Err(WebSocketUpgradeRejection::InvalidUpgradeHeader);
+        }
+        if !header_eq(&parts.headers, header::SEC_WEBSOCKET_VERSION, "13") {
+            return Err(WebSocketUpgradeRejection::InvalidWebSocketVersionHeader);
+        }
+
+        let sec_websocket_key = parts
+            .headers
+            .get(header::SEC_WEBSOCKET_KEY)
+            .cloned()
+            .ok_or(WebSocketUpgradeRejection::WebSocketKeyHeaderMissing)?;
+
+        let on_upgrade = parts
+            .extensions
+            .remove::<OnUpgrade>()
+            .ok_or(WebSocketUpgradeRejection::ConnectionNotUpgradable)?;
+
+        let sec_websocket_protocol = parts.headers.get(header::SEC_WEBSOCKET_PROTOCOL).cloned();
+
+        Ok(Self {
+            config: WebSocketConfig::default(),
+            sec_websocket_key,
+            sec_websocket_protocol,
+            protocol: None,
+            on_upgrade,
+        })
+    }
+}
+
+fn header_eq(headers: &HeaderMap, key: HeaderName, value: &'static str) -> bool {
+    headers
+        .get(&key)
+        .is_some_and(|header| header.as_bytes().eq_ignore_ascii_case(value.as_bytes()))
+}
+
+fn header_contains(headers: &HeaderMap, key: HeaderName, value: &'static str) -> bool {
+    headers
+        .get_all(&key)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case(value))
+}
+
+/// Rejection used by [`WebSocketUpgrade`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebSocketUpgradeRejection {
+    MethodNotGet,
+    UnsupportedHttpVersion,
+    InvalidConnectionHeader,
+    InvalidUpgradeHeader,
+    InvalidWebSocketVersionHeader,
+    WebSocketKeyHeaderMissing,
+    /// The connection was not served with upgrade support, e.g. it did not go
+    /// through [`serve`](crate::serve::serve).
+    ConnectionNotUpgradable,
+}
+
+impl WebSocketUpgradeRejection {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::MethodNotGet => StatusCode::METHOD_NOT_ALLOWED,
+            Self::ConnectionNotUpgradable => StatusCode::UPGRADE_REQUIRED,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    pub fn body_text(&self) -> &'static str {
+        match self {
+            Self::MethodNotGet => "Request method must be `GET`",
+            Self::UnsupportedHttpVersion => "WebSocket upgrades require HTTP/1.1",
+            Self::InvalidConnectionHeader => "Connection header did not include 'upgrade'",
+            Self::InvalidUpgradeHeader => "`Upgrade` header did not include 'websocket'",
+            Self::InvalidWebSocketVersionHeader => {
+                "`Sec-WebSocket-Version` header did not include '13'"
+            }
+            Self::WebSocketKeyHeaderMissing => "`Sec-WebSocket-Key` header missing",
+            Self::ConnectionNotUpgradable => {
+                "WebSocket request couldn't be upgraded since no upgrade state was present"
+            }
+        }
+    }
+}
+
+impl fmt::Display for WebSocketUpgradeRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.body_text())
+    }
+}
+
+impl std::error::Error for WebSocketUpgradeRejection {}
+
+impl IntoResponse for WebSocketUpgradeRejection {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(self.status())
+            .detail(self.body_text())
+            .into_rejection()
+    }
+}
+
+/// A message sent or received over a [`WebSocket`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Bytes),
+    /// Pings are answered with a pong automatically, they are still passed on for
+    /// inspection.
+    Ping(Bytes),
+    Pong(Bytes),
+    /// The peer started the closing handshake. The close frame has been answered
+    /// already, [`WebSocket::recv`] returns `None` afterwards.
+    Close(Option<CloseFrame>),
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_owned())
+    }
+}
+
+impl From<Bytes> for Message {
+    fn from(data: Bytes) -> Self {
+        Self::Binary(data)
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Binary(data.into())
+    }
+}
+
+/// The payload of a close frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+/// Status codes used when monet closes a connection itself.
+pub mod close_code {
+    pub const NORMAL: u16 = 1000;
+    pub const AWAY: u16 = 1001;
+    pub const PROTOCOL: u16 = 1002;
+    pub const UNSUPPORTED: u16 = 1003;
+    pub const INVALID: u16 = 1007;
+    pub const POLICY: u16 = 1008;
+    pub const SIZE: u16 = 1009;
+    pub const ERROR: u16 = 1011;
+}
+
+/// Errors of an established [`WebSocket`].
+#[derive(Debug)]
+pub enum WebSocketError {
+    Io(io::Error),
+    /// The peer violated the protocol. The connection has been closed with
+    /// [`close_code::PROTOCOL`].
+    Protocol(&'static str),
+    /// A frame or message exceeded the configured limits. The connection has been
+    /// closed with [`close_code::SIZE`].
+    MessageTooBig,
+    /// A text message was not valid UTF-8. The connection has been closed with
+    /// [`close_code::INVALID`].
+    InvalidUtf8,
+    /// A message was sent after the close frame.
+    AlreadyClosed,
+}
+
+impl WebSocketError {
+    fn close_code(&self) -> Option<u16> {
+        match self {
+            Self::Protocol(_) => Some(close_code::PROTOCOL),
+            Self::MessageTooBig => Some(close_code::SIZE),
+            Self::InvalidUtf8 => Some(close_code::INVALID),
+            Self::Io(_) | Self::AlreadyClosed => None,
+        }
+    }
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "WebSocket IO error: {err}"),
+            Self::Protocol(reason) => write!(f, "WebSocket protocol error: {reason}"),
+            Self::MessageTooBig => f.write_str("WebSocket message too big"),
+            Self::InvalidUtf8 => f.write_str("WebSocket text message is not valid UTF-8"),
+            Self::AlreadyClosed => f.write_str("WebSocket is already closed"),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WebSocketError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_bits(bits: u8) -> Option<Self> {
+        Some(match bits {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            _ => return None,
+        })
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        matches!(self, Self::Close | Self::Ping | Self::Pong)
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    fin: bool,
+    opcode: OpCode,
+    payload: Bytes,
+}
+
+/// An established WebSocket connection, see [`WebSocketUpgrade`].
+///
+/// Messages are read with [`WebSocket::recv`], or through the monoio
+/// [`Stream`](monoio::io::stream::Stream) impl, and written with
+/// [`WebSocket::send`] or the [`Sink`](monoio::io::sink::Sink) impl. The socket is
+/// bound to the core the connection was accepted on.
+pub struct WebSocket {
+    io: TokioIo<Upgraded>,
+    config: WebSocketConfig,
+    protocol: Option<HeaderValue>,
+    read_buf: BytesMut,
+    fragments: Option<(OpCode, BytesMut)>,
+    close_sent: bool,
+    close_received: bool,
+    _not_send: PhantomData<Rc<()>>,
+}
+
+impl fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("protocol", &self.protocol)
+            .field("close_sent", &self.close_sent)
+            .field("close_received", &self.close_received)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebSocket {
+    fn new(upgraded: Upgraded, config: WebSocketConfig, protocol: Option<HeaderValue>) -> Self {
+        Self {
+            io: TokioIo::new(upgraded),
+            config,
+            protocol,
+            read_buf: BytesMut::with_capacity(8 * 1024),
+            fragments: None,
+            close_sent: false,
+            close_received: false,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The subprotocol negotiated during the handshake.
+    pub fn protocol(&self) -> Option<&HeaderValue> {
+        self.protocol.as_ref()
+    }
+
+    /// Receive the next message. Returns `None` once the connection is closed.
+    ///
+    /// Protocol violations close the connection with the matching status code and
+    /// are reported as the last item.
+    pub async fn recv(&mut self) -> Option<Result<Message, WebSocketError>> {
+        if self.close_received {
+            return None;
+        }
+
+        match self.read_message().await {
+            Ok(Some(message)) => Some(Ok(message)),
+            Ok(None) => {
+                self.close_received = true;
+                None
+            }
+            Err(err) => {
+                self.close_received = true;
+                if let Some(code) = err.close_code() {
+                    let _ = self
+                        .close(Some(CloseFrame {
+                            code,
+                            reason: String::new(),
+                        }))
+                        .await;
+                }
+                Some(Err(err))
+            }
+        }
+    }
+
+    /// Send a message. Text and binary messages are fragmented according to
+    /// [`WebSocketUpgrade::write_frame_size`].
+    pub async fn send(&mut self, message: Message) -> Result<(), WebSocketError> {
+        if self.close_sent {
+            return Err(WebSocketError::AlreadyClosed);
+        }
+
+        match message {
+            Message::Text(text) => self.write_data(OpCode::Text, text.as_bytes()).await,
+            Message::Binary(data) => self.write_data(OpCode::Binary, &data).await,
+            Message::Ping(data) => self.write_frame(true, OpCode::Ping, &data).await,
+            Message::Pong(data) => self.write_frame(true, OpCode::Pong, &data).await,
+            Message::Close(frame) => self.close(frame).await,
+        }
+    }
+
+    /// Start the closing handshake, if it hasn't been started yet.
+    pub async fn close(&mut self, frame: Option<CloseFrame>) -> Result<(), WebSocketError> {
+        if self.close_sent {
+            return Ok(());
+        }
+        self.close_sent = true;
+
+        let mut payload = BytesMut::new();
+        if let Some(CloseFrame { code, reason }) = frame {
+            payload.put_u16(code);
+            payload.put_slice(reason.as_bytes());
+        }
+        self.write_frame(true, OpCode::Close, &payload).await
+    }
+
+    async fn read_message(&mut self) -> Result<Option<Message>, WebSocketError> {
+        loop {
+            let Some(frame) = self.read_frame().await? else {
+                return Ok(None);
+            };
+
+            match frame.opcode {
+                OpCode::Ping => {
+                    if !self.close_sent {
+                        self.write_frame(true, OpCode::Pong, &frame.payload).await?;
+                    }
+                    return Ok(Some(Message::Ping(frame.payload)));
+                }
+                OpCode::Pong => return Ok(Some(Message::Pong(frame.payload))),
+                OpCode::Close => {
+                    let close_frame = parse_close(frame.payload)?;
+                    let reply = close_frame.as_ref().map(|frame| CloseFrame {
+                        code: frame.code,
+                        reason: String::new(),
+                    });
+                    self.close(reply).await?;
+                    self.close_received = true;
+                    return Ok(Some(Message::Close(close_frame)));
+                }
+                OpCode::Text | OpCode::Binary => {
+                    if self.fragments.is_some() {
+                        return Err(WebSocketError::Protocol(
+                            "new data frame before the fragmented message finished",
+                        ));
+                    }
+                    if frame.fin {
+                        return data_message(frame.opcode, frame.payload).map(Some);
+                    }
+                    self.fragments = Some((frame.opcode, BytesMut::from(&frame.payload[..])));
+                }
+                OpCode::Continuation => {
+                    let Some((_, buf)) = self.fragments.as_mut() else {
+                        return Err(WebSocketError::Protocol(
+                            "continuation frame without a fragmented message",
+                        ));
+                    };
+                    if buf.len() + frame.payload.len() > self.config.max_message_size {
+                        return Err(WebSocketError::MessageTooBig);
+                    }
+                    buf.extend_from_slice(&frame.payload);
+
+                    if frame.fin {
+                        let (opcode, buf) = self.fragments.take().expect("checked above");
+                        return data_message(opcode, buf.freeze()).map(Some);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn read_frame(&mut self) -> Result<Option<Frame>, WebSocketError> {
+        let max_size = self.config.max_frame_size.min(self.config.max_message_size);
+        loop {
+            if let Some(frame) = decode_frame(&mut self.read_buf, max_size)? {
+                return Ok(Some(frame));
+            }
+
+            if self.io.read_buf(&mut self.read_buf).await? == 0 {
+                if self.read_buf.is_empty() {
+                    return Ok(None);
+                }
+                return Err(WebSocketError::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+
+    async fn write_data(&mut self, opcode: OpCode, data: &[u8]) -> Result<(), WebSocketError> {
+        let Some(frame_size) = self.config.write_frame_size else {
+            return self.write_frame(true, opcode, data).await;
+        };
+
+        let mut chunks = data.chunks(frame_size).peekable();
+        let mut opcode = opcode;
+        if chunks.peek().is_none() {
+            return self.write_frame(true, opcode, data).await;
+        }
+        while let Some(chunk) = chunks.next() {
+            let fin = chunks.peek().is_none();
+            self.write_frame(fin, opcode, chunk).await?;
+            opcode = OpCode::Continuation;
+        }
+        Ok(())
+    }
+
+    async fn write_frame(
+        &mut self,
+        fin: bool,
+        opcode: OpCode,
+        payload: &[u8],
+    ) -> Result<(), WebSocketError> {
+        let mut buf = BytesMut::with_capacity(payload.len() + 10);
+        buf.put_u8(((fin as u8) << 7) | opcode.bits());
+        match payload.len() {
+            len @ 0..=125 => buf.put_u8(len as u8),
+            len @ 126..=0xFFFF => {
+                buf.put_u8(126);
+                buf.put_u16(len as u16);
+            }
+            len => {
+                buf.put_u8(127);
+                buf.put_u64(len as u64);
+            }
+        }
+        buf.put_slice(payload);
+
+        self.io.write_all(&buf).await?;
+        self.io.flush().await?;
+        Ok(())
+    }
+}
+
+/// Take the next client frame off the front of `read_buf`, unmasked, or `None` if
+/// it hasn't been fully received yet.
+fn decode_frame(read_buf: &mut BytesMut, max_size: usize) -> Result<Option<Frame>, WebSocketError> {
+    let buf = &read_buf[..];
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+
+    let fin = buf[0] & 0x80 != 0;
+    if buf[0] & 0x70 != 0 {
+        return Err(WebSocketError::Protocol("reserved bits must be zero"));
+    }
+    let opcode =
+        OpCode::from_bits(buf[0] & 0x0F).ok_or(WebSocketError::Protocol("unknown opcode"))?;
+
+    if buf[1] & 0x80 == 0 {
+        return Err(WebSocketError::Protocol("client frames must be masked"));
+    }
+
+    let (len, mut header_len) = match buf[1] & 0x7F {
+        126 if buf.len() < 4 => return Ok(None),
+        126 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() < 10 => return Ok(None),
+        127 => (u64::from_be_bytes(buf[2..10].try_into().unwrap()), 10),
+        len => (len as u64, 2),
+    };
+
+    if opcode.is_control() && (!fin || len > 125) {
+        return Err(WebSocketError::Protocol(
+            "control frames must not be fragmented or exceed 125 bytes",
+        ));
+    }
+    if len > max_size as u64 {
+        return Err(WebSocketError::MessageTooBig);
+    }
+    let len = len as usize;
+
+    if buf.len() < header_len + 4 + len {
+        read_buf.reserve(header_len + 4 + len - buf.len());
+        return Ok(None);
+    }
+
+    let mask: [u8; 4] = buf[header_len..header_len + 4].try_into().unwrap();
+    header_len += 4;
+
+    read_buf.advance(header_len);
+    let mut payload = read_buf.split_to(len);
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Some(Frame {
+        fin,
+        opcode,
+        payload: payload.freeze(),
+    }))
+}
+
+fn data_message(opcode: OpCode, payload: Bytes) -> Result<Message, WebSocketError> {
+    match opcode {
+        OpCode::Text => String::from_utf8(payload.into())
+            .map(Message::Text)
+            .map_err(|_| WebSocketError::InvalidUtf8),
+        _ => Ok(Message::Binary(payload)),
+    }
+}
+
+fn parse_close(mut payload: Bytes) -> Result<Option<CloseFrame>, WebSocketError> {
+    match payload.len() {
+        0 => Ok(None),
+        1 => Err(WebSocketError::Protocol("close frame payload of one byte")),
+        _ => {
+            let code = payload.get_u16();
+            if !is_valid_close_code(code) {
+                return Err(WebSocketError::Protocol("invalid close code"));
+            }
+            let reason =
+                String::from_utf8(payload.into()).map_err(|_| WebSocketError::InvalidUtf8)?;
+            Ok(Some(CloseFrame { code, reason }))
+        }
+    }
+}
+
+/// Whether a peer may send `code` in a close frame. 1005, 1006 and 1015 stand in
+/// for a missing code and must never be sent, and the rest below 3000 is reserved
+/// for the protocol itself, except for the codes registered with IANA.
+fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+impl monoio::io::stream::Stream for WebSocket {
+    type Item = Result<Message, WebSocketError>;
+
+    fn next(&mut self) -> impl Future<Output = Option<Self::Item>> {
+        self.recv()
+    }
+}
+
+impl monoio::io::sink::Sink<Message> for WebSocket {
+    type Error = WebSocketError;
+
+    fn send(&mut self, item: Message) -> impl Future<Output = Result<(), Self::Error>> {
+        WebSocket::send(self, item)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.io.flush().await?;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), Self::Error> {
+        WebSocket::close(self, None).await?;
+        self.io.shutdown().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Router, get,
+        test::{RequestBuilder, TestClient},
+    };
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    /// A masked frame as sent by a client, with `first` as its first byte.
+    fn client_frame(first: u8, payload: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u8(first);
+        match payload.len() {
+            len @ 0..=125 => buf.put_u8(0x80 | len as u8),
+            len @ 126..=0xFFFF => {
+                buf.put_u8(0x80 | 126);
+                buf.put_u16(len as u16);
+            }
+            len => {
+                buf.put_u8(0x80 | 127);
+                buf.put_u64(len as u64);
+            }
+        }
+        buf.put_slice(&MASK);
+        buf.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ MASK[i % 4]),
+        );
+        buf
+    }
+
+    fn decode(buf: &mut BytesMut) -> Result<Option<Frame>, WebSocketError> {
+        decode_frame(buf, DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    #[test]
+    fn decodes_masked_frames() {
+        let mut buf = client_frame(0x81, b"Hello");
+        let frame = decode(&mut buf).unwrap().unwrap();
+        assert!(frame.fin);
+        assert_eq!(frame.opcode, OpCode::Text);
+        assert_eq!(frame.payload, "Hello");
+        assert!(buf.is_empty());
+
+        let mut buf = client_frame(0x02, &[1, 2, 3]);
+        let frame = decode(&mut buf).unwrap().unwrap();
+        assert!(!frame.fin);
+        assert_eq!(frame.opcode, OpCode::Binary);
+        assert_eq!(frame.payload, &[1, 2, 3][..]);
+    }
+
+    #[test]
+    fn decodes_extended_lengths() {
+        for len in [125, 126, 200, 0xFFFF, 0x10000] {
+            let payload = vec![0xAB; len];
+            let mut buf = client_frame(0x82, &payload);
+            let frame = decode(&mut buf).unwrap().unwrap();
+            assert_eq!(frame.payload.len(), len);
+            assert!(frame.payload.iter().all(|&byte| byte == 0xAB));
+        }
+    }
+
+    #[test]
+    fn waits_for_the_whole_frame() {
+        let frame = client_frame(0x81, &[b'x'; 300]);
+        // Split inside the extended length, inside the mask and inside the payload.
+        for split in [1, 3, 6, 100] {
+            let mut buf = BytesMut::from(&frame[..split]);
+            assert!(decode(&mut buf).unwrap().is_none(), "split at {split}");
+            assert_eq!(buf.len(), split);
+
+            buf.extend_from_slice(&frame[split..]);
+            let decoded = decode(&mut buf).unwrap().unwrap();
+            assert_eq!(decoded.payload.len(), 300);
+        }
+    }
+
+    #[test]
+    fn decodes_consecutive_frames() {
+        let mut buf = client_frame(0x01, b"Hel");
+        buf.extend_from_slice(&client_frame(0x89, b"ping"));
+        buf.extend_from_slice(&client_frame(0x80, b"lo"));
+
+        let first = decode(&mut buf).unwrap().unwrap();
+        assert_eq!(
+            (first.opcode, &first.payload[..]),
+            (OpCode::Text, &b"Hel"[..])
+        );
+        let ping = decode(&mut buf).unwrap().unwrap();
+        assert_eq!(
+            (ping.opcode, &ping.payload[..]),
+            (OpCode::Ping, &b"ping"[..])
+        );
+        let last = decode(&mut buf).unwrap().unwrap();
+        assert_eq!(last.opcode, OpCode::Continuation);
+        assert!(last.fin);
+        assert!(decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_protocol_violations() {
+        let mut unmasked = BytesMut::from(&[0x81, 0x02, b'h', b'i'][..]);
+        let mut reserved_bits = client_frame(0xC1, b"hi");
+        let mut unknown_opcode = client_frame(0x83, b"hi");
+        let mut fragmented_ping = client_frame(0x09, b"hi");
+        let mut long_ping = client_frame(0x89, &[0; 126]);
+
+        for buf in [
+            &mut unmasked,
+            &mut reserved_bits,
+            &mut unknown_opcode,
+            &mut fragmented_ping,
+            &mut long_ping,
+        ] {
+            assert!(matches!(decode(buf), Err(WebSocketError::Protocol(_))));
+        }
+    }
+
+    #[test]
+    fn rejects_frames_over_the_limit_from_their_header() {
+        let frame = client_frame(0x82, &[0; 2000]);
+        // The header alone is enough to reject the frame.
+        let mut buf = BytesMut::from(&frame[..8]);
+        assert!(matches!(
+            decode_frame(&mut buf, 1024),
+            Err(WebSocketError::MessageTooBig)
+        ));
+
+        let mut buf = frame.clone();
+        assert!(decode_frame(&mut buf, 2000).unwrap().is_some());
+    }
+
+    #[test]
+    fn text_messages_must_be_utf8() {
+        let text = data_message(OpCode::Text, Bytes::from_static("héllo".as_bytes())).unwrap();
+        assert!(matches!(text, Message::Text(text) if text == "héllo"));
+
+        let invalid = data_message(OpCode::Text, Bytes::from_static(&[0xFF, 0xFE]));
+        assert!(matches!(invalid, Err(WebSocketError::InvalidUtf8)));
+
+        let binary = data_message(OpCode::Binary, Bytes::from_static(&[0xFF])).unwrap();
+        assert!(matches!(binary, Message::Binary(data) if data[..] == [0xFF]));
+    }
+
+    #[test]
+    fn parses_close_payloads() {
+        assert!(parse_close(Bytes::new()).unwrap().is_none());
+
+        let frame = parse_close(Bytes::from_static(b"\x03\xe8bye"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.code, close_code::NORMAL);
+        assert_eq!(frame.reason, "bye");
+
+        assert!(matches!(
+            parse_close(Bytes::from_static(b"\x03")),
+            Err(WebSocketError::Protocol(_))
+        ));
+        assert!(matches!(
+            parse_close(Bytes::from_static(b"\x03\xe8\xFF")),
+            Err(WebSocketError::InvalidUtf8)
+        ));
+    }
+
+    #[test]
+    fn rejects_reserved_close_codes() {
+        for code in [0, 999, 1004, 1005, 1006, 1015, 1016, 2999, 5000, u16::MAX] {
+            let result = parse_close(Bytes::copy_from_slice(&code.to_be_bytes()));
+            assert!(
+                matches!(result, Err(WebSocketError::Protocol(_))),
+                "accepted {code}"
+            );
+        }
+        for code in [1000u16, 1001, 1002, 1003, 1007, 1011, 1014, 3000, 4999] {
+            let frame = parse_close(Bytes::copy_from_slice(&code.to_be_bytes()))
+                .unwrap()
+                .unwrap();
+            assert_eq!(frame.code, code);
+        }
+    }
+
+    fn handshake<'a>(client: &'a TestClient, version: &'static str) -> RequestBuilder<'a> {
+        client
+            .get("/ws")
+            .header(header::CONNECTION, "keep-alive, Upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_VERSION, version)
+            .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+    }
+
+    #[monoio::test]
+    async fn rejects_invalid_handshakes() {
+        let app = Router::new().route(
+            "/ws",
+            get(|ws: WebSocketUpgrade| async move { ws.on_upgrade(|_socket| async {}) }),
+        );
+        let client = TestClient::new(app);
+
+        client
+            .get("/ws")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        handshake(&client, "12")
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        // A valid handshake still needs a connection that can be upgraded.
+        handshake(&client, "13")
+            .send()
+            .await
+            .assert_status(StatusCode::UPGRADE_REQUIRED);
+    }
+}
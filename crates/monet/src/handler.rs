@@ -17,7 +17,7 @@ pub trait Handler<X, S>: Clone + Sized + 'static {
 }
 
 pub trait HandlerWithoutStateExt<T>: Handler<T, ()> {
-    /// Convert the handler into a [`Service`] and no state.
+    /// Convert the handler into a [`Service`](tower::Service) and no state.
     fn into_service(self) -> HandlerService<Self, T, ()>;
 }
 
@@ -83,28 +83,6 @@ macro_rules! impl_handler {
     };
 }
 
-#[rustfmt::skip]
-macro_rules! all_the_tuples {
-    ($name:ident) => {
-        // $name!([], T1);
-        // $name!([T1], T2);
-        // $name!([T1, T2], T3);
-        $name!([T1, T2, T3], T4);
-        $name!([T1, T2, T3, T4], T5);
-        $name!([T1, T2, T3, T4, T5], T6);
-        $name!([T1, T2, T3, T4, T5, T6], T7);
-        $name!([T1, T2, T3, T4, T5, T6, T7], T8);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8], T9);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9], T10);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10], T11);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11], T12);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12], T13);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13], T14);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14], T15);
-        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15], T16);
-    };
-}
-
 all_the_tuples!(impl_handler);
 
 #[allow(non_snake_case, unused_mut)]
@@ -8,10 +8,7 @@ use futures::future::Map;
 
 use crate::{
     Body, BoxError, HttpBody, HttpRequest, HttpResponse, TowerService,
-    extract::{FromRequest, FromRequestParts},
     handler::{Handler, HandlerService},
-    opaque_future,
-    response::IntoResponse,
 };
 
 impl<H, X, S, B> TowerService<HttpRequest<B>> for HandlerService<H, X, S>
@@ -27,7 +24,7 @@ where
 
     type Future = IntoServiceFuture<H::Future>;
 
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
@@ -44,13 +41,11 @@ where
     }
 }
 
+type IntoResult = fn(HttpResponse) -> Result<HttpResponse, Infallible>;
+
 opaque_future! {
     /// The response future for [`IntoService`](super::IntoService).
-    pub type IntoServiceFuture<F> =
-        Map<
-            F,
-            fn(HttpResponse) -> Result<HttpResponse, Infallible>,
-        >;
+    pub type IntoServiceFuture<F> = Map<F, IntoResult>;
 }
 
 impl<H, X, S> HandlerService<H, X, S> {
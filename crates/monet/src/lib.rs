@@ -1,25 +1,36 @@
-#![allow(clippy::all)]
-#![allow(warnings)]
-
 pub use self::{
-    extract::state::State, response::IntoResponse, routing::method_router::get,
-    routing::route::Route, routing::router::Router, serve::serve,
+    extract::from_ref::FromRef, extract::json::Json, extract::state::State,
+    extract::task_scope::TaskScope, response::IntoResponse, response::Problem,
+    routing::method_router::get, routing::route::Route, routing::router::Router, serve::serve,
+    serve::serve_unix,
 };
 pub use bytes::Bytes;
 pub use http_body::{Body as HttpBody, Frame};
 use http_body_util::BodyExt;
+#[cfg(feature = "macros")]
+pub use monet_macros::debug_handler;
 use std::borrow::Cow;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+#[macro_use]
+pub(crate) mod macros;
+
 pub mod extract;
 pub mod handler;
 pub mod handler_tower_impl;
+pub mod middleware;
+#[cfg(feature = "openapi")]
+pub mod openapi;
 pub mod response;
 pub mod routing;
 pub mod serve;
-#[macro_use]
-pub(crate) mod macros;
+pub mod services;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub(crate) mod util;
 pub(crate) mod prelude {
     pub use crate::{
         Body, BoxError, HttpBody, HttpRequest, HttpResponse, IntoResponse, Route, TowerLayer,
@@ -0,0 +1,47 @@
+//! Utilities for writing middleware.
+
+pub mod auth;
+pub mod body_limit;
+pub mod catch_panic;
+#[cfg(feature = "compression")]
+mod codec;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod cors;
+#[cfg(feature = "compression")]
+pub mod decompression;
+pub mod from_extractor;
+pub mod from_fn;
+pub mod map_request;
+pub mod map_response;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_id;
+pub mod timeout;
+pub mod trace;
+
+pub use self::{
+    auth::{Auth, AuthLayer},
+    body_limit::{DEFAULT_BODY_LIMIT, DefaultBodyLimit, DefaultBodyLimitService},
+    catch_panic::{CatchPanic, CatchPanicLayer},
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, Cors, CorsLayer, ExposeHeaders},
+    from_extractor::{
+        FromExtractor, FromExtractorLayer, from_extractor, from_extractor_with_state,
+    },
+    from_fn::{FromFn, FromFnLayer, Next, from_fn, from_fn_with_state},
+    map_request::{
+        IntoMapRequestResult, MapRequest, MapRequestLayer, map_request, map_request_with_state,
+    },
+    map_response::{MapResponse, MapResponseLayer, map_response, map_response_with_state},
+    metrics::{Metrics, MetricsLayer, MetricsRegistry},
+    rate_limit::{KeyBy, RateLimit, RateLimitLayer},
+    request_id::{RequestId, RequestIdLayer, SetRequestId},
+    timeout::{Deadline, TimeoutLayer, TimeoutService},
+    trace::{Trace, TraceLayer},
+};
+#[cfg(feature = "compression")]
+pub use self::{
+    codec::CompressionLevel,
+    compression::{Compression, CompressionLayer},
+    decompression::{RequestDecompression, RequestDecompressionLayer},
+};
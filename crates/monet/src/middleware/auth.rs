@@ -0,0 +1,288 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use crate::{
+    extract::{FromRequestParts, auth::Authenticated},
+    prelude::*,
+};
+
+type Validator<E, S, P> =
+    Rc<dyn Fn(E, S) -> Pin<Box<dyn Future<Output = Result<P, HttpResponse>>>>>;
+
+/// Authenticate requests with a validator, before they reach the inner service.
+///
+/// The credentials are extracted with `E`, usually
+/// [`BasicAuth`](crate::extract::auth::BasicAuth),
+/// [`BearerToken`](crate::extract::auth::BearerToken) or
+/// [`ApiKey`](crate::extract::auth::ApiKey), and passed to the validator. If it
+/// accepts them, the principal it returns is inserted into the request
+/// extensions, where handlers get it with [`Authenticated`]. Otherwise its
+/// rejection is returned, like the rejection of the extractor when there are no
+/// credentials.
+///
+/// ```no_run
+/// # use monet::{Router, get, extract::auth::{Authenticated, AuthRejection, BearerToken}};
+/// # use monet::middleware::AuthLayer;
+/// # #[derive(Clone)]
+/// # struct Db;
+/// # impl Db {
+/// #     async fn user_for_token(&self, _: &str) -> Option<User> { None }
+/// # }
+/// # #[derive(Clone)]
+/// # struct User { name: String }
+/// # let db = Db;
+/// async fn validate(BearerToken(token): BearerToken, db: Db) -> Result<User, AuthRejection> {
+///     db.user_for_token(&token).await.ok_or(AuthRejection::bearer())
+/// }
+///
+/// async fn me(Authenticated(user): Authenticated<User>) -> String {
+///     format!("Hello, {}", user.name)
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/me", get(me))
+///     .route_layer(AuthLayer::with_state(db, validate));
+/// ```
+pub struct AuthLayer<E, P, S = ()> {
+    validator: Validator<E, S, P>,
+    state: S,
+}
+
+impl<E, P> AuthLayer<E, P>
+where
+    E: 'static,
+    P: 'static,
+{
+    /// Authenticate with `validator`, which receives the extracted credentials.
+    pub fn new<F, Fut, R>(validator: F) -> Self
+    where
+        F: Fn(E) -> Fut + 'static,
+        Fut: Future<Output = Result<P, R>> + 'static,
+        R: IntoResponse,
+    {
+        Self::with_state((), move |credentials, ()| validator(credentials))
+    }
+}
+
+impl<E, P, S> AuthLayer<E, P, S>
+where
+    E: 'static,
+    P: 'static,
+{
+    /// Like [`AuthLayer::new`], but `validator` also receives a clone of `state`,
+    /// and the extractor has access to it.
+    pub fn with_state<F, Fut, R>(state: S, validator: F) -> Self
+    where
+        F: Fn(E, S) -> Fut + 'static,
+        Fut: Future<Output = Result<P, R>> + 'static,
+        R: IntoResponse,
+    {
+        let validator: Validator<E, S, P> = Rc::new(move |credentials, state| {
+            let future = validator(credentials, state);
+            Box::pin(async move { future.await.map_err(IntoResponse::into_response) })
+        });
+        Self { validator, state }
+    }
+}
+
+impl<E, P, S> Clone for AuthLayer<E, P, S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            validator: self.validator.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<E, P, S> fmt::Debug for AuthLayer<E, P, S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthLayer")
+            .field("credentials", &std::any::type_name::<E>())
+            .field("principal", &std::any::type_name::<P>())
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, E, P, S> TowerLayer<I> for AuthLayer<E, P, S>
+where
+    S: Clone,
+{
+    type Service = Auth<I, E, P, S>;
+
+    fn layer(&self, inner: I) -> Self::Service {
+        Auth {
+            inner,
+            validator: self.validator.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Authenticates requests, see [`AuthLayer`].
+pub struct Auth<I, E, P, S = ()> {
+    inner: I,
+    validator: Validator<E, S, P>,
+    state: S,
+}
+
+impl<I, E, P, S> Clone for Auth<I, E, P, S>
+where
+    I: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            validator: self.validator.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<I, E, P, S> fmt::Debug for Auth<I, E, P, S>
+where
+    I: fmt::Debug,
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Auth")
+            .field("inner", &self.inner)
+            .field("credentials", &std::any::type_name::<E>())
+            .field("principal", &std::any::type_name::<P>())
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, E, P, S> TowerService<HttpRequest> for Auth<I, E, P, S>
+where
+    E: FromRequestParts<S> + 'static,
+    P: Clone + Send + Sync + 'static,
+    I: TowerService<HttpRequest, Error = Infallible> + Clone + 'static,
+    I::Response: IntoResponse + 'static,
+    I::Future: 'static,
+    S: Clone + 'static,
+{
+    type Response = HttpResponse;
+    type Error = Infallible;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: HttpRequest) -> Self::Future {
+        let validator = self.validator.clone();
+        let state = self.state.clone();
+        let mut inner = self.inner.clone();
+
+        let future = Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+
+            let credentials = match E::from_request_parts(&mut parts, &state).await {
+                Ok(credentials) => credentials,
+                Err(rejection) => return Ok(rejection.into_response()),
+            };
+            let principal = match validator(credentials, state).await {
+                Ok(principal) => principal,
+                Err(res) => return Ok(res),
+            };
+            parts.extensions.insert(Authenticated(principal));
+
+            let req = HttpRequest::from_parts(parts, body);
+            match inner.call(req).await {
+                Ok(res) => Ok(res.into_response()),
+                Err(err) => match err {},
+            }
+        });
+
+        ResponseFuture::new(future)
+    }
+}
+
+opaque_future! {
+    /// Response future for [`Auth`].
+    pub type ResponseFuture =
+        Pin<Box<dyn Future<Output = Result<HttpResponse, Infallible>>>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{
+        StatusCode,
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    };
+
+    use super::*;
+    use crate::{
+        Router,
+        extract::auth::{AuthRejection, BearerToken},
+        get,
+        test::TestClient,
+    };
+
+    #[derive(Clone)]
+    struct User(String);
+
+    async fn validate(BearerToken(token): BearerToken) -> Result<User, AuthRejection> {
+        match token.as_str() {
+            "secret" => Ok(User("ferris".to_owned())),
+            _ => Err(AuthRejection::bearer()),
+        }
+    }
+
+    async fn me(Authenticated(User(name)): Authenticated<User>) -> String {
+        name
+    }
+
+    #[monoio::test]
+    async fn validates_credentials() {
+        let app = Router::new()
+            .route("/me", get(me))
+            .route_layer(AuthLayer::new(validate));
+        let client = TestClient::new(app);
+
+        client
+            .get("/me")
+            .header(AUTHORIZATION, "Bearer secret")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("ferris");
+        client
+            .get("/me")
+            .header(AUTHORIZATION, "Bearer wrong")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED)
+            .assert_header(WWW_AUTHENTICATE, "Bearer error=\"invalid_token\"");
+        client
+            .get("/me")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[monoio::test]
+    async fn authenticated_without_the_layer_is_a_server_error() {
+        let app = Router::new().route("/me", get(me));
+
+        TestClient::new(app)
+            .get("/me")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,150 @@
+use std::task::{Context, Poll};
+
+use crate::prelude::*;
+
+/// The limit applied by extractors that buffer the whole request body, such as
+/// [`Json`](crate::Json) and [`Form`](crate::extract::form::Form), when no
+/// [`DefaultBodyLimit`] is set.
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Set the maximum size of the request bodies buffered by extractors, which is
+/// [`DEFAULT_BODY_LIMIT`] otherwise.
+///
+/// Larger bodies are rejected with `413 Content Too Large`, before they are read
+/// when the request announces its `Content-Length`. Only extractors that read the
+/// whole body are affected, handlers taking the [`HttpRequest`] can still stream
+/// bodies of any size.
+///
+/// ```no_run
+/// # use monet::{Router, get, middleware::DefaultBodyLimit};
+/// # async fn upload() {}
+/// let app: Router = Router::new()
+///     .route("/upload", get(upload))
+///     .layer(DefaultBodyLimit::max(64 * 1024 * 1024));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct DefaultBodyLimit {
+    limit: BodyLimit,
+}
+
+/// The limit recorded in the request's extensions by [`DefaultBodyLimit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BodyLimit {
+    Max(usize),
+    Disabled,
+}
+
+impl BodyLimit {
+    /// The limit for `req`, `None` if it was disabled.
+    pub(crate) fn of<B>(req: &HttpRequest<B>) -> Option<usize> {
+        match req.extensions().get::<Self>() {
+            Some(Self::Disabled) => None,
+            Some(Self::Max(limit)) => Some(*limit),
+            None => Some(DEFAULT_BODY_LIMIT),
+        }
+    }
+}
+
+impl DefaultBodyLimit {
+    /// Reject bodies larger than `limit` bytes.
+    pub fn max(limit: usize) -> Self {
+        Self {
+            limit: BodyLimit::Max(limit),
+        }
+    }
+
+    /// Buffer bodies of any size.
+    pub fn disable() -> Self {
+        Self {
+            limit: BodyLimit::Disabled,
+        }
+    }
+}
+
+impl<S> TowerLayer<S> for DefaultBodyLimit {
+    type Service = DefaultBodyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DefaultBodyLimitService {
+            inner,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Records the body limit of requests, see [`DefaultBodyLimit`].
+#[derive(Clone, Debug)]
+pub struct DefaultBodyLimitService<S> {
+    inner: S,
+    limit: BodyLimit,
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for DefaultBodyLimitService<S>
+where
+    S: TowerService<HttpRequest<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: HttpRequest<B>) -> Self::Future {
+        req.extensions_mut().insert(self.limit);
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{Json, Router, test::TestClient};
+
+    fn client(limit: DefaultBodyLimit) -> TestClient {
+        TestClient::new(
+            Router::new()
+                .fallback(|Json(value): Json<Value>| async move { Json(value) })
+                .layer(limit),
+        )
+    }
+
+    #[monoio::test]
+    async fn rejects_bodies_over_the_limit() {
+        let client = client(DefaultBodyLimit::max(16));
+        client
+            .post("/")
+            .json(&json!({ "a": 1 }))
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+        client
+            .post("/")
+            .json(&json!({ "text": "more than sixteen bytes" }))
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[monoio::test]
+    async fn can_be_disabled() {
+        let large = json!({ "text": "x".repeat(DEFAULT_BODY_LIMIT) });
+        client(DefaultBodyLimit::disable())
+            .post("/")
+            .json(&large)
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+        client(DefaultBodyLimit::max(DEFAULT_BODY_LIMIT))
+            .post("/")
+            .json(&large)
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
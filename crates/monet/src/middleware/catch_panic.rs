@@ -0,0 +1,222 @@
+use std::{
+    any::Any,
+    future::Future,
+    panic::{AssertUnwindSafe, catch_unwind},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, ready},
+};
+
+use futures_util::future::{CatchUnwind, FutureExt};
+use http::StatusCode;
+use pin_project_lite::pin_project;
+
+use crate::{Problem, prelude::*};
+
+type PanicHandler = Rc<dyn Fn(Box<dyn Any + Send>) -> HttpResponse>;
+
+/// Turn panics in handlers and the services below into `500 Internal Server
+/// Error` responses.
+///
+/// Without it a panic unwinds out of the connection task, dropping the
+/// connection without a response. With it the client gets a response, the panic
+/// message is logged with [`tracing`] at the `ERROR` level and the connection
+/// stays open for further requests.
+///
+/// Only panics raised while producing the response head are caught, not those
+/// raised while the body is streamed. The process' panic hook still runs as
+/// usual.
+///
+/// ```no_run
+/// # use http::StatusCode;
+/// # use monet::{Problem, Router, get, middleware::CatchPanicLayer};
+/// # async fn root() {}
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(CatchPanicLayer::custom(|_panic| {
+///         Problem::new(StatusCode::INTERNAL_SERVER_ERROR).detail("something went wrong")
+///     }));
+/// ```
+#[derive(Clone, Default)]
+pub struct CatchPanicLayer {
+    handler: Option<PanicHandler>,
+}
+
+impl CatchPanicLayer {
+    /// Respond to panics with an empty `500 Internal Server Error`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Respond to panics with the response `handler` builds from the panic
+    /// payload.
+    pub fn custom<F, R>(handler: F) -> Self
+    where
+        F: Fn(Box<dyn Any + Send>) -> R + 'static,
+        R: IntoResponse,
+    {
+        Self {
+            handler: Some(Rc::new(move |panic| handler(panic).into_response())),
+        }
+    }
+}
+
+impl fmt::Debug for CatchPanicLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CatchPanicLayer")
+            .field("custom", &self.handler.is_some())
+            .finish()
+    }
+}
+
+impl<S> TowerLayer<S> for CatchPanicLayer {
+    type Service = CatchPanic<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CatchPanic {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Catches panics, see [`CatchPanicLayer`].
+#[derive(Clone, Debug)]
+pub struct CatchPanic<S> {
+    inner: S,
+    layer: CatchPanicLayer,
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for CatchPanic<S>
+where
+    S: TowerService<HttpRequest<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: HttpRequest<B>) -> Self::Future {
+        match catch_unwind(AssertUnwindSafe(|| self.inner.call(req))) {
+            Ok(future) => ResponseFuture {
+                kind: Kind::Future {
+                    future: AssertUnwindSafe(future).catch_unwind(),
+                    layer: Some(self.layer.clone()),
+                },
+            },
+            Err(panic) => ResponseFuture {
+                kind: Kind::Panicked {
+                    res: Some(self.layer.response_for(panic)),
+                },
+            },
+        }
+    }
+}
+
+impl CatchPanicLayer {
+    fn response_for(&self, panic: Box<dyn Any + Send>) -> HttpResponse {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str));
+        match message {
+            Some(message) => tracing::error!(panic = message, "handler panicked"),
+            None => tracing::error!("handler panicked"),
+        }
+
+        match &self.handler {
+            Some(handler) => handler(panic),
+            None => Problem::new(StatusCode::INTERNAL_SERVER_ERROR).into_rejection(),
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`CatchPanic`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        kind: Kind<F>,
+    }
+}
+
+pin_project! {
+    #[project = KindProj]
+    enum Kind<F> {
+        Future {
+            #[pin]
+            future: CatchUnwind<AssertUnwindSafe<F>>,
+            layer: Option<CatchPanicLayer>,
+        },
+        Panicked { res: Option<HttpResponse> },
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().kind.project() {
+            KindProj::Future { future, layer } => match ready!(future.poll(cx)) {
+                Ok(res) => Poll::Ready(res.map(IntoResponse::into_response)),
+                Err(panic) => {
+                    let layer = layer.take().expect("future polled after completion");
+                    Poll::Ready(Ok(layer.response_for(panic)))
+                }
+            },
+            KindProj::Panicked { res } => {
+                Poll::Ready(Ok(res.take().expect("future polled after completion")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntoResponse, Router, get, test::TestClient};
+
+    async fn boom() -> &'static str {
+        panic!("boom")
+    }
+
+    #[monoio::test]
+    async fn turns_panics_into_500s() {
+        let app = Router::new()
+            .route("/", get(boom))
+            .layer(CatchPanicLayer::new());
+
+        TestClient::new(app)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[monoio::test]
+    async fn custom_handlers_see_the_payload() {
+        let app = Router::new()
+            .route("/", get(boom))
+            .layer(CatchPanicLayer::custom(|panic| {
+                let message = panic.downcast_ref::<&str>().copied().unwrap_or_default();
+                let mut res = message.to_owned().into_response();
+                *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                res
+            }));
+
+        TestClient::new(app)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::SERVICE_UNAVAILABLE)
+            .assert_text("boom");
+    }
+}
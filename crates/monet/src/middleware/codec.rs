@@ -0,0 +1,483 @@
+//! The content codings shared by [`CompressionLayer`](super::CompressionLayer) and
+//! [`RequestDecompressionLayer`](super::RequestDecompressionLayer).
+//!
+//! Encoders work on whole frames: every frame of the original body is fed through
+//! the encoder and whatever it produced is flushed out as one frame. Streaming
+//! bodies such as server-sent events therefore stay streaming, at the cost of a
+//! slightly worse ratio for bodies made of many tiny frames.
+//!
+//! Decoders can't do the same, as a few bytes of input may inflate to gigabytes of
+//! output. They decode into chunks of [`DECODE_CHUNK_SIZE`] bytes instead, one
+//! chunk at a time.
+
+use std::io::{self, BufRead, Read, Write};
+
+use bytes::{Buf, Bytes, BytesMut};
+use http::{HeaderMap, HeaderValue, header};
+
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const BROTLI_LGWIN: u32 = 22;
+/// The most a [`Decoder`] hands out at once.
+pub(crate) const DECODE_CHUNK_SIZE: usize = 16 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Gzip,
+    Deflate,
+    Br,
+    Zstd,
+}
+
+impl Encoding {
+    /// The order we prefer codings in when the client likes several equally.
+    const PREFERENCE: [Self; 4] = [Self::Br, Self::Zstd, Self::Gzip, Self::Deflate];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Br => "br",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    pub(crate) fn header_value(self) -> HeaderValue {
+        HeaderValue::from_static(self.as_str())
+    }
+
+    pub(crate) fn from_coding(coding: &str) -> Option<Self> {
+        Self::PREFERENCE
+            .into_iter()
+            .find(|encoding| coding.eq_ignore_ascii_case(encoding.as_str()))
+            .or_else(|| coding.eq_ignore_ascii_case("x-gzip").then_some(Self::Gzip))
+    }
+}
+
+/// Which codings a layer supports.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AcceptEncoding {
+    pub(crate) gzip: bool,
+    pub(crate) deflate: bool,
+    pub(crate) br: bool,
+    pub(crate) zstd: bool,
+}
+
+impl Default for AcceptEncoding {
+    fn default() -> Self {
+        Self {
+            gzip: true,
+            deflate: true,
+            br: true,
+            zstd: true,
+        }
+    }
+}
+
+impl AcceptEncoding {
+    pub(crate) fn supports(&self, encoding: Encoding) -> bool {
+        match encoding {
+            Encoding::Gzip => self.gzip,
+            Encoding::Deflate => self.deflate,
+            Encoding::Br => self.br,
+            Encoding::Zstd => self.zstd,
+        }
+    }
+
+    /// The supported codings as an `Accept-Encoding` value.
+    pub(crate) fn header_value(&self) -> HeaderValue {
+        let codings = Encoding::PREFERENCE
+            .into_iter()
+            .filter(|encoding| self.supports(*encoding))
+            .map(Encoding::as_str)
+            .collect::<Vec<_>>()
+            .join(",");
+        HeaderValue::from_str(&codings).expect("codings are valid header values")
+    }
+
+    /// Pick the coding for a response from the request's `Accept-Encoding` header:
+    /// the supported one with the highest q-value, ties broken by our preference.
+    pub(crate) fn negotiate(&self, headers: &HeaderMap) -> Option<Encoding> {
+        let mut best: Option<(Encoding, f32)> = None;
+
+        for encoding in Encoding::PREFERENCE {
+            if !self.supports(encoding) {
+                continue;
+            }
+            let Some(q) = qvalue(headers, encoding) else {
+                continue;
+            };
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((encoding, q));
+            }
+        }
+
+        best.map(|(encoding, _)| encoding)
+    }
+}
+
+/// The q-value the client gives `encoding`, if it mentions it at all.
+fn qvalue(headers: &HeaderMap, encoding: Encoding) -> Option<f32> {
+    let mut wildcard = None;
+
+    for item in headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let mut params = item.split(';');
+        let coding = params.next().unwrap_or_default().trim();
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if Encoding::from_coding(coding) == Some(encoding) {
+            return Some(q);
+        }
+        if coding == "*" {
+            wildcard = Some(q);
+        }
+    }
+
+    wildcard
+}
+
+/// The level of compression to use, see [`CompressionLayer::quality`].
+///
+/// [`CompressionLayer::quality`]: super::CompressionLayer::quality
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompressionLevel {
+    Fastest,
+    Best,
+    /// A trade-off between speed and size that suits on-the-fly compression. For
+    /// brotli this is level 4 rather than the library default of 11.
+    #[default]
+    Default,
+    /// An algorithm specific level, clamped to the range the algorithm supports.
+    Precise(i32),
+}
+
+impl CompressionLevel {
+    fn flate2(self) -> flate2::Compression {
+        match self {
+            Self::Fastest => flate2::Compression::fast(),
+            Self::Best => flate2::Compression::best(),
+            Self::Default => flate2::Compression::default(),
+            Self::Precise(level) => flate2::Compression::new(level.clamp(0, 9) as u32),
+        }
+    }
+
+    fn brotli(self) -> u32 {
+        match self {
+            Self::Fastest => 0,
+            Self::Best => 11,
+            Self::Default => 4,
+            Self::Precise(level) => level.clamp(0, 11) as u32,
+        }
+    }
+
+    fn zstd(self) -> i32 {
+        let range = zstd::compression_level_range();
+        match self {
+            Self::Fastest => *range.start(),
+            Self::Best => *range.end(),
+            Self::Default => zstd::DEFAULT_COMPRESSION_LEVEL,
+            Self::Precise(level) => level.clamp(*range.start(), *range.end()),
+        }
+    }
+}
+
+pub(crate) enum Encoder {
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    Deflate(flate2::write::ZlibEncoder<Vec<u8>>),
+    Br(Box<brotli::CompressorWriter<Vec<u8>>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Encoder {
+    pub(crate) fn new(encoding: Encoding, level: CompressionLevel) -> io::Result<Self> {
+        Ok(match encoding {
+            Encoding::Gzip => Self::Gzip(flate2::write::GzEncoder::new(Vec::new(), level.flate2())),
+            Encoding::Deflate => {
+                Self::Deflate(flate2::write::ZlibEncoder::new(Vec::new(), level.flate2()))
+            }
+            Encoding::Br => Self::Br(Box::new(brotli::CompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER_SIZE,
+                level.brotli(),
+                BROTLI_LGWIN,
+            ))),
+            Encoding::Zstd => {
+                Self::Zstd(zstd::stream::write::Encoder::new(Vec::new(), level.zstd())?)
+            }
+        })
+    }
+
+    /// Compress `data` and return everything that can be sent so far.
+    pub(crate) fn encode(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        match self {
+            Self::Gzip(encoder) => flush_out(encoder, data, |encoder| encoder.get_mut()),
+            Self::Deflate(encoder) => flush_out(encoder, data, |encoder| encoder.get_mut()),
+            Self::Br(encoder) => flush_out(&mut **encoder, data, |encoder| encoder.get_mut()),
+            Self::Zstd(encoder) => flush_out(encoder, data, |encoder| encoder.get_mut()),
+        }
+    }
+
+    /// End the stream and return the remaining output.
+    pub(crate) fn finish(self) -> io::Result<Bytes> {
+        let out = match self {
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Deflate(encoder) => encoder.finish()?,
+            Self::Br(encoder) => encoder.into_inner(),
+            Self::Zstd(encoder) => encoder.finish()?,
+        };
+        Ok(out.into())
+    }
+}
+
+/// Compressed input waiting to be read by a [`Decoder`]. Reading from it while it
+/// is empty fails with `WouldBlock` until the end of the input was marked, so the
+/// decompressors stop where they are and resume once more input was pushed.
+#[derive(Debug, Default)]
+pub(crate) struct Input {
+    data: Bytes,
+    ended: bool,
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.data.is_empty() && !self.ended {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        Ok(&self.data)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.data.advance(amt);
+    }
+}
+
+/// A decompressor fed with [`Decoder::push`] and drained with
+/// [`Decoder::decode`], which hands out at most [`DECODE_CHUNK_SIZE`] bytes at a
+/// time no matter how well the input compresses.
+pub(crate) struct Decoder {
+    reader: Reader,
+    done: bool,
+}
+
+enum Reader {
+    Gzip(flate2::bufread::MultiGzDecoder<Input>),
+    Deflate(flate2::bufread::ZlibDecoder<Input>),
+    Br(Box<brotli::Decompressor<Input>>),
+    Zstd(zstd::stream::read::Decoder<'static, Input>),
+}
+
+impl Decoder {
+    pub(crate) fn new(encoding: Encoding) -> io::Result<Self> {
+        let input = Input::default();
+        let reader = match encoding {
+            Encoding::Gzip => Reader::Gzip(flate2::bufread::MultiGzDecoder::new(input)),
+            Encoding::Deflate => Reader::Deflate(flate2::bufread::ZlibDecoder::new(input)),
+            Encoding::Br => Reader::Br(Box::new(brotli::Decompressor::new(
+                input,
+                BROTLI_BUFFER_SIZE,
+            ))),
+            Encoding::Zstd => Reader::Zstd(zstd::stream::read::Decoder::with_buffer(input)?),
+        };
+        Ok(Self {
+            reader,
+            done: false,
+        })
+    }
+
+    fn input(&mut self) -> &mut Input {
+        match &mut self.reader {
+            Reader::Gzip(reader) => reader.get_mut(),
+            Reader::Deflate(reader) => reader.get_mut(),
+            Reader::Br(reader) => reader.get_mut(),
+            Reader::Zstd(reader) => reader.get_mut(),
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.reader {
+            Reader::Gzip(reader) => reader.read(buf),
+            Reader::Deflate(reader) => reader.read(buf),
+            Reader::Br(reader) => reader.read(buf),
+            Reader::Zstd(reader) => reader.read(buf),
+        }
+    }
+
+    /// Queue the next piece of compressed input, to be decoded by `decode`.
+    pub(crate) fn push(&mut self, data: Bytes) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.done {
+            return Err(trailing_data());
+        }
+        let input = self.input();
+        if input.data.is_empty() {
+            input.data = data;
+        } else {
+            let mut joined = BytesMut::with_capacity(input.data.len() + data.len());
+            joined.extend_from_slice(&input.data);
+            joined.extend_from_slice(&data);
+            input.data = joined.freeze();
+        }
+        Ok(())
+    }
+
+    /// Mark the end of the input, after which `decode` fails if the stream was
+    /// truncated.
+    pub(crate) fn end(&mut self) {
+        self.input().ended = true;
+    }
+
+    /// Decompress the queued input, returning up to [`DECODE_CHUNK_SIZE`] bytes, or
+    /// `None` once everything was decoded that can be until more input is pushed.
+    pub(crate) fn decode(&mut self) -> io::Result<Option<Bytes>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut out = vec![0; DECODE_CHUNK_SIZE];
+        let mut len = 0;
+        while len < out.len() {
+            match self.read(&mut out[len..]) {
+                Ok(0) => {
+                    self.done = true;
+                    if !self.input().data.is_empty() {
+                        return Err(trailing_data());
+                    }
+                    break;
+                }
+                Ok(n) => len += n,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        if len == 0 {
+            return Ok(None);
+        }
+        out.truncate(len);
+        Ok(Some(out.into()))
+    }
+}
+
+fn trailing_data() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "data after the end of the compressed stream",
+    )
+}
+
+fn flush_out<W>(
+    writer: &mut W,
+    data: &[u8],
+    buffer: impl FnOnce(&mut W) -> &mut Vec<u8>,
+) -> io::Result<Bytes>
+where
+    W: Write,
+{
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(std::mem::take(buffer(writer)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODINGS: [Encoding; 4] = Encoding::PREFERENCE;
+
+    fn compress(encoding: Encoding, data: &[u8]) -> Vec<u8> {
+        let mut encoder = Encoder::new(encoding, CompressionLevel::Default).unwrap();
+        let mut out = encoder.encode(data).unwrap().to_vec();
+        out.extend_from_slice(&encoder.finish().unwrap());
+        out
+    }
+
+    /// Decode `data` fed in pieces of `piece` bytes, checking the chunk sizes.
+    fn decompress(encoding: Encoding, data: &[u8], piece: usize) -> io::Result<Vec<u8>> {
+        let mut decoder = Decoder::new(encoding)?;
+        let mut out = Vec::new();
+        let mut drain = |decoder: &mut Decoder| {
+            while let Some(chunk) = decoder.decode()? {
+                assert!(chunk.len() <= DECODE_CHUNK_SIZE);
+                out.extend_from_slice(&chunk);
+            }
+            io::Result::Ok(())
+        };
+        for piece in data.chunks(piece) {
+            decoder.push(Bytes::copy_from_slice(piece))?;
+            drain(&mut decoder)?;
+        }
+        decoder.end();
+        drain(&mut decoder)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn round_trips_in_bounded_chunks() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        for encoding in ENCODINGS {
+            let compressed = compress(encoding, &data);
+            for piece in [1, 7, 4096, compressed.len()] {
+                assert_eq!(
+                    decompress(encoding, &compressed, piece).unwrap(),
+                    data,
+                    "{encoding:?} in pieces of {piece}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hands_out_highly_compressed_input_a_chunk_at_a_time() {
+        let data = vec![0; 8 * 1024 * 1024];
+        for encoding in ENCODINGS {
+            let compressed = compress(encoding, &data);
+            let mut decoder = Decoder::new(encoding).unwrap();
+            decoder.push(compressed.into()).unwrap();
+            let chunk = decoder.decode().unwrap().unwrap();
+            assert_eq!(chunk.len(), DECODE_CHUNK_SIZE, "{encoding:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_streams() {
+        let data = b"hello world, hello world, hello world";
+        for encoding in ENCODINGS {
+            let compressed = compress(encoding, data);
+            let truncated = &compressed[..compressed.len() - 4];
+            assert!(
+                decompress(encoding, truncated, truncated.len()).is_err(),
+                "{encoding:?}"
+            );
+        }
+
+        let compressed = compress(Encoding::Zstd, data);
+        let err = decompress(Encoding::Zstd, &compressed[..compressed.len() - 4], 16).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rejects_data_after_the_stream() {
+        let mut compressed = compress(Encoding::Deflate, b"hello");
+        compressed.extend_from_slice(b"junk");
+        let err = decompress(Encoding::Deflate, &compressed, compressed.len()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
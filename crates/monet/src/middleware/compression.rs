@@ -0,0 +1,455 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, StatusCode, header};
+use pin_project_lite::pin_project;
+
+use super::codec::{AcceptEncoding, CompressionLevel, Encoder, Encoding};
+use crate::{Frame, prelude::*};
+
+const DEFAULT_MIN_SIZE: u64 = 32;
+
+/// Compress response bodies with gzip, deflate, brotli or zstd, whichever the
+/// client prefers according to its `Accept-Encoding` header.
+///
+/// Responses are left alone when they
+/// - already have a `Content-Encoding`, or are partial content,
+/// - are known to be smaller than [`CompressionLayer::min_size`],
+/// - have a content type that is compressed already, such as images (except SVG),
+///   video, audio or archives.
+///
+/// ```no_run
+/// # use monet::{Router, get, middleware::CompressionLayer};
+/// # async fn root() {}
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(CompressionLayer::new().no_deflate());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CompressionLayer {
+    accept: AcceptEncoding,
+    level: CompressionLevel,
+    min_size: u64,
+}
+
+impl Default for CompressionLayer {
+    fn default() -> Self {
+        Self {
+            accept: AcceptEncoding::default(),
+            level: CompressionLevel::default(),
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+}
+
+impl CompressionLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn gzip(mut self, enable: bool) -> Self {
+        self.accept.gzip = enable;
+        self
+    }
+
+    pub fn deflate(mut self, enable: bool) -> Self {
+        self.accept.deflate = enable;
+        self
+    }
+
+    pub fn br(mut self, enable: bool) -> Self {
+        self.accept.br = enable;
+        self
+    }
+
+    pub fn zstd(mut self, enable: bool) -> Self {
+        self.accept.zstd = enable;
+        self
+    }
+
+    pub fn no_gzip(self) -> Self {
+        self.gzip(false)
+    }
+
+    pub fn no_deflate(self) -> Self {
+        self.deflate(false)
+    }
+
+    pub fn no_br(self) -> Self {
+        self.br(false)
+    }
+
+    pub fn no_zstd(self) -> Self {
+        self.zstd(false)
+    }
+
+    pub fn quality(mut self, level: CompressionLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Bodies whose exact size is known and below `min_size` bytes are sent
+    /// uncompressed. Defaults to 32 bytes.
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size;
+        self
+    }
+}
+
+impl<S> TowerLayer<S> for CompressionLayer {
+    type Service = Compression<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Compression {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// Compresses response bodies, see [`CompressionLayer`].
+#[derive(Clone, Debug)]
+pub struct Compression<S> {
+    inner: S,
+    layer: CompressionLayer,
+}
+
+impl<S, ReqBody, ResBody> TowerService<HttpRequest<ReqBody>> for Compression<S>
+where
+    S: TowerService<HttpRequest<ReqBody>, Response = HttpResponse<ResBody>>,
+    ResBody: HttpBody<Data = Bytes>,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = HttpResponse<CompressionBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: HttpRequest<ReqBody>) -> Self::Future {
+        let encoding = self.layer.accept.negotiate(req.headers());
+        ResponseFuture {
+            inner: self.inner.call(req),
+            encoding,
+            layer: self.layer,
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`Compression`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        encoding: Option<Encoding>,
+        layer: CompressionLayer,
+    }
+}
+
+impl<F, B, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<HttpResponse<B>, E>>,
+    B: HttpBody<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Output = Result<HttpResponse<CompressionBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.inner.poll(cx)?);
+
+        if !should_compress(&res, this.layer.min_size) {
+            return Poll::Ready(Ok(res.map(CompressionBody::identity)));
+        }
+
+        let (mut parts, body) = res.into_parts();
+        // The representation depends on `Accept-Encoding` from here on, whether or
+        // not this particular client gets it compressed.
+        parts
+            .headers
+            .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+        let encoder = this
+            .encoding
+            .and_then(|encoding| Some((encoding, Encoder::new(encoding, this.layer.level).ok()?)));
+        let Some((encoding, encoder)) = encoder else {
+            return Poll::Ready(Ok(HttpResponse::from_parts(
+                parts,
+                CompressionBody::identity(body),
+            )));
+        };
+
+        parts
+            .headers
+            .insert(header::CONTENT_ENCODING, encoding.header_value());
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.remove(header::ACCEPT_RANGES);
+
+        Poll::Ready(Ok(HttpResponse::from_parts(
+            parts,
+            CompressionBody::encoded(body, encoder),
+        )))
+    }
+}
+
+fn should_compress<B>(res: &HttpResponse<B>, min_size: u64) -> bool
+where
+    B: HttpBody,
+{
+    let headers = res.headers();
+    if headers.contains_key(header::CONTENT_ENCODING)
+        || headers.contains_key(header::CONTENT_RANGE)
+        || res.status() == StatusCode::NO_CONTENT
+        || res.status() == StatusCode::NOT_MODIFIED
+    {
+        return false;
+    }
+
+    if res.body().is_end_stream() {
+        return false;
+    }
+    let size = res
+        .body()
+        .size_hint()
+        .exact()
+        .or_else(|| content_length(headers));
+    if size.is_some_and(|size| size < min_size) {
+        return false;
+    }
+
+    compressible_content_type(headers)
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+fn compressible_content_type(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return true;
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if essence == "image/svg+xml" {
+        return true;
+    }
+    if ["image/", "video/", "audio/", "font/woff"]
+        .iter()
+        .any(|prefix| essence.starts_with(prefix))
+    {
+        return false;
+    }
+    !matches!(
+        essence.as_str(),
+        "application/gzip"
+            | "application/x-gzip"
+            | "application/zip"
+            | "application/zstd"
+            | "application/x-bzip2"
+            | "application/x-7z-compressed"
+            | "application/x-rar-compressed"
+            | "application/x-xz"
+            | "application/grpc"
+    )
+}
+
+pin_project! {
+    /// Response body of [`Compression`], compressed or passed through as is.
+    pub struct CompressionBody<B> {
+        #[pin]
+        inner: B,
+        state: EncodeState,
+    }
+}
+
+enum EncodeState {
+    Identity,
+    Encoding(Encoder),
+    /// The encoder was finished before trailers, which are sent next.
+    Trailers(HeaderMap),
+    Done,
+}
+
+impl<B> CompressionBody<B> {
+    fn identity(inner: B) -> Self {
+        Self {
+            inner,
+            state: EncodeState::Identity,
+        }
+    }
+
+    fn encoded(inner: B, encoder: Encoder) -> Self {
+        Self {
+            inner,
+            state: EncodeState::Encoding(encoder),
+        }
+    }
+}
+
+impl<B> fmt::Debug for CompressionBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            EncodeState::Identity => "identity",
+            EncodeState::Encoding(_) => "encoding",
+            EncodeState::Trailers(_) => "trailers",
+            EncodeState::Done => "done",
+        };
+        f.debug_struct("CompressionBody")
+            .field("state", &state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B> HttpBody for CompressionBody<B>
+where
+    B: HttpBody<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+
+        loop {
+            match std::mem::replace(this.state, EncodeState::Done) {
+                EncodeState::Identity => {
+                    *this.state = EncodeState::Identity;
+                    return this.inner.poll_frame(cx).map_err(Into::into);
+                }
+                EncodeState::Done => return Poll::Ready(None),
+                EncodeState::Trailers(trailers) => {
+                    return Poll::Ready(Some(Ok(Frame::trailers(trailers))));
+                }
+                EncodeState::Encoding(mut encoder) => {
+                    let frame = match this.inner.as_mut().poll_frame(cx) {
+                        Poll::Pending => {
+                            *this.state = EncodeState::Encoding(encoder);
+                            return Poll::Pending;
+                        }
+                        Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err.into()))),
+                        Poll::Ready(Some(Ok(frame))) => frame,
+                        Poll::Ready(None) => {
+                            let rest = encoder.finish()?;
+                            return Poll::Ready(Some(Ok(Frame::data(rest))));
+                        }
+                    };
+
+                    match frame.into_data() {
+                        Ok(data) => {
+                            let out = encoder.encode(&data)?;
+                            *this.state = EncodeState::Encoding(encoder);
+                            if !out.is_empty() {
+                                return Poll::Ready(Some(Ok(Frame::data(out))));
+                            }
+                        }
+                        Err(frame) => {
+                            let Ok(trailers) = frame.into_trailers() else {
+                                *this.state = EncodeState::Encoding(encoder);
+                                continue;
+                            };
+                            *this.state = EncodeState::Trailers(trailers);
+                            let rest = encoder.finish()?;
+                            return Poll::Ready(Some(Ok(Frame::data(rest))));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self.state {
+            EncodeState::Identity => self.inner.is_end_stream(),
+            EncodeState::Done => true,
+            _ => false,
+        }
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        match self.state {
+            EncodeState::Identity => self.inner.size_hint(),
+            _ => http_body::SizeHint::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    const TEXT: &str = "the quick brown fox jumps over the lazy dog, again and again and again";
+
+    fn client() -> TestClient {
+        TestClient::new(
+            Router::new()
+                .route("/", get(|| async { TEXT }))
+                .route("/short", get(|| async { "short" }))
+                .layer(CompressionLayer::new()),
+        )
+    }
+
+    #[monoio::test]
+    async fn compresses_with_the_accepted_encoding() {
+        let res = client()
+            .get("/")
+            .header(header::ACCEPT_ENCODING, "br;q=0.5, gzip")
+            .send()
+            .await;
+        res.assert_status(StatusCode::OK)
+            .assert_header(header::CONTENT_ENCODING, "gzip")
+            .assert_header(header::VARY, "accept-encoding");
+
+        let mut decoded = String::new();
+        GzDecoder::new(&res.bytes()[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, TEXT);
+    }
+
+    #[monoio::test]
+    async fn leaves_other_responses_alone() {
+        let client = client();
+
+        let res = client.get("/").send().await;
+        assert_eq!(res.header(header::CONTENT_ENCODING), None);
+        res.assert_text(TEXT);
+
+        // Bodies below the minimum size are not worth compressing.
+        let res = client
+            .get("/short")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .send()
+            .await;
+        assert_eq!(res.header(header::CONTENT_ENCODING), None);
+        res.assert_text("short");
+    }
+}
@@ -0,0 +1,650 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
+use pin_project_lite::pin_project;
+
+use crate::prelude::*;
+
+const WILDCARD: HeaderValue = HeaderValue::from_static("*");
+
+/// Add [CORS] headers to responses and answer preflight requests.
+///
+/// Preflight requests, `OPTIONS` requests with both an `Origin` and an
+/// `Access-Control-Request-Method` header, are answered by the layer itself and
+/// never reach the inner service. Added with [`Router::layer`] this covers every
+/// route as well as the fallback, so routes don't need an `OPTIONS` handler.
+///
+/// A new `CorsLayer` allows nothing; use the builder methods to open it up, or
+/// start from [`CorsLayer::permissive`].
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use http::{HeaderValue, Method, header};
+/// # use monet::{Router, get, middleware::CorsLayer};
+/// # async fn root() {}
+/// let cors = CorsLayer::new()
+///     .allow_origin([
+///         HeaderValue::from_static("https://example.com"),
+///         HeaderValue::from_static("https://api.example.com"),
+///     ])
+///     .allow_methods([Method::GET, Method::POST])
+///     .allow_headers([header::CONTENT_TYPE])
+///     .allow_credentials(true)
+///     .max_age(Duration::from_secs(3600));
+///
+/// let app: Router = Router::new().route("/", get(root)).layer(cors);
+/// ```
+///
+/// # Panics
+///
+/// Layering a service panics if credentials are allowed together with a
+/// wildcard origin, methods, headers or exposed headers, since browsers reject
+/// that combination.
+///
+/// [CORS]: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
+/// [`Router::layer`]: crate::Router::layer
+#[derive(Clone, Debug, Default)]
+pub struct CorsLayer {
+    allow_origin: AllowOrigin,
+    allow_methods: AllowMethods,
+    allow_headers: AllowHeaders,
+    expose_headers: ExposeHeaders,
+    allow_credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl CorsLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow any origin, method and header and expose all headers, without
+    /// credentials.
+    pub fn permissive() -> Self {
+        Self::new()
+            .allow_origin(AllowOrigin::any())
+            .allow_methods(AllowMethods::any())
+            .allow_headers(AllowHeaders::any())
+            .expose_headers(ExposeHeaders::any())
+    }
+
+    /// Like [`CorsLayer::permissive`] but with credentials, mirroring the request
+    /// instead of using wildcards.
+    pub fn very_permissive() -> Self {
+        Self::new()
+            .allow_origin(AllowOrigin::mirror_request())
+            .allow_methods(AllowMethods::mirror_request())
+            .allow_headers(AllowHeaders::mirror_request())
+            .allow_credentials(true)
+    }
+
+    /// Set the `Access-Control-Allow-Origin` header.
+    pub fn allow_origin(mut self, origin: impl Into<AllowOrigin>) -> Self {
+        self.allow_origin = origin.into();
+        self
+    }
+
+    /// Set the `Access-Control-Allow-Methods` header sent in preflight responses.
+    pub fn allow_methods(mut self, methods: impl Into<AllowMethods>) -> Self {
+        self.allow_methods = methods.into();
+        self
+    }
+
+    /// Set the `Access-Control-Allow-Headers` header sent in preflight responses.
+    pub fn allow_headers(mut self, headers: impl Into<AllowHeaders>) -> Self {
+        self.allow_headers = headers.into();
+        self
+    }
+
+    /// Set the `Access-Control-Expose-Headers` header.
+    pub fn expose_headers(mut self, headers: impl Into<ExposeHeaders>) -> Self {
+        self.expose_headers = headers.into();
+        self
+    }
+
+    /// Send `Access-Control-Allow-Credentials: true`.
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    /// Set the `Access-Control-Max-Age` header sent in preflight responses, how
+    /// long browsers may cache the result of a preflight.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    fn ensure_usable(&self) {
+        if !self.allow_credentials {
+            return;
+        }
+        let wildcard = [
+            ("allow_origin", self.allow_origin.is_wildcard()),
+            (
+                "allow_methods",
+                matches!(self.allow_methods, AllowMethods::Any),
+            ),
+            (
+                "allow_headers",
+                matches!(self.allow_headers, AllowHeaders::Any),
+            ),
+            (
+                "expose_headers",
+                matches!(self.expose_headers, ExposeHeaders::Any),
+            ),
+        ]
+        .into_iter()
+        .find_map(|(name, wildcard)| wildcard.then_some(name));
+
+        if let Some(name) = wildcard {
+            panic!(
+                "Invalid CORS configuration: cannot combine `allow_credentials(true)` with a \
+                 wildcard `{name}`, use `mirror_request()` or a list instead"
+            );
+        }
+    }
+
+    /// The headers for an actual, non-preflight, response.
+    fn response_headers(&self, origin: Option<&HeaderValue>) -> HeaderMap {
+        let mut headers = self.origin_headers(origin);
+        if let Some(value) = self.expose_headers.header_value() {
+            headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, value);
+        }
+        headers
+    }
+
+    fn preflight_headers(&self, origin: Option<&HeaderValue>, request: &HeaderMap) -> HeaderMap {
+        let mut headers = self.origin_headers(origin);
+
+        if let Some(value) = self.allow_methods.header_value(request) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, value);
+        }
+        if let Some(value) = self.allow_headers.header_value(request) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+        if self.allow_methods.is_mirror() {
+            headers.append(
+                header::VARY,
+                HeaderValue::from_static("access-control-request-method"),
+            );
+        }
+        if self.allow_headers.is_mirror() {
+            headers.append(
+                header::VARY,
+                HeaderValue::from_static("access-control-request-headers"),
+            );
+        }
+        headers
+    }
+
+    fn origin_headers(&self, origin: Option<&HeaderValue>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Some(value) = origin.and_then(|origin| self.allow_origin.header_value(origin)) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+            if self.allow_credentials {
+                headers.insert(
+                    header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                    HeaderValue::from_static("true"),
+                );
+            }
+        }
+        // Unless every origin gets `*`, the response depends on the request's origin.
+        if !self.allow_origin.is_wildcard() {
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+        headers
+    }
+}
+
+impl<S> TowerLayer<S> for CorsLayer {
+    type Service = Cors<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        self.ensure_usable();
+        Cors {
+            inner,
+            layer: Rc::new(self.clone()),
+        }
+    }
+}
+
+/// Adds CORS headers and answers preflight requests, see [`CorsLayer`].
+#[derive(Clone, Debug)]
+pub struct Cors<S> {
+    inner: S,
+    layer: Rc<CorsLayer>,
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for Cors<S>
+where
+    S: TowerService<HttpRequest<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: HttpRequest<B>) -> Self::Future {
+        let origin = req.headers().get(header::ORIGIN);
+
+        if req.method() == Method::OPTIONS
+            && origin.is_some()
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            let headers = self.layer.preflight_headers(origin, req.headers());
+            return ResponseFuture::preflight(headers);
+        }
+
+        let headers = self.layer.response_headers(origin);
+        ResponseFuture::inner(self.inner.call(req), headers)
+    }
+}
+
+pin_project! {
+    /// Response future for [`Cors`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        kind: Kind<F>,
+    }
+}
+
+pin_project! {
+    #[project = KindProj]
+    enum Kind<F> {
+        Inner { #[pin] future: F, headers: Option<HeaderMap> },
+        Preflight { headers: Option<HeaderMap> },
+    }
+}
+
+impl<F> ResponseFuture<F> {
+    fn inner(future: F, headers: HeaderMap) -> Self {
+        Self {
+            kind: Kind::Inner {
+                future,
+                headers: Some(headers),
+            },
+        }
+    }
+
+    fn preflight(headers: HeaderMap) -> Self {
+        Self {
+            kind: Kind::Preflight {
+                headers: Some(headers),
+            },
+        }
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().kind.project() {
+            KindProj::Inner { future, headers } => {
+                let mut res = std::task::ready!(future.poll(cx)?).into_response();
+                let headers = headers.take().expect("future polled after completion");
+                apply_headers(res.headers_mut(), headers);
+                Poll::Ready(Ok(res))
+            }
+            KindProj::Preflight { headers } => {
+                let mut res = StatusCode::OK.into_response();
+                let headers = headers.take().expect("future polled after completion");
+                apply_headers(res.headers_mut(), headers);
+                Poll::Ready(Ok(res))
+            }
+        }
+    }
+}
+
+/// Add the CORS headers to a response, overriding any the handler set itself
+/// except for `Vary`, which is appended to.
+fn apply_headers(target: &mut HeaderMap, headers: HeaderMap) {
+    let mut name = None;
+    for (next, value) in headers {
+        name = next.or(name);
+        let name: &HeaderName = name.as_ref().expect("first item has a name");
+        if name == header::VARY {
+            target.append(header::VARY, value);
+        } else {
+            target.insert(name, value);
+        }
+    }
+}
+
+/// Which origins may access the resource, see [`CorsLayer::allow_origin`].
+#[derive(Clone, Default)]
+pub struct AllowOrigin(OriginInner);
+
+#[derive(Clone, Default)]
+enum OriginInner {
+    #[default]
+    None,
+    Any,
+    List(Vec<HeaderValue>),
+    Predicate(Rc<dyn Fn(&HeaderValue) -> bool>),
+    Mirror,
+}
+
+impl AllowOrigin {
+    /// Allow any origin with `Access-Control-Allow-Origin: *`.
+    pub fn any() -> Self {
+        Self(OriginInner::Any)
+    }
+
+    pub fn exact(origin: HeaderValue) -> Self {
+        Self(OriginInner::List(vec![origin]))
+    }
+
+    pub fn list<I>(origins: I) -> Self
+    where
+        I: IntoIterator<Item = HeaderValue>,
+    {
+        Self(OriginInner::List(origins.into_iter().collect()))
+    }
+
+    /// Allow the origins `predicate` returns `true` for.
+    pub fn predicate<F>(predicate: F) -> Self
+    where
+        F: Fn(&HeaderValue) -> bool + 'static,
+    {
+        Self(OriginInner::Predicate(Rc::new(predicate)))
+    }
+
+    /// Allow any origin by echoing the request's `Origin` header back. Unlike
+    /// [`AllowOrigin::any`] this can be combined with credentials.
+    pub fn mirror_request() -> Self {
+        Self(OriginInner::Mirror)
+    }
+
+    fn is_wildcard(&self) -> bool {
+        matches!(self.0, OriginInner::Any)
+    }
+
+    fn header_value(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.0 {
+            OriginInner::None => None,
+            OriginInner::Any => Some(WILDCARD),
+            OriginInner::List(origins) => origins.contains(origin).then(|| origin.clone()),
+            OriginInner::Predicate(predicate) => predicate(origin).then(|| origin.clone()),
+            OriginInner::Mirror => Some(origin.clone()),
+        }
+    }
+}
+
+impl fmt::Debug for AllowOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            OriginInner::None => f.debug_tuple("None").finish(),
+            OriginInner::Any => f.debug_tuple("Any").finish(),
+            OriginInner::List(origins) => f.debug_tuple("List").field(origins).finish(),
+            OriginInner::Predicate(_) => f.debug_tuple("Predicate").finish(),
+            OriginInner::Mirror => f.debug_tuple("Mirror").finish(),
+        }
+    }
+}
+
+impl From<HeaderValue> for AllowOrigin {
+    fn from(origin: HeaderValue) -> Self {
+        Self::exact(origin)
+    }
+}
+
+impl From<Vec<HeaderValue>> for AllowOrigin {
+    fn from(origins: Vec<HeaderValue>) -> Self {
+        Self::list(origins)
+    }
+}
+
+impl<const N: usize> From<[HeaderValue; N]> for AllowOrigin {
+    fn from(origins: [HeaderValue; N]) -> Self {
+        Self::list(origins)
+    }
+}
+
+/// Which methods may be used, see [`CorsLayer::allow_methods`].
+#[derive(Clone, Debug, Default)]
+pub enum AllowMethods {
+    #[default]
+    None,
+    Any,
+    List(Vec<Method>),
+    /// Allow whatever the preflight's `Access-Control-Request-Method` asks for.
+    Mirror,
+}
+
+impl AllowMethods {
+    pub fn any() -> Self {
+        Self::Any
+    }
+
+    pub fn list<I>(methods: I) -> Self
+    where
+        I: IntoIterator<Item = Method>,
+    {
+        Self::List(methods.into_iter().collect())
+    }
+
+    pub fn mirror_request() -> Self {
+        Self::Mirror
+    }
+
+    fn is_mirror(&self) -> bool {
+        matches!(self, Self::Mirror)
+    }
+
+    fn header_value(&self, request: &HeaderMap) -> Option<HeaderValue> {
+        match self {
+            Self::None => None,
+            Self::Any => Some(WILDCARD),
+            Self::List(methods) => join(methods.iter().map(Method::as_str)),
+            Self::Mirror => request.get(header::ACCESS_CONTROL_REQUEST_METHOD).cloned(),
+        }
+    }
+}
+
+impl From<Method> for AllowMethods {
+    fn from(method: Method) -> Self {
+        Self::List(vec![method])
+    }
+}
+
+impl From<Vec<Method>> for AllowMethods {
+    fn from(methods: Vec<Method>) -> Self {
+        Self::List(methods)
+    }
+}
+
+impl<const N: usize> From<[Method; N]> for AllowMethods {
+    fn from(methods: [Method; N]) -> Self {
+        Self::list(methods)
+    }
+}
+
+/// Which request headers may be used, see [`CorsLayer::allow_headers`].
+#[derive(Clone, Debug, Default)]
+pub enum AllowHeaders {
+    #[default]
+    None,
+    Any,
+    List(Vec<HeaderName>),
+    /// Allow whatever the preflight's `Access-Control-Request-Headers` asks for.
+    Mirror,
+}
+
+impl AllowHeaders {
+    pub fn any() -> Self {
+        Self::Any
+    }
+
+    pub fn list<I>(headers: I) -> Self
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        Self::List(headers.into_iter().collect())
+    }
+
+    pub fn mirror_request() -> Self {
+        Self::Mirror
+    }
+
+    fn is_mirror(&self) -> bool {
+        matches!(self, Self::Mirror)
+    }
+
+    fn header_value(&self, request: &HeaderMap) -> Option<HeaderValue> {
+        match self {
+            Self::None => None,
+            Self::Any => Some(WILDCARD),
+            Self::List(headers) => join(headers.iter().map(HeaderName::as_str)),
+            Self::Mirror => request.get(header::ACCESS_CONTROL_REQUEST_HEADERS).cloned(),
+        }
+    }
+}
+
+impl From<HeaderName> for AllowHeaders {
+    fn from(header: HeaderName) -> Self {
+        Self::List(vec![header])
+    }
+}
+
+impl From<Vec<HeaderName>> for AllowHeaders {
+    fn from(headers: Vec<HeaderName>) -> Self {
+        Self::List(headers)
+    }
+}
+
+impl<const N: usize> From<[HeaderName; N]> for AllowHeaders {
+    fn from(headers: [HeaderName; N]) -> Self {
+        Self::list(headers)
+    }
+}
+
+/// Which response headers scripts may read, see [`CorsLayer::expose_headers`].
+#[derive(Clone, Debug, Default)]
+pub enum ExposeHeaders {
+    #[default]
+    None,
+    Any,
+    List(Vec<HeaderName>),
+}
+
+impl ExposeHeaders {
+    pub fn any() -> Self {
+        Self::Any
+    }
+
+    pub fn list<I>(headers: I) -> Self
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        Self::List(headers.into_iter().collect())
+    }
+
+    fn header_value(&self) -> Option<HeaderValue> {
+        match self {
+            Self::None => None,
+            Self::Any => Some(WILDCARD),
+            Self::List(headers) => join(headers.iter().map(HeaderName::as_str)),
+        }
+    }
+}
+
+impl From<HeaderName> for ExposeHeaders {
+    fn from(header: HeaderName) -> Self {
+        Self::List(vec![header])
+    }
+}
+
+impl From<Vec<HeaderName>> for ExposeHeaders {
+    fn from(headers: Vec<HeaderName>) -> Self {
+        Self::List(headers)
+    }
+}
+
+impl<const N: usize> From<[HeaderName; N]> for ExposeHeaders {
+    fn from(headers: [HeaderName; N]) -> Self {
+        Self::list(headers)
+    }
+}
+
+fn join<'a>(items: impl Iterator<Item = &'a str>) -> Option<HeaderValue> {
+    let joined = items.collect::<Vec<_>>().join(",");
+    if joined.is_empty() {
+        return None;
+    }
+    Some(HeaderValue::from_str(&joined).expect("methods and header names are valid header values"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    fn client() -> TestClient {
+        TestClient::new(
+            Router::new().route("/", get(|| async { "hello" })).layer(
+                CorsLayer::new()
+                    .allow_origin(HeaderValue::from_static("https://example.com"))
+                    .allow_methods([Method::GET, Method::POST])
+                    .max_age(Duration::from_secs(60)),
+            ),
+        )
+    }
+
+    #[monoio::test]
+    async fn answers_preflight_requests() {
+        let res = client()
+            .options("/")
+            .header(header::ORIGIN, "https://example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .send()
+            .await;
+        res.assert_status(StatusCode::OK)
+            .assert_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "https://example.com")
+            .assert_header(header::ACCESS_CONTROL_ALLOW_METHODS, "GET,POST")
+            .assert_header(header::ACCESS_CONTROL_MAX_AGE, "60");
+        assert!(res.bytes().is_empty());
+    }
+
+    #[monoio::test]
+    async fn adds_headers_to_responses() {
+        let client = client();
+        client
+            .get("/")
+            .header(header::ORIGIN, "https://example.com")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "https://example.com")
+            .assert_text("hello");
+
+        let res = client
+            .get("/")
+            .header(header::ORIGIN, "https://evil.example")
+            .send()
+            .await;
+        assert_eq!(res.header(header::ACCESS_CONTROL_ALLOW_ORIGIN), None);
+    }
+}
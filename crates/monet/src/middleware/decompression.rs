@@ -0,0 +1,351 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode, header};
+use http_body_util::Limited;
+use pin_project_lite::pin_project;
+
+use super::{
+    body_limit::BodyLimit,
+    codec::{AcceptEncoding, Decoder, Encoding},
+};
+use crate::{Frame, Problem, prelude::*};
+
+/// Decompress request bodies sent with a gzip, deflate, brotli or zstd
+/// `Content-Encoding`, so handlers always see the plain body.
+///
+/// Requests with a coding that is not supported are rejected with
+/// `415 Unsupported Media Type` and an `Accept-Encoding` header listing the
+/// supported ones, see RFC 9110 section 15.5.16.
+///
+/// The decompressed body is capped at the limit set with
+/// [`DefaultBodyLimit`](super::DefaultBodyLimit), so a small request can't inflate
+/// into more than the server is willing to buffer. Extractors reject larger bodies
+/// with `413 Content Too Large`. The limit is read when the request passes this
+/// layer, so `DefaultBodyLimit` has to be added after it, to wrap it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestDecompressionLayer {
+    accept: AcceptEncoding,
+}
+
+impl RequestDecompressionLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn gzip(mut self, enable: bool) -> Self {
+        self.accept.gzip = enable;
+        self
+    }
+
+    pub fn deflate(mut self, enable: bool) -> Self {
+        self.accept.deflate = enable;
+        self
+    }
+
+    pub fn br(mut self, enable: bool) -> Self {
+        self.accept.br = enable;
+        self
+    }
+
+    pub fn zstd(mut self, enable: bool) -> Self {
+        self.accept.zstd = enable;
+        self
+    }
+
+    pub fn no_gzip(self) -> Self {
+        self.gzip(false)
+    }
+
+    pub fn no_deflate(self) -> Self {
+        self.deflate(false)
+    }
+
+    pub fn no_br(self) -> Self {
+        self.br(false)
+    }
+
+    pub fn no_zstd(self) -> Self {
+        self.zstd(false)
+    }
+}
+
+impl<S> TowerLayer<S> for RequestDecompressionLayer {
+    type Service = RequestDecompression<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestDecompression {
+            inner,
+            accept: self.accept,
+        }
+    }
+}
+
+/// Decompresses request bodies, see [`RequestDecompressionLayer`].
+#[derive(Clone, Debug)]
+pub struct RequestDecompression<S> {
+    inner: S,
+    accept: AcceptEncoding,
+}
+
+impl<S> TowerService<HttpRequest> for RequestDecompression<S>
+where
+    S: TowerService<HttpRequest>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: HttpRequest) -> Self::Future {
+        let encoding = match content_encoding(req.headers(), &self.accept) {
+            Ok(encoding) => encoding,
+            Err(()) => return ResponseFuture::unsupported(&self.accept),
+        };
+        let Some(encoding) = encoding else {
+            return ResponseFuture::inner(self.inner.call(req));
+        };
+        let Ok(decoder) = Decoder::new(encoding) else {
+            return ResponseFuture::unsupported(&self.accept);
+        };
+
+        let limit = BodyLimit::of(&req);
+        let (mut parts, body) = req.into_parts();
+        parts.headers.remove(header::CONTENT_ENCODING);
+        parts.headers.remove(header::CONTENT_LENGTH);
+        let body = DecompressionBody {
+            inner: body,
+            decoder,
+            trailers: None,
+            ended: false,
+        };
+        let body = match limit {
+            Some(limit) => Body::new(Limited::new(body, limit)),
+            None => Body::new(body),
+        };
+
+        ResponseFuture::inner(self.inner.call(HttpRequest::from_parts(parts, body)))
+    }
+}
+
+/// The coding of the request body, `Err` if it is one we can't decode.
+fn content_encoding(headers: &HeaderMap, accept: &AcceptEncoding) -> Result<Option<Encoding>, ()> {
+    let Some(value) = headers.get(header::CONTENT_ENCODING) else {
+        return Ok(None);
+    };
+    let coding = value.to_str().map_err(|_| ())?.trim();
+    if coding.is_empty() || coding.eq_ignore_ascii_case("identity") {
+        return Ok(None);
+    }
+    match Encoding::from_coding(coding) {
+        Some(encoding) if accept.supports(encoding) => Ok(Some(encoding)),
+        _ => Err(()),
+    }
+}
+
+pin_project! {
+    /// Response future for [`RequestDecompression`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        kind: Kind<F>,
+    }
+}
+
+pin_project! {
+    #[project = KindProj]
+    enum Kind<F> {
+        Inner { #[pin] future: F },
+        Unsupported { res: Option<HttpResponse> },
+    }
+}
+
+impl<F> ResponseFuture<F> {
+    fn inner(future: F) -> Self {
+        Self {
+            kind: Kind::Inner { future },
+        }
+    }
+
+    fn unsupported(accept: &AcceptEncoding) -> Self {
+        let mut res = Problem::new(StatusCode::UNSUPPORTED_MEDIA_TYPE).into_rejection();
+        res.headers_mut()
+            .insert(header::ACCEPT_ENCODING, accept.header_value());
+        Self {
+            kind: Kind::Unsupported { res: Some(res) },
+        }
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().kind.project() {
+            KindProj::Inner { future } => future.poll(cx).map_ok(IntoResponse::into_response),
+            KindProj::Unsupported { res } => {
+                Poll::Ready(Ok(res.take().expect("future polled after completion")))
+            }
+        }
+    }
+}
+
+pin_project! {
+    struct DecompressionBody<B> {
+        #[pin]
+        inner: B,
+        decoder: Decoder,
+        // Trailers that arrived while the decoder still had output to hand out.
+        trailers: Option<Frame<Bytes>>,
+        // Whether the inner body has no more data.
+        ended: bool,
+    }
+}
+
+impl<B> HttpBody for DecompressionBody<B>
+where
+    B: HttpBody<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+
+        loop {
+            if let Some(out) = this.decoder.decode()? {
+                return Poll::Ready(Some(Ok(Frame::data(out))));
+            }
+            if *this.ended {
+                return Poll::Ready(this.trailers.take().map(Ok));
+            }
+
+            match std::task::ready!(this.inner.as_mut().poll_frame(cx)) {
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => this.decoder.push(data)?,
+                    Err(frame) => {
+                        *this.trailers = Some(frame);
+                        this.decoder.end();
+                        *this.ended = true;
+                    }
+                },
+                None => {
+                    this.decoder.end();
+                    *this.ended = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        Json, Router,
+        middleware::{DEFAULT_BODY_LIMIT, DefaultBodyLimit},
+        test::TestClient,
+    };
+
+    fn client() -> TestClient {
+        TestClient::new(
+            Router::new()
+                .fallback(|Json(value): Json<Value>| async move { Json(value) })
+                .layer(RequestDecompressionLayer::new()),
+        )
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[monoio::test]
+    async fn decodes_request_bodies() {
+        client()
+            .post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(gzip(br#"{"a":1}"#))
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_json(&json!({ "a": 1 }));
+    }
+
+    #[monoio::test]
+    async fn rejects_unsupported_encodings() {
+        let res = client()
+            .post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_ENCODING, "compress")
+            .body("{}")
+            .send()
+            .await;
+        res.assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(res.header(header::ACCEPT_ENCODING).is_some());
+    }
+
+    #[monoio::test]
+    async fn caps_the_decompressed_size() {
+        // Far below the limit on the wire, far above it once inflated.
+        let bomb = gzip(&vec![b' '; DEFAULT_BODY_LIMIT + 1]);
+        assert!(bomb.len() < 16 * 1024);
+
+        client()
+            .post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(bomb)
+            .send()
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[monoio::test]
+    async fn uses_the_default_body_limit() {
+        let client = TestClient::new(
+            Router::new()
+                .fallback(|Json(value): Json<Value>| async move { Json(value) })
+                .layer(RequestDecompressionLayer::new())
+                .layer(DefaultBodyLimit::max(1024)),
+        );
+        // A JSON string of `len` bytes.
+        let send = |len: usize| {
+            client
+                .post("/")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_ENCODING, "gzip")
+                .body(gzip(format!("\"{}\"", "a".repeat(len - 2)).as_bytes()))
+                .send()
+        };
+
+        send(1024).await.assert_status(StatusCode::OK);
+        send(1025)
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{extract::FromRequestParts, prelude::*};
+
+/// Create a middleware that runs the extractor `E` as a guard.
+///
+/// If the extractor fails, its rejection is returned and the inner service is not
+/// called. The extracted value itself is discarded, so this is mostly useful for
+/// checks such as authorization:
+///
+/// ```no_run
+/// # use http::{StatusCode, header, request::Parts};
+/// # use monet::{Router, get, extract::FromRequestParts, middleware};
+/// # async fn stats() {}
+/// struct RequireAuth;
+///
+/// impl<S> FromRequestParts<S> for RequireAuth {
+///     type Rejection = StatusCode;
+///
+///     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+///         match parts.headers.get(header::AUTHORIZATION) {
+///             Some(token) if token == "Bearer admin" => Ok(Self),
+///             _ => Err(StatusCode::UNAUTHORIZED),
+///         }
+///     }
+/// }
+///
+/// let admin: Router = Router::new()
+///     .route("/stats", get(stats))
+///     .route_layer(middleware::from_extractor::<RequireAuth>());
+/// ```
+pub fn from_extractor<E>() -> FromExtractorLayer<E, ()> {
+    from_extractor_with_state(())
+}
+
+/// Like [`from_extractor`], but the extractor has access to `state`.
+pub fn from_extractor_with_state<E, S>(state: S) -> FromExtractorLayer<E, S> {
+    FromExtractorLayer {
+        state,
+        _extractor: PhantomData,
+    }
+}
+
+/// A [`TowerLayer`] that runs an extractor as a guard, see [`from_extractor`].
+pub struct FromExtractorLayer<E, S> {
+    state: S,
+    _extractor: PhantomData<fn() -> E>,
+}
+
+impl<E, S> Clone for FromExtractorLayer<E, S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            _extractor: PhantomData,
+        }
+    }
+}
+
+impl<E, S> fmt::Debug for FromExtractorLayer<E, S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromExtractorLayer")
+            .field("extractor", &std::any::type_name::<E>())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<I, E, S> TowerLayer<I> for FromExtractorLayer<E, S>
+where
+    S: Clone,
+{
+    type Service = FromExtractor<I, E, S>;
+
+    fn layer(&self, inner: I) -> Self::Service {
+        FromExtractor {
+            inner,
+            state: self.state.clone(),
+            _extractor: PhantomData,
+        }
+    }
+}
+
+/// A middleware that runs an extractor as a guard, see [`from_extractor`].
+pub struct FromExtractor<I, E, S> {
+    inner: I,
+    state: S,
+    _extractor: PhantomData<fn() -> E>,
+}
+
+impl<I, E, S> Clone for FromExtractor<I, E, S>
+where
+    I: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+            _extractor: PhantomData,
+        }
+    }
+}
+
+impl<I, E, S> fmt::Debug for FromExtractor<I, E, S>
+where
+    I: fmt::Debug,
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromExtractor")
+            .field("inner", &self.inner)
+            .field("extractor", &std::any::type_name::<E>())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<I, E, S> TowerService<HttpRequest> for FromExtractor<I, E, S>
+where
+    E: FromRequestParts<S> + 'static,
+    I: TowerService<HttpRequest, Error = Infallible> + Clone + 'static,
+    I::Response: IntoResponse + 'static,
+    I::Future: 'static,
+    S: Clone + 'static,
+{
+    type Response = HttpResponse;
+    type Error = Infallible;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: HttpRequest) -> Self::Future {
+        let state = self.state.clone();
+        let mut inner = self.inner.clone();
+
+        let future = Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+
+            if let Err(rejection) = E::from_request_parts(&mut parts, &state).await {
+                return Ok(rejection.into_response());
+            }
+
+            let req = HttpRequest::from_parts(parts, body);
+            match inner.call(req).await {
+                Ok(res) => Ok(res.into_response()),
+                Err(err) => match err {},
+            }
+        });
+
+        ResponseFuture::new(future)
+    }
+}
+
+opaque_future! {
+    /// Response future for [`FromExtractor`].
+    pub type ResponseFuture =
+        Pin<Box<dyn Future<Output = Result<HttpResponse, Infallible>>>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{StatusCode, header};
+
+    use super::*;
+    use crate::{Router, extract::auth::BearerToken, get, test::TestClient};
+
+    #[monoio::test]
+    async fn guards_the_inner_service() {
+        let app = Router::new()
+            .route("/", get(|| async { "hello" }))
+            .route_layer(from_extractor::<BearerToken>());
+        let client = TestClient::new(app);
+
+        client
+            .get("/")
+            .header(header::AUTHORIZATION, "Bearer abc")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("hello");
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+}
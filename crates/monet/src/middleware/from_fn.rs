@@ -0,0 +1,312 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    extract::{FromRequest, FromRequestParts},
+    prelude::*,
+};
+
+/// Create a middleware from an async function.
+///
+/// The function takes any number of [`FromRequestParts`] extractors, then one
+/// [`FromRequest`] extractor, usually the [`HttpRequest`] itself, and finally the
+/// [`Next`] service. It returns anything that implements [`IntoResponse`].
+///
+/// ```no_run
+/// use http::{StatusCode, header};
+/// use monet::{
+///     HttpRequest, HttpResponse, IntoResponse, Router, get,
+///     middleware::{self, Next, RequestId, RequestIdLayer},
+/// };
+///
+/// async fn auth(request_id: RequestId, req: HttpRequest, next: Next) -> HttpResponse {
+///     if req.headers().contains_key(header::AUTHORIZATION) {
+///         next.run(req).await
+///     } else {
+///         tracing::debug!(%request_id, "missing credentials");
+///         StatusCode::UNAUTHORIZED.into_response()
+///     }
+/// }
+///
+/// # async fn root() {}
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(middleware::from_fn(auth))
+///     .layer(RequestIdLayer::new());
+/// ```
+///
+/// Rejections of the extractors are returned as the response, the inner service is
+/// not called then.
+pub fn from_fn<F, T>(f: F) -> FromFnLayer<F, (), T> {
+    from_fn_with_state((), f)
+}
+
+/// Like [`from_fn`], but the extractors have access to `state`, e.g. through
+/// [`State`](crate::State).
+pub fn from_fn_with_state<F, S, T>(state: S, f: F) -> FromFnLayer<F, S, T> {
+    FromFnLayer {
+        f,
+        state,
+        _extractor: PhantomData,
+    }
+}
+
+/// A [`TowerLayer`] from an async function, see [`from_fn`].
+pub struct FromFnLayer<F, S, T> {
+    f: F,
+    state: S,
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<F, S, T> Clone for FromFnLayer<F, S, T>
+where
+    F: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            state: self.state.clone(),
+            _extractor: self._extractor,
+        }
+    }
+}
+
+impl<F, S, T> fmt::Debug for FromFnLayer<F, S, T>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromFnLayer")
+            .field("f", &std::any::type_name::<F>())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<I, F, S, T> TowerLayer<I> for FromFnLayer<F, S, T>
+where
+    F: Clone,
+    S: Clone,
+{
+    type Service = FromFn<F, S, I, T>;
+
+    fn layer(&self, inner: I) -> Self::Service {
+        FromFn {
+            f: self.f.clone(),
+            state: self.state.clone(),
+            inner,
+            _extractor: PhantomData,
+        }
+    }
+}
+
+/// A middleware created from an async function, see [`from_fn`].
+pub struct FromFn<F, S, I, T> {
+    f: F,
+    inner: I,
+    state: S,
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<F, S, I, T> Clone for FromFn<F, S, I, T>
+where
+    F: Clone,
+    I: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+            _extractor: self._extractor,
+        }
+    }
+}
+
+impl<F, S, I, T> fmt::Debug for FromFn<F, S, I, T>
+where
+    S: fmt::Debug,
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromFn")
+            .field("f", &std::any::type_name::<F>())
+            .field("inner", &self.inner)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+macro_rules! impl_service {
+    (
+        [$($ty:ident),*], $last:ident
+    ) => {
+        #[allow(non_snake_case, unused_mut)]
+        impl<F, Fut, Out, S, I, M, $($ty,)* $last> TowerService<HttpRequest> for FromFn<F, S, I, (M, $($ty,)* $last,)>
+        where
+            F: FnMut($($ty,)* $last, Next) -> Fut + Clone + 'static,
+            $( $ty: FromRequestParts<S>, )*
+            $last: FromRequest<S, M>,
+            Fut: Future<Output = Out> + 'static,
+            Out: IntoResponse + 'static,
+            I: TowerService<HttpRequest, Error = Infallible> + Clone + 'static,
+            I::Response: IntoResponse + 'static,
+            I::Future: 'static,
+            S: Clone + 'static,
+        {
+            type Response = HttpResponse;
+            type Error = Infallible;
+            type Future = ResponseFuture;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: HttpRequest) -> Self::Future {
+                let mut f = self.f.clone();
+                let state = self.state.clone();
+                let next = Next {
+                    inner: Route::new(self.inner.clone()),
+                };
+
+                let future = Box::pin(async move {
+                    let (mut parts, body) = req.into_parts();
+
+                    $(
+                        let $ty = match $ty::from_request_parts(&mut parts, &state).await {
+                            Ok(value) => value,
+                            Err(rejection) => return Ok(rejection.into_response()),
+                        };
+                    )*
+
+                    let req = HttpRequest::from_parts(parts, body);
+
+                    let $last = match $last::from_request(req, &state).await {
+                        Ok(value) => value,
+                        Err(rejection) => return Ok(rejection.into_response()),
+                    };
+
+                    Ok(f($($ty,)* $last, next).await.into_response())
+                });
+
+                ResponseFuture::new(future)
+            }
+        }
+    };
+}
+
+impl_service!([], T1);
+impl_service!([T1], T2);
+impl_service!([T1, T2], T3);
+all_the_tuples!(impl_service);
+
+/// The remainder of a middleware stack, including the handler.
+#[derive(Debug, Clone)]
+pub struct Next {
+    inner: Route,
+}
+
+impl Next {
+    /// Execute the remaining middleware stack.
+    pub async fn run(self, req: HttpRequest) -> HttpResponse {
+        match self.inner.call(req).await {
+            Ok(res) => res,
+            Err(err) => match err {},
+        }
+    }
+}
+
+impl TowerService<HttpRequest> for Next {
+    type Response = HttpResponse;
+    type Error = Infallible;
+    type Future = crate::routing::route_tower_impl::RouteFuture<Infallible>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: HttpRequest) -> Self::Future {
+        self.inner.clone().call(req)
+    }
+}
+
+opaque_future! {
+    /// Response future for [`FromFn`].
+    pub type ResponseFuture =
+        Pin<Box<dyn Future<Output = Result<HttpResponse, Infallible>>>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderValue, Method, StatusCode};
+
+    use super::*;
+    use crate::{Router, State, get, test::TestClient};
+
+    async fn require_header(req: HttpRequest, next: Next) -> HttpResponse {
+        if !req.headers().contains_key("x-allowed") {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        let mut res = next.run(req).await;
+        res.headers_mut()
+            .insert("x-checked", HeaderValue::from_static("yes"));
+        res
+    }
+
+    #[monoio::test]
+    async fn wraps_the_inner_service() {
+        let app = Router::new()
+            .route("/", get(|| async { "hello" }))
+            .layer(from_fn(require_header));
+        let client = TestClient::new(app);
+
+        client
+            .get("/")
+            .header("x-allowed", "1")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_header("x-checked", "yes")
+            .assert_text("hello");
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+
+    #[monoio::test]
+    async fn runs_extractors_with_state() {
+        async fn only(
+            State(allowed): State<Method>,
+            method: Method,
+            req: HttpRequest,
+            next: Next,
+        ) -> HttpResponse {
+            if method == allowed {
+                next.run(req).await
+            } else {
+                StatusCode::METHOD_NOT_ALLOWED.into_response()
+            }
+        }
+
+        let app = Router::new()
+            .fallback(|| async { "hello" })
+            .layer(from_fn_with_state(Method::GET, only));
+        let client = TestClient::new(app);
+
+        client.get("/").send().await.assert_text("hello");
+        client
+            .post("/")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
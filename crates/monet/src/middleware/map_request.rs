@@ -0,0 +1,271 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    extract::{FromRequest, FromRequestParts},
+    prelude::*,
+};
+
+/// Create a middleware from an async function that transforms the request.
+///
+/// Like [`from_fn`](super::from_fn()), the function takes any number of
+/// [`FromRequestParts`] extractors followed by one [`FromRequest`] extractor,
+/// usually the [`HttpRequest`]. It returns the request to pass on, or a
+/// `Result<HttpRequest, E>` to respond with `E` instead.
+///
+/// ```no_run
+/// # use monet::{HttpRequest, Router, get, middleware};
+/// # async fn root() {}
+/// async fn strip_prefix(mut req: HttpRequest) -> HttpRequest {
+///     // ...
+///     req
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(middleware::map_request(strip_prefix));
+/// ```
+pub fn map_request<F, T>(f: F) -> MapRequestLayer<F, (), T> {
+    map_request_with_state((), f)
+}
+
+/// Like [`map_request`], but the extractors have access to `state`.
+pub fn map_request_with_state<F, S, T>(state: S, f: F) -> MapRequestLayer<F, S, T> {
+    MapRequestLayer {
+        f,
+        state,
+        _extractor: PhantomData,
+    }
+}
+
+/// A [`TowerLayer`] from an async function, see [`map_request`].
+pub struct MapRequestLayer<F, S, T> {
+    f: F,
+    state: S,
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<F, S, T> Clone for MapRequestLayer<F, S, T>
+where
+    F: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            state: self.state.clone(),
+            _extractor: self._extractor,
+        }
+    }
+}
+
+impl<F, S, T> fmt::Debug for MapRequestLayer<F, S, T>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapRequestLayer")
+            .field("f", &std::any::type_name::<F>())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<I, F, S, T> TowerLayer<I> for MapRequestLayer<F, S, T>
+where
+    F: Clone,
+    S: Clone,
+{
+    type Service = MapRequest<F, S, I, T>;
+
+    fn layer(&self, inner: I) -> Self::Service {
+        MapRequest {
+            f: self.f.clone(),
+            state: self.state.clone(),
+            inner,
+            _extractor: PhantomData,
+        }
+    }
+}
+
+/// A middleware created from an async function, see [`map_request`].
+pub struct MapRequest<F, S, I, T> {
+    f: F,
+    inner: I,
+    state: S,
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<F, S, I, T> Clone for MapRequest<F, S, I, T>
+where
+    F: Clone,
+    I: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+            _extractor: self._extractor,
+        }
+    }
+}
+
+impl<F, S, I, T> fmt::Debug for MapRequest<F, S, I, T>
+where
+    S: fmt::Debug,
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapRequest")
+            .field("f", &std::any::type_name::<F>())
+            .field("inner", &self.inner)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+macro_rules! impl_service {
+    (
+        [$($ty:ident),*], $last:ident
+    ) => {
+        #[allow(non_snake_case, unused_mut)]
+        impl<F, Fut, S, I, M, $($ty,)* $last> TowerService<HttpRequest> for MapRequest<F, S, I, (M, $($ty,)* $last,)>
+        where
+            F: FnMut($($ty,)* $last) -> Fut + Clone + 'static,
+            $( $ty: FromRequestParts<S>, )*
+            $last: FromRequest<S, M>,
+            Fut: Future + 'static,
+            Fut::Output: IntoMapRequestResult + 'static,
+            I: TowerService<HttpRequest, Error = Infallible> + Clone + 'static,
+            I::Response: IntoResponse + 'static,
+            I::Future: 'static,
+            S: Clone + 'static,
+        {
+            type Response = HttpResponse;
+            type Error = Infallible;
+            type Future = ResponseFuture;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: HttpRequest) -> Self::Future {
+                let mut f = self.f.clone();
+                let state = self.state.clone();
+                let mut inner = self.inner.clone();
+
+                let future = Box::pin(async move {
+                    let (mut parts, body) = req.into_parts();
+
+                    $(
+                        let $ty = match $ty::from_request_parts(&mut parts, &state).await {
+                            Ok(value) => value,
+                            Err(rejection) => return Ok(rejection.into_response()),
+                        };
+                    )*
+
+                    let req = HttpRequest::from_parts(parts, body);
+
+                    let $last = match $last::from_request(req, &state).await {
+                        Ok(value) => value,
+                        Err(rejection) => return Ok(rejection.into_response()),
+                    };
+
+                    match f($($ty,)* $last).await.into_map_request_result() {
+                        Ok(req) => match inner.call(req).await {
+                            Ok(res) => Ok(res.into_response()),
+                            Err(err) => match err {},
+                        },
+                        Err(res) => Ok(res),
+                    }
+                });
+
+                ResponseFuture::new(future)
+            }
+        }
+    };
+}
+
+impl_service!([], T1);
+impl_service!([T1], T2);
+impl_service!([T1, T2], T3);
+all_the_tuples!(impl_service);
+
+/// The result of a [`map_request`] function: either the request to pass on, or a
+/// response to return right away.
+pub trait IntoMapRequestResult {
+    // The response is returned right away, boxing it would only add an allocation.
+    #[allow(clippy::result_large_err)]
+    fn into_map_request_result(self) -> Result<HttpRequest, HttpResponse>;
+}
+
+impl IntoMapRequestResult for HttpRequest {
+    fn into_map_request_result(self) -> Result<HttpRequest, HttpResponse> {
+        Ok(self)
+    }
+}
+
+impl<E> IntoMapRequestResult for Result<HttpRequest, E>
+where
+    E: IntoResponse,
+{
+    fn into_map_request_result(self) -> Result<HttpRequest, HttpResponse> {
+        self.map_err(IntoResponse::into_response)
+    }
+}
+
+opaque_future! {
+    /// Response future for [`MapRequest`].
+    pub type ResponseFuture =
+        Pin<Box<dyn Future<Output = Result<HttpResponse, Infallible>>>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderValue, StatusCode};
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    async fn add_user(mut req: HttpRequest) -> Result<HttpRequest, StatusCode> {
+        if req.uri().path() == "/blocked" {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        req.headers_mut()
+            .insert("x-user", HeaderValue::from_static("ferris"));
+        Ok(req)
+    }
+
+    #[monoio::test]
+    async fn transforms_or_rejects_requests() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|req: HttpRequest| async move {
+                    req.headers()["x-user"].to_str().unwrap().to_owned()
+                }),
+            )
+            .route("/blocked", get(|| async { "unreachable" }))
+            .layer(map_request(add_user));
+        let client = TestClient::new(app);
+
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("ferris");
+        client
+            .get("/blocked")
+            .send()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{extract::FromRequestParts, prelude::*};
+
+/// Create a middleware from an async function that transforms the response.
+///
+/// The function takes any number of [`FromRequestParts`] extractors, which run on
+/// the request before it is passed on, followed by the [`HttpResponse`]. It returns
+/// anything that implements [`IntoResponse`].
+///
+/// Unlike tower's `MapResponseLayer`, the function can be async and use
+/// extractors:
+///
+/// ```no_run
+/// # use http::Method;
+/// # use monet::{HttpResponse, Router, get, middleware};
+/// # async fn root() {}
+/// async fn vary_on_method(method: Method, mut res: HttpResponse) -> HttpResponse {
+///     res.headers_mut().insert("x-method", method.as_str().parse().unwrap());
+///     res
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(middleware::map_response(vary_on_method));
+/// ```
+pub fn map_response<F, T>(f: F) -> MapResponseLayer<F, (), T> {
+    map_response_with_state((), f)
+}
+
+/// Like [`map_response`], but the extractors have access to `state`.
+pub fn map_response_with_state<F, S, T>(state: S, f: F) -> MapResponseLayer<F, S, T> {
+    MapResponseLayer {
+        f,
+        state,
+        _extractor: PhantomData,
+    }
+}
+
+/// A [`TowerLayer`] from an async function, see [`map_response`].
+pub struct MapResponseLayer<F, S, T> {
+    f: F,
+    state: S,
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<F, S, T> Clone for MapResponseLayer<F, S, T>
+where
+    F: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            state: self.state.clone(),
+            _extractor: self._extractor,
+        }
+    }
+}
+
+impl<F, S, T> fmt::Debug for MapResponseLayer<F, S, T>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapResponseLayer")
+            .field("f", &std::any::type_name::<F>())
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<I, F, S, T> TowerLayer<I> for MapResponseLayer<F, S, T>
+where
+    F: Clone,
+    S: Clone,
+{
+    type Service = MapResponse<F, S, I, T>;
+
+    fn layer(&self, inner: I) -> Self::Service {
+        MapResponse {
+            f: self.f.clone(),
+            state: self.state.clone(),
+            inner,
+            _extractor: PhantomData,
+        }
+    }
+}
+
+/// A middleware created from an async function, see [`map_response`].
+pub struct MapResponse<F, S, I, T> {
+    f: F,
+    inner: I,
+    state: S,
+    _extractor: PhantomData<fn() -> T>,
+}
+
+impl<F, S, I, T> Clone for MapResponse<F, S, I, T>
+where
+    F: Clone,
+    I: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+            _extractor: self._extractor,
+        }
+    }
+}
+
+impl<F, S, I, T> fmt::Debug for MapResponse<F, S, I, T>
+where
+    S: fmt::Debug,
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapResponse")
+            .field("f", &std::any::type_name::<F>())
+            .field("inner", &self.inner)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+macro_rules! impl_service {
+    (
+        [$($ty:ident),*], $last:ident
+    ) => {
+        impl_service!(@impl [$($ty,)* $last]);
+    };
+
+    (@impl [$($ty:ident),*]) => {
+        #[allow(non_snake_case, unused_mut, unused_variables)]
+        impl<F, Fut, S, I, $($ty,)*> TowerService<HttpRequest> for MapResponse<F, S, I, ($($ty,)*)>
+        where
+            F: FnMut($($ty,)* HttpResponse) -> Fut + Clone + 'static,
+            $( $ty: FromRequestParts<S>, )*
+            Fut: Future + 'static,
+            Fut::Output: IntoResponse + 'static,
+            I: TowerService<HttpRequest, Error = Infallible> + Clone + 'static,
+            I::Response: IntoResponse + 'static,
+            I::Future: 'static,
+            S: Clone + 'static,
+        {
+            type Response = HttpResponse;
+            type Error = Infallible;
+            type Future = ResponseFuture;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: HttpRequest) -> Self::Future {
+                let mut f = self.f.clone();
+                let state = self.state.clone();
+                let mut inner = self.inner.clone();
+
+                let future = Box::pin(async move {
+                    let (mut parts, body) = req.into_parts();
+
+                    $(
+                        let $ty = match $ty::from_request_parts(&mut parts, &state).await {
+                            Ok(value) => value,
+                            Err(rejection) => return Ok(rejection.into_response()),
+                        };
+                    )*
+
+                    let req = HttpRequest::from_parts(parts, body);
+
+                    let res = match inner.call(req).await {
+                        Ok(res) => res.into_response(),
+                        Err(err) => match err {},
+                    };
+
+                    Ok(f($($ty,)* res).await.into_response())
+                });
+
+                ResponseFuture::new(future)
+            }
+        }
+    };
+}
+
+impl_service!(@impl []);
+impl_service!([], T1);
+impl_service!([T1], T2);
+impl_service!([T1, T2], T3);
+all_the_tuples!(impl_service);
+
+opaque_future! {
+    /// Response future for [`MapResponse`].
+    pub type ResponseFuture =
+        Pin<Box<dyn Future<Output = Result<HttpResponse, Infallible>>>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Method, StatusCode};
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    async fn tag_method(method: Method, mut res: HttpResponse) -> HttpResponse {
+        res.headers_mut()
+            .insert("x-method", method.as_str().parse().unwrap());
+        res
+    }
+
+    #[monoio::test]
+    async fn transforms_responses() {
+        let app = Router::new()
+            .route("/", get(|| async { "hello" }))
+            .layer(map_response(tag_method));
+        let client = TestClient::new(app);
+
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_header("x-method", "GET")
+            .assert_text("hello");
+        // Responses that didn't come from a handler are mapped too.
+        client
+            .head("/nowhere")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND)
+            .assert_header("x-method", "HEAD");
+    }
+}
@@ -0,0 +1,783 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    fmt::Write,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll, ready},
+    time::Instant,
+};
+
+use futures::{
+    StreamExt,
+    channel::{mpsc, oneshot},
+    future::join_all,
+};
+use http::{HeaderValue, Method, header::CONTENT_TYPE};
+use pin_project_lite::pin_project;
+
+use crate::{extract::matched_path::MatchedPath, prelude::*, util};
+
+/// The latency buckets used by default, in seconds.
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The `path` label of requests that didn't match a route.
+const UNMATCHED: &str = "unmatched";
+
+type SnapshotRequest = oneshot::Sender<CoreSnapshot>;
+
+/// Metrics shared by all cores, rendered in the Prometheus text format.
+///
+/// Create one registry before starting the cores and hand a clone to each of them.
+/// Every core records into counters of its own, without any locking or atomics,
+/// through a [`MetricsLayer`] and, for connections, [`Serve::metrics`]. Rendering
+/// asks each core for a snapshot of its counters over a channel and adds them up,
+/// so requests are reported for the whole process, and connections per core.
+///
+/// The registry is a service responding with the rendered metrics, to be mounted
+/// wherever they should be scraped:
+///
+/// ```no_run
+/// # use std::thread;
+/// # use monet::{Router, get, serve, routing::method_router::MethodRouter};
+/// # use monet::middleware::{MetricsLayer, MetricsRegistry};
+/// # async fn root() {}
+/// # fn listener() -> monoio::net::TcpListener { todo!() }
+/// # let cores = 2;
+/// let metrics = MetricsRegistry::new();
+///
+/// for _ in 0..cores {
+///     let metrics = metrics.clone();
+///     thread::spawn(move || {
+///         let mut runtime = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
+///             .enable_timer()
+///             .build()
+///             .unwrap();
+///         runtime.block_on(async move {
+///             let app: Router = Router::new()
+///                 .route("/", get(root))
+///                 .route("/metrics", MethodRouter::new().get_service(metrics.clone()))
+///                 .layer(MetricsLayer::new(metrics.clone()));
+///
+///             serve(listener(), app).metrics(metrics).await;
+///         })
+///     });
+/// }
+/// ```
+///
+/// The metrics are:
+///
+/// - `http_requests_total`, a counter by `method`, `path` and `status`,
+/// - `http_request_duration_seconds`, a histogram of the latency until the
+///   response head, by `method`, `path` and `status`,
+/// - `http_requests_in_flight`, a gauge by `method` and `path`,
+/// - `http_connections_open`, a gauge by `core`,
+/// - `http_connections_total`, a counter by `core`.
+///
+/// The `path` is the [matched path](MatchedPath), or `unmatched` for requests that
+/// didn't match any route, and methods other than the standard ones are counted
+/// as `OTHER`, so the number of series stays bounded.
+///
+/// [`Serve::metrics`]: crate::serve::Serve::metrics
+#[derive(Clone)]
+pub struct MetricsRegistry {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    id: usize,
+    buckets: Arc<[f64]>,
+    cores: Mutex<Vec<mpsc::UnboundedSender<SnapshotRequest>>>,
+}
+
+thread_local! {
+    /// The counters of this core, by registry id.
+    static CORES: RefCell<HashMap<usize, Rc<CoreMetrics>>> = RefCell::default();
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_BUCKETS)
+    }
+
+    /// Use `buckets` as the upper bounds, in seconds, of the latency histogram.
+    pub fn with_buckets(buckets: &[f64]) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let mut buckets = buckets.to_vec();
+        buckets.retain(|le| le.is_finite());
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+
+        Self {
+            shared: Arc::new(Shared {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                buckets: buckets.into(),
+                cores: Mutex::default(),
+            }),
+        }
+    }
+
+    /// The counters of the current core, created and registered on first use.
+    pub(crate) fn core(&self) -> Rc<CoreMetrics> {
+        let id = self.shared.id;
+        if let Some(core) = CORES.with(|cores| cores.borrow().get(&id).cloned()) {
+            return core;
+        }
+
+        let core = Rc::new(CoreMetrics::new(self.shared.buckets.clone()));
+        let (tx, rx) = mpsc::unbounded();
+        self.shared
+            .cores
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(tx);
+        CORES.with(|cores| cores.borrow_mut().insert(id, core.clone()));
+        monoio::spawn(answer_snapshots(id, core.clone(), rx));
+        core
+    }
+
+    /// Collect the counters of all cores and render them in the Prometheus text
+    /// exposition format.
+    pub async fn render(&self) -> String {
+        let senders = self
+            .shared
+            .cores
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        let replies = senders.iter().filter_map(|tx| {
+            let (reply, rx) = oneshot::channel();
+            tx.unbounded_send(reply).ok().map(|()| rx)
+        });
+        let snapshots: Vec<_> = join_all(replies)
+            .await
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect();
+
+        // Forget the cores that have shut down.
+        self.shared
+            .cores
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .retain(|tx| !tx.is_closed());
+
+        render(&self.shared.buckets, snapshots)
+    }
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MetricsRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsRegistry")
+            .field("buckets", &self.shared.buckets)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Responds with the rendered metrics.
+impl<B> TowerService<HttpRequest<B>> for MetricsRegistry {
+    type Response = HttpResponse;
+    type Error = Infallible;
+    type Future = RenderFuture;
+
+    #[inline]
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: HttpRequest<B>) -> Self::Future {
+        let registry = self.clone();
+        RenderFuture::new(Box::pin(async move {
+            let mut res = registry.render().await.into_response();
+            res.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+            );
+            Ok(res)
+        }))
+    }
+}
+
+opaque_future! {
+    /// Response future for [`MetricsRegistry`].
+    pub type RenderFuture =
+        Pin<Box<dyn Future<Output = Result<HttpResponse, Infallible>>>>;
+}
+
+/// Answer the snapshot requests of [`MetricsRegistry::render`] for one core,
+/// until the registry is gone.
+async fn answer_snapshots(
+    id: usize,
+    core: Rc<CoreMetrics>,
+    mut requests: mpsc::UnboundedReceiver<SnapshotRequest>,
+) {
+    while let Some(reply) = requests.next().await {
+        let _ = reply.send(core.snapshot());
+    }
+    CORES.with(|cores| cores.borrow_mut().remove(&id));
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RouteKey {
+    method: &'static str,
+    path: Arc<str>,
+}
+
+/// The `method` label of a request: the method itself if it is one of those of
+/// RFC 9110 or `PATCH`, `OTHER` otherwise, as clients can send any token.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::CONNECT => "CONNECT",
+        Method::OPTIONS => "OPTIONS",
+        Method::TRACE => "TRACE",
+        Method::PATCH => "PATCH",
+        _ => "OTHER",
+    }
+}
+
+#[derive(Clone)]
+struct Histogram {
+    /// The number of observations per bucket, not cumulative, with one more for
+    /// those above the last bound.
+    counts: Box<[u64]>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(buckets: usize) -> Self {
+        Self {
+            counts: vec![0; buckets + 1].into(),
+            sum: 0.0,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.sum += other.sum;
+    }
+
+    fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// The counters of one core.
+pub(crate) struct CoreMetrics {
+    core: u32,
+    buckets: Arc<[f64]>,
+    requests: RefCell<HashMap<(RouteKey, u16), Histogram>>,
+    in_flight: RefCell<HashMap<RouteKey, u64>>,
+    connections_open: Cell<u64>,
+    connections_total: Cell<u64>,
+}
+
+impl CoreMetrics {
+    fn new(buckets: Arc<[f64]>) -> Self {
+        Self {
+            core: util::core_id(),
+            buckets,
+            requests: RefCell::default(),
+            in_flight: RefCell::default(),
+            connections_open: Cell::new(0),
+            connections_total: Cell::new(0),
+        }
+    }
+
+    /// Count a new connection, open until the guard is dropped.
+    pub(crate) fn connection_opened(self: &Rc<Self>) -> OpenConnection {
+        self.connections_open.set(self.connections_open.get() + 1);
+        self.connections_total.set(self.connections_total.get() + 1);
+        OpenConnection { core: self.clone() }
+    }
+
+    fn request_started(self: &Rc<Self>, route: RouteKey) -> InFlight {
+        *self
+            .in_flight
+            .borrow_mut()
+            .entry(route.clone())
+            .or_default() += 1;
+        InFlight {
+            core: self.clone(),
+            route,
+            start: Instant::now(),
+        }
+    }
+
+    fn snapshot(&self) -> CoreSnapshot {
+        CoreSnapshot {
+            core: self.core,
+            requests: self
+                .requests
+                .borrow()
+                .iter()
+                .map(|(key, histogram)| (key.clone(), histogram.clone()))
+                .collect(),
+            in_flight: self
+                .in_flight
+                .borrow()
+                .iter()
+                .map(|(route, count)| (route.clone(), *count))
+                .collect(),
+            connections_open: self.connections_open.get(),
+            connections_total: self.connections_total.get(),
+        }
+    }
+}
+
+/// A connection counted by [`CoreMetrics::connection_opened`].
+pub(crate) struct OpenConnection {
+    core: Rc<CoreMetrics>,
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        let open = &self.core.connections_open;
+        open.set(open.get() - 1);
+    }
+}
+
+/// A request in flight, recorded as finished with a status, or only removed from
+/// the in-flight gauge if dropped before the response.
+struct InFlight {
+    core: Rc<CoreMetrics>,
+    route: RouteKey,
+    start: Instant,
+}
+
+impl InFlight {
+    fn finish(self, status: u16) {
+        let seconds = self.start.elapsed().as_secs_f64();
+        let core = &self.core;
+        let bucket = core.buckets.partition_point(|le| *le < seconds);
+        let mut requests = core.requests.borrow_mut();
+        let histogram = requests
+            .entry((self.route.clone(), status))
+            .or_insert_with(|| Histogram::new(core.buckets.len()));
+        histogram.counts[bucket] += 1;
+        histogram.sum += seconds;
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut in_flight = self.core.in_flight.borrow_mut();
+        if let Some(count) = in_flight.get_mut(&self.route) {
+            *count -= 1;
+        }
+    }
+}
+
+/// The counters of one core, sent to the core rendering the metrics.
+struct CoreSnapshot {
+    core: u32,
+    requests: Vec<((RouteKey, u16), Histogram)>,
+    in_flight: Vec<(RouteKey, u64)>,
+    connections_open: u64,
+    connections_total: u64,
+}
+
+fn render(buckets: &[f64], mut snapshots: Vec<CoreSnapshot>) -> String {
+    let mut requests = BTreeMap::<_, Histogram>::new();
+    let mut in_flight = BTreeMap::<_, u64>::new();
+    for snapshot in &snapshots {
+        for ((route, status), histogram) in &snapshot.requests {
+            requests
+                .entry((route.path.clone(), route.method, *status))
+                .or_insert_with(|| Histogram::new(buckets.len()))
+                .add(histogram);
+        }
+        for (route, count) in &snapshot.in_flight {
+            *in_flight
+                .entry((route.path.clone(), route.method))
+                .or_default() += count;
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.core);
+
+    let mut out = String::new();
+
+    out.push_str("# HELP http_requests_total Total number of HTTP requests.\n");
+    out.push_str("# TYPE http_requests_total counter\n");
+    for ((path, method, status), histogram) in &requests {
+        let _ = writeln!(
+            out,
+            "http_requests_total{{method=\"{method}\",path=\"{}\",status=\"{status}\"}} {}",
+            escape(path),
+            histogram.count()
+        );
+    }
+
+    out.push_str(
+        "# HELP http_request_duration_seconds HTTP request latency until the response head.\n",
+    );
+    out.push_str("# TYPE http_request_duration_seconds histogram\n");
+    for ((path, method, status), histogram) in &requests {
+        let labels = format!(
+            "method=\"{method}\",path=\"{}\",status=\"{status}\"",
+            escape(path)
+        );
+        let mut cumulative = 0;
+        for (le, count) in buckets.iter().zip(&histogram.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{labels},le=\"{le}\"}} {cumulative}"
+            );
+        }
+        let count = histogram.count();
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {count}"
+        );
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_sum{{{labels}}} {}",
+            histogram.sum
+        );
+        let _ = writeln!(
+            out,
+            "http_request_duration_seconds_count{{{labels}}} {count}"
+        );
+    }
+
+    out.push_str("# HELP http_requests_in_flight Number of HTTP requests being served.\n");
+    out.push_str("# TYPE http_requests_in_flight gauge\n");
+    for ((path, method), count) in &in_flight {
+        let _ = writeln!(
+            out,
+            "http_requests_in_flight{{method=\"{method}\",path=\"{}\"}} {count}",
+            escape(path)
+        );
+    }
+
+    out.push_str("# HELP http_connections_open Number of open connections per core.\n");
+    out.push_str("# TYPE http_connections_open gauge\n");
+    for snapshot in &snapshots {
+        let _ = writeln!(
+            out,
+            "http_connections_open{{core=\"{}\"}} {}",
+            snapshot.core, snapshot.connections_open
+        );
+    }
+
+    out.push_str("# HELP http_connections_total Total number of accepted connections per core.\n");
+    out.push_str("# TYPE http_connections_total counter\n");
+    for snapshot in &snapshots {
+        let _ = writeln!(
+            out,
+            "http_connections_total{{core=\"{}\"}} {}",
+            snapshot.core, snapshot.connections_total
+        );
+    }
+
+    out
+}
+
+/// Escape a label value for the text format.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Record request metrics into a [`MetricsRegistry`].
+///
+/// Apply it with [`Router::layer`](crate::Router::layer) so the matched path is
+/// known, see the registry for the metrics recorded.
+#[derive(Clone, Debug)]
+pub struct MetricsLayer {
+    registry: MetricsRegistry,
+}
+
+impl MetricsLayer {
+    pub fn new(registry: MetricsRegistry) -> Self {
+        Self { registry }
+    }
+}
+
+impl<S> TowerLayer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics {
+            inner,
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+/// Records request metrics, see [`MetricsLayer`].
+#[derive(Clone, Debug)]
+pub struct Metrics<S> {
+    inner: S,
+    registry: MetricsRegistry,
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for Metrics<S>
+where
+    S: TowerService<HttpRequest<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: HttpRequest<B>) -> Self::Future {
+        let path = match req.extensions().get::<MatchedPath>() {
+            Some(path) => path.0.clone(),
+            None => UNMATCHED.into(),
+        };
+        let route = RouteKey {
+            method: method_label(req.method()),
+            path,
+        };
+        let in_flight = self.registry.core().request_started(route);
+
+        ResponseFuture {
+            future: self.inner.call(req),
+            in_flight: Some(in_flight),
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`Metrics`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        future: F,
+        in_flight: Option<InFlight>,
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.future.poll(cx)?).into_response();
+        if let Some(in_flight) = this.in_flight.take() {
+            in_flight.finish(res.status().as_u16());
+        }
+        Poll::Ready(Ok(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    #[monoio::test(timer_enabled = true)]
+    async fn counts_requests_per_route() {
+        let registry = MetricsRegistry::new();
+        let app = Router::new()
+            .route("/users/{id}", get(|| async { "user" }))
+            .layer(MetricsLayer::new(registry.clone()));
+        let client = TestClient::new(app);
+
+        for id in 1..=2 {
+            client
+                .get(&format!("/users/{id}"))
+                .send()
+                .await
+                .assert_status(StatusCode::OK);
+        }
+
+        let rendered = registry.render().await;
+        assert!(
+            rendered.contains(
+                "http_requests_total{method=\"GET\",path=\"/users/{id}\",status=\"200\"} 2"
+            ),
+            "{rendered}"
+        );
+        assert!(
+            rendered.contains("# TYPE http_request_duration_seconds histogram"),
+            "{rendered}"
+        );
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn counts_other_methods_together() {
+        let registry = MetricsRegistry::new();
+        let app = Router::new()
+            .fallback(|| async {})
+            .layer(MetricsLayer::new(registry.clone()));
+        let client = TestClient::new(app);
+
+        for method in ["PURGE", "X-ANYTHING", "BREW"] {
+            client
+                .request(Method::from_bytes(method.as_bytes()).unwrap(), "/")
+                .send()
+                .await
+                .assert_status(StatusCode::OK);
+        }
+
+        let rendered = registry.render().await;
+        assert!(
+            rendered.contains(
+                "http_requests_total{method=\"OTHER\",path=\"unmatched\",status=\"200\"} 3"
+            ),
+            "{rendered}"
+        );
+        assert!(!rendered.contains("PURGE"), "{rendered}");
+    }
+
+    fn histogram(counts: &[u64], sum: f64) -> Histogram {
+        Histogram {
+            counts: counts.into(),
+            sum,
+        }
+    }
+
+    #[test]
+    fn renders_the_text_format() {
+        let users = RouteKey {
+            method: "GET",
+            path: "/users/{id}".into(),
+        };
+        let quoted = RouteKey {
+            method: "POST",
+            path: "/a\"b".into(),
+        };
+        let snapshots = vec![
+            CoreSnapshot {
+                core: 1,
+                requests: vec![
+                    ((users.clone(), 200), histogram(&[1, 1, 0], 0.5)),
+                    ((quoted.clone(), 201), histogram(&[1, 0, 0], 0.25)),
+                ],
+                in_flight: vec![(users.clone(), 1)],
+                connections_open: 1,
+                connections_total: 4,
+            },
+            CoreSnapshot {
+                core: 0,
+                requests: vec![((users.clone(), 200), histogram(&[0, 1, 2], 5.0))],
+                in_flight: vec![(users, 2), (quoted, 0)],
+                connections_open: 2,
+                connections_total: 3,
+            },
+        ];
+
+        assert_eq!(
+            render(&[0.1, 1.0], snapshots),
+            "\
+# HELP http_requests_total Total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method=\"POST\",path=\"/a\\\"b\",status=\"201\"} 1
+http_requests_total{method=\"GET\",path=\"/users/{id}\",status=\"200\"} 5
+# HELP http_request_duration_seconds HTTP request latency until the response head.
+# TYPE http_request_duration_seconds histogram
+http_request_duration_seconds_bucket{method=\"POST\",path=\"/a\\\"b\",status=\"201\",le=\"0.1\"} 1
+http_request_duration_seconds_bucket{method=\"POST\",path=\"/a\\\"b\",status=\"201\",le=\"1\"} 1
+http_request_duration_seconds_bucket{method=\"POST\",path=\"/a\\\"b\",status=\"201\",le=\"+Inf\"} 1
+http_request_duration_seconds_sum{method=\"POST\",path=\"/a\\\"b\",status=\"201\"} 0.25
+http_request_duration_seconds_count{method=\"POST\",path=\"/a\\\"b\",status=\"201\"} 1
+http_request_duration_seconds_bucket{method=\"GET\",path=\"/users/{id}\",status=\"200\",le=\"0.1\"} 1
+http_request_duration_seconds_bucket{method=\"GET\",path=\"/users/{id}\",status=\"200\",le=\"1\"} 3
+http_request_duration_seconds_bucket{method=\"GET\",path=\"/users/{id}\",status=\"200\",le=\"+Inf\"} 5
+http_request_duration_seconds_sum{method=\"GET\",path=\"/users/{id}\",status=\"200\"} 5.5
+http_request_duration_seconds_count{method=\"GET\",path=\"/users/{id}\",status=\"200\"} 5
+# HELP http_requests_in_flight Number of HTTP requests being served.
+# TYPE http_requests_in_flight gauge
+http_requests_in_flight{method=\"POST\",path=\"/a\\\"b\"} 0
+http_requests_in_flight{method=\"GET\",path=\"/users/{id}\"} 3
+# HELP http_connections_open Number of open connections per core.
+# TYPE http_connections_open gauge
+http_connections_open{core=\"0\"} 2
+http_connections_open{core=\"1\"} 1
+# HELP http_connections_total Total number of accepted connections per core.
+# TYPE http_connections_total counter
+http_connections_total{core=\"0\"} 3
+http_connections_total{core=\"1\"} 4
+"
+        );
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn adds_up_the_cores() {
+        fn app(registry: &MetricsRegistry) -> Router {
+            Router::new()
+                .route("/", get(|| async {}))
+                .layer(MetricsLayer::new(registry.clone()))
+        }
+
+        let registry = MetricsRegistry::new();
+        TestClient::new(app(&registry)).get("/").send().await;
+
+        // Another core records a request, then keeps running so it can answer
+        // the snapshot request until told to stop.
+        let (ready_tx, ready) = oneshot::channel();
+        let (stop, stop_rx) = oneshot::channel::<()>();
+        let other = std::thread::spawn({
+            let registry = registry.clone();
+            move || {
+                let mut runtime = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
+                    .build()
+                    .unwrap();
+                runtime.block_on(async move {
+                    let client = TestClient::new(app(&registry));
+                    client.get("/").send().await;
+                    client.get("/").send().await;
+                    ready_tx.send(()).unwrap();
+                    let _ = stop_rx.await;
+                });
+            }
+        });
+        ready.await.unwrap();
+
+        let rendered = registry.render().await;
+        stop.send(()).unwrap();
+        other.join().unwrap();
+
+        assert!(
+            rendered.contains("http_requests_total{method=\"GET\",path=\"/\",status=\"200\"} 3"),
+            "{rendered}"
+        );
+        assert_eq!(
+            rendered.matches("http_connections_total{core=").count(),
+            2,
+            "{rendered}"
+        );
+    }
+}
@@ -0,0 +1,601 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll, ready},
+    time::{Duration, Instant},
+};
+
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header::RETRY_AFTER, request::Parts};
+use pin_project_lite::pin_project;
+
+use crate::{
+    Problem,
+    extract::connect_info::{ConnectInfo, MissingConnectInfo},
+    prelude::*,
+};
+
+pub const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+type KeyFn = Rc<dyn Fn(&Parts) -> Option<String>>;
+
+/// Limit the rate of requests per client with token buckets.
+///
+/// Every key, by default the client's IP address, gets a bucket holding up to
+/// `burst` tokens, refilled at `requests` per `period`. Each request takes a
+/// token; requests finding the bucket empty are answered with `429 Too Many
+/// Requests` and a `Retry-After` header without reaching the inner service. All
+/// responses carry `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`
+/// headers. Requests without a key, e.g. without the header they are keyed by,
+/// are not limited, except with [`KeyBy::ip`]: a missing client address means the
+/// app isn't served with connect info, and is answered with `500 Internal Server
+/// Error` rather than silently limiting nothing.
+///
+/// The buckets belong to the core: each core enforces the limit on the requests
+/// it serves, without any coordination between cores. To enforce a limit for the
+/// whole process instead, [split](RateLimitLayer::split_across_cores) it evenly
+/// across the cores, which works well when the kernel spreads connections
+/// evenly. Keys whose bucket has filled up again are evicted periodically, by a
+/// task on the runtime, which must have its timer enabled.
+///
+/// ```no_run
+/// # use std::{net::SocketAddr, time::Duration};
+/// # use monet::{Router, get, serve, middleware::RateLimitLayer};
+/// # async fn root() {}
+/// # async fn run(listener: monoio::net::TcpListener) {
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(RateLimitLayer::new(100, Duration::from_secs(60)).burst(20));
+///
+/// serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await;
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    requests: u32,
+    period: Duration,
+    burst: Option<u32>,
+    cores: u32,
+    key: KeyBy,
+    eviction_interval: Duration,
+    buckets: Rc<Buckets>,
+}
+
+impl RateLimitLayer {
+    /// Allow `requests` per `period` for each key, and bursts of as many.
+    ///
+    /// # Panics
+    ///
+    /// If `requests` or `period` is zero.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        assert!(requests > 0, "rate limits must allow some requests");
+        assert!(!period.is_zero(), "rate limit periods can't be zero");
+        Self {
+            requests,
+            period,
+            burst: None,
+            cores: 1,
+            key: KeyBy::ip(),
+            eviction_interval: Duration::from_secs(60),
+            buckets: Rc::default(),
+        }
+    }
+
+    /// The number of requests a key can make at once after being idle. Defaults
+    /// to the number of requests per period.
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = Some(burst);
+        self
+    }
+
+    /// What requests are counted by. Defaults to [`KeyBy::ip`].
+    pub fn key(mut self, key: KeyBy) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Treat the limit as one for the whole process, served by `cores` cores, by
+    /// giving each core an equal share of the rate and the burst, rounded up.
+    pub fn split_across_cores(mut self, cores: u32) -> Self {
+        self.cores = cores.max(1);
+        self
+    }
+
+    /// How often keys are checked for eviction. Defaults to 60 seconds.
+    pub fn eviction_interval(mut self, interval: Duration) -> Self {
+        self.eviction_interval = interval;
+        self
+    }
+
+    fn limits(&self) -> Limits {
+        let share = |n: u32| n.div_ceil(self.cores).max(1);
+        let requests = share(self.requests);
+        Limits {
+            capacity: share(self.burst.unwrap_or(self.requests)),
+            per_token: self.period / requests,
+        }
+    }
+}
+
+impl fmt::Debug for RateLimitLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitLayer")
+            .field("requests", &self.requests)
+            .field("period", &self.period)
+            .field("burst", &self.burst)
+            .field("cores", &self.cores)
+            .field("key", &self.key)
+            .field("eviction_interval", &self.eviction_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> TowerLayer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limits: self.limits(),
+            key: self.key.clone(),
+            eviction_interval: self.eviction_interval,
+            buckets: self.buckets.clone(),
+        }
+    }
+}
+
+/// What [`RateLimitLayer`] counts requests by.
+#[derive(Clone)]
+pub struct KeyBy(KeyInner);
+
+#[derive(Clone)]
+enum KeyInner {
+    Ip,
+    Header(HeaderName),
+    Custom(KeyFn),
+}
+
+impl KeyBy {
+    /// The client's IP address, from the
+    /// [`ConnectInfo<SocketAddr>`](ConnectInfo) recorded by
+    /// [`Router::into_make_service_with_connect_info`](crate::Router::into_make_service_with_connect_info).
+    /// Requests without it are rejected with `500 Internal Server Error`.
+    pub fn ip() -> Self {
+        Self(KeyInner::Ip)
+    }
+
+    /// The value of the header `name`, e.g. an API key, or a client address set
+    /// by a trusted proxy.
+    pub fn header(name: HeaderName) -> Self {
+        Self(KeyInner::Header(name))
+    }
+
+    /// The key `f` returns for the request, if any.
+    ///
+    /// Unlike handlers and [`from_extractor`](super::from_extractor()), `f` is not
+    /// an extractor: it runs synchronously, before the request is passed on, and
+    /// only sees the request head. Values that take async work to find, such as
+    /// an authenticated user, can be looked up by an earlier middleware and read
+    /// from the extensions here.
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&Parts) -> Option<String> + 'static,
+    {
+        Self(KeyInner::Custom(Rc::new(f)))
+    }
+
+    fn key_for(&self, parts: &Parts) -> Result<Option<Key>, MissingConnectInfo> {
+        Ok(match &self.0 {
+            KeyInner::Ip => {
+                let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<SocketAddr>>()
+                else {
+                    return Err(MissingConnectInfo(std::any::type_name::<SocketAddr>()));
+                };
+                Some(Key::Ip(addr.ip()))
+            }
+            KeyInner::Header(name) => parts
+                .headers
+                .get(name)
+                .map(|value| Key::Bytes(value.as_bytes().into())),
+            KeyInner::Custom(f) => f(parts).map(|key| Key::Bytes(key.into_bytes().into())),
+        })
+    }
+}
+
+impl fmt::Debug for KeyBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            KeyInner::Ip => f.write_str("Ip"),
+            KeyInner::Header(name) => f.debug_tuple("Header").field(name).finish(),
+            KeyInner::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(IpAddr),
+    Bytes(Box<[u8]>),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Limits {
+    capacity: u32,
+    /// The time it takes to refill one token.
+    per_token: Duration,
+}
+
+/// The buckets of one core, shared by the services of a layer.
+#[derive(Default)]
+struct Buckets {
+    map: RefCell<HashMap<Key, Bucket>>,
+    evicting: Cell<bool>,
+}
+
+/// A token bucket, stored as the instant it is, or was, full again. The tokens
+/// left are the time from `now` to that instant subtracted from the capacity.
+struct Bucket {
+    full_at: Instant,
+    capacity: u32,
+    per_token: Duration,
+}
+
+impl Bucket {
+    /// Take a token, or learn how long until one is available.
+    fn take(&mut self, now: Instant) -> Result<RateLimitState, Duration> {
+        let window = self.per_token * self.capacity;
+        let full_at = self.full_at.max(now);
+        let next = full_at + self.per_token;
+        // Taking the token would push refilling further out than a whole bucket.
+        if next > now + window {
+            return Err(next - (now + window));
+        }
+        self.full_at = next;
+
+        let missing = (next - now)
+            .as_nanos()
+            .div_ceil(self.per_token.as_nanos().max(1));
+        let missing = u32::try_from(missing).unwrap_or(u32::MAX);
+        Ok(RateLimitState {
+            limit: self.capacity,
+            remaining: self.capacity.saturating_sub(missing),
+            reset: next - now,
+        })
+    }
+}
+
+/// What the `RateLimit-*` headers report.
+#[derive(Clone, Copy, Debug)]
+struct RateLimitState {
+    limit: u32,
+    remaining: u32,
+    reset: Duration,
+}
+
+impl RateLimitState {
+    fn write(&self, headers: &mut HeaderMap) {
+        headers.insert(RATELIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(RATELIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(RATELIMIT_RESET, HeaderValue::from(ceil_secs(self.reset)));
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+impl Buckets {
+    fn take(
+        self: &Rc<Self>,
+        key: Key,
+        limits: Limits,
+        eviction_interval: Duration,
+    ) -> Result<RateLimitState, RateLimitState> {
+        if !self.evicting.replace(true) {
+            monoio::spawn(evict_idle(Rc::downgrade(self), eviction_interval));
+        }
+
+        let now = Instant::now();
+        let mut map = self.map.borrow_mut();
+        let bucket = map.entry(key).or_insert_with(|| Bucket {
+            full_at: now,
+            capacity: limits.capacity,
+            per_token: limits.per_token,
+        });
+        bucket.take(now).map_err(|retry_after| RateLimitState {
+            limit: bucket.capacity,
+            remaining: 0,
+            reset: retry_after,
+        })
+    }
+}
+
+/// Drop the buckets that are full again, which are no different from new ones,
+/// until the layer is gone.
+async fn evict_idle(buckets: Weak<Buckets>, interval: Duration) {
+    loop {
+        monoio::time::sleep(interval).await;
+        let Some(buckets) = buckets.upgrade() else {
+            return;
+        };
+        let now = Instant::now();
+        buckets
+            .map
+            .borrow_mut()
+            .retain(|_, bucket| bucket.full_at > now);
+    }
+}
+
+/// Limits the rate of requests, see [`RateLimitLayer`].
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limits: Limits,
+    key: KeyBy,
+    eviction_interval: Duration,
+    buckets: Rc<Buckets>,
+}
+
+impl<S> fmt::Debug for RateLimit<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("inner", &self.inner)
+            .field("limits", &self.limits)
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for RateLimit<S>
+where
+    S: TowerService<HttpRequest<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: HttpRequest<B>) -> Self::Future {
+        let (parts, body) = req.into_parts();
+        let key = match self.key.key_for(&parts) {
+            Ok(key) => key,
+            Err(missing) => {
+                return ResponseFuture {
+                    kind: Kind::Rejected {
+                        res: Some(missing.into_response()),
+                    },
+                };
+            }
+        };
+        let req = HttpRequest::from_parts(parts, body);
+
+        let Some(key) = key else {
+            return ResponseFuture {
+                kind: Kind::Allowed {
+                    future: self.inner.call(req),
+                    state: None,
+                },
+            };
+        };
+
+        match self.buckets.take(key, self.limits, self.eviction_interval) {
+            Ok(state) => ResponseFuture {
+                kind: Kind::Allowed {
+                    future: self.inner.call(req),
+                    state: Some(state),
+                },
+            },
+            Err(state) => {
+                let mut res = Problem::new(StatusCode::TOO_MANY_REQUESTS).into_rejection();
+                state.write(res.headers_mut());
+                res.headers_mut().insert(
+                    RETRY_AFTER,
+                    HeaderValue::from(ceil_secs(state.reset).max(1)),
+                );
+                ResponseFuture {
+                    kind: Kind::Rejected { res: Some(res) },
+                }
+            }
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`RateLimit`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        kind: Kind<F>,
+    }
+}
+
+pin_project! {
+    #[project = KindProj]
+    enum Kind<F> {
+        Allowed {
+            #[pin]
+            future: F,
+            state: Option<RateLimitState>,
+        },
+        Rejected { res: Option<HttpResponse> },
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().kind.project() {
+            KindProj::Allowed { future, state } => {
+                let mut res = ready!(future.poll(cx)?).into_response();
+                if let Some(state) = state {
+                    state.write(res.headers_mut());
+                }
+                Poll::Ready(Ok(res))
+            }
+            KindProj::Rejected { res } => {
+                Poll::Ready(Ok(res.take().expect("future polled after completion")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Router, get, middleware::map_request, test::TestClient};
+
+    #[monoio::test(timer_enabled = true)]
+    async fn rejects_once_the_bucket_is_empty() {
+        let app = Router::new().route("/", get(|| async { "hello" })).layer(
+            RateLimitLayer::new(2, Duration::from_secs(60))
+                .key(KeyBy::header(HeaderName::from_static("x-client"))),
+        );
+        let client = TestClient::new(app);
+
+        for remaining in ["1", "0"] {
+            client
+                .get("/")
+                .header("x-client", "a")
+                .send()
+                .await
+                .assert_status(StatusCode::OK)
+                .assert_header("ratelimit-limit", "2")
+                .assert_header("ratelimit-remaining", remaining);
+        }
+        let res = client.get("/").header("x-client", "a").send().await;
+        res.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.header(RETRY_AFTER).is_some());
+
+        // Other keys have their own bucket, and requests without a key are not limited.
+        client
+            .get("/")
+            .header("x-client", "b")
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+        for _ in 0..3 {
+            client.get("/").send().await.assert_status(StatusCode::OK);
+        }
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn keys_by_ip_or_fails_loudly() {
+        let limited = || {
+            Router::new()
+                .route("/", get(|| async {}))
+                .layer(RateLimitLayer::new(1, Duration::from_secs(60)))
+        };
+
+        TestClient::new(limited())
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let addr: SocketAddr = "192.0.2.1:1234".parse().unwrap();
+        let app = limited().layer(map_request(move |mut req: HttpRequest| async move {
+            req.extensions_mut().insert(ConnectInfo(addr));
+            req
+        }));
+        let client = TestClient::new(app);
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_header(RATELIMIT_REMAINING, "0")
+            .assert_header(RATELIMIT_RESET, "60");
+        client
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::TOO_MANY_REQUESTS)
+            .assert_header(RETRY_AFTER, "60")
+            .assert_header(RATELIMIT_REMAINING, "0");
+    }
+
+    #[test]
+    fn refills_tokens_over_time() {
+        let start = Instant::now();
+        let secs = |secs: f64| start + Duration::from_secs_f64(secs);
+        let mut bucket = Bucket {
+            full_at: start,
+            capacity: 3,
+            per_token: Duration::from_secs(1),
+        };
+
+        for remaining in [2, 1, 0] {
+            let state = bucket.take(start).unwrap();
+            assert_eq!(state.remaining, remaining);
+            assert_eq!(state.reset, Duration::from_secs(3 - u64::from(remaining)));
+        }
+        assert_eq!(bucket.take(start).unwrap_err(), Duration::from_secs(1));
+
+        // Half way through refilling the second token, one is available.
+        let state = bucket.take(secs(1.5)).unwrap();
+        assert_eq!(state.remaining, 0);
+        assert_eq!(state.reset, Duration::from_secs_f64(2.5));
+        assert_eq!(
+            bucket.take(secs(1.5)).unwrap_err(),
+            Duration::from_secs_f64(0.5)
+        );
+
+        // Long idle buckets are full, but not fuller.
+        assert_eq!(bucket.take(secs(60.0)).unwrap().remaining, 2);
+    }
+
+    #[test]
+    fn splits_limits_across_cores() {
+        let limits = RateLimitLayer::new(10, Duration::from_secs(60))
+            .burst(5)
+            .split_across_cores(4)
+            .limits();
+        assert_eq!(limits.capacity, 2);
+        assert_eq!(limits.per_token, Duration::from_secs(20));
+
+        let limits = RateLimitLayer::new(1, Duration::from_secs(60))
+            .split_across_cores(8)
+            .limits();
+        assert_eq!(limits.capacity, 1);
+        assert_eq!(limits.per_token, Duration::from_secs(60));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn evicts_buckets_once_full_again() {
+        let layer = RateLimitLayer::new(2, Duration::from_millis(200))
+            .key(KeyBy::header(HeaderName::from_static("x-client")))
+            .eviction_interval(Duration::from_millis(10));
+        let client = TestClient::new(
+            Router::new()
+                .route("/", get(|| async {}))
+                .layer(layer.clone()),
+        );
+        let keys = || layer.buckets.map.borrow().len();
+
+        client.get("/").header("x-client", "a").send().await;
+        assert_eq!(keys(), 1);
+
+        // The token is back after 100ms.
+        monoio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(keys(), 1);
+        monoio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(keys(), 0);
+    }
+}
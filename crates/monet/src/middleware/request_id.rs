@@ -0,0 +1,293 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::BuildHasher,
+    pin::Pin,
+    sync::OnceLock,
+    task::{Context, Poll, ready},
+};
+
+use http::{HeaderName, HeaderValue, StatusCode, request::Parts};
+use pin_project_lite::pin_project;
+
+use crate::{Problem, extract::FromRequestParts, prelude::*, util};
+
+/// The header request ids are read from and written to by default.
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Incoming ids longer than this are replaced by a fresh one.
+const MAX_INCOMING_LEN: usize = 256;
+
+/// Give every request an id, exposed to handlers as [`RequestId`] and echoed on
+/// the response.
+///
+/// A request that already carries a usable `x-request-id`, for example from a
+/// proxy or an upstream service, keeps it. Otherwise an id is generated from a
+/// per-process random prefix, the index of the core serving the request and a
+/// counter local to that core, so generating ids needs no coordination between
+/// cores.
+///
+/// ```no_run
+/// # use monet::{Router, get, middleware::{RequestIdLayer, TraceLayer}};
+/// # async fn root() {}
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(TraceLayer::new())
+///     .layer(RequestIdLayer::new());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequestIdLayer {
+    header: HeaderName,
+}
+
+impl Default for RequestIdLayer {
+    fn default() -> Self {
+        Self {
+            header: X_REQUEST_ID,
+        }
+    }
+}
+
+impl RequestIdLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `header` instead of `x-request-id`.
+    pub fn header_name(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+}
+
+impl<S> TowerLayer<S> for RequestIdLayer {
+    type Service = SetRequestId<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SetRequestId {
+            inner,
+            header: self.header.clone(),
+        }
+    }
+}
+
+/// Sets and propagates request ids, see [`RequestIdLayer`].
+#[derive(Clone, Debug)]
+pub struct SetRequestId<S> {
+    inner: S,
+    header: HeaderName,
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for SetRequestId<S>
+where
+    S: TowerService<HttpRequest<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: HttpRequest<B>) -> Self::Future {
+        let id = req
+            .headers()
+            .get(&self.header)
+            .filter(|value| is_usable(value))
+            .cloned()
+            .map(RequestId)
+            .unwrap_or_else(RequestId::generate);
+
+        req.headers_mut().insert(&self.header, id.0.clone());
+        req.extensions_mut().insert(id.clone());
+
+        ResponseFuture {
+            inner: self.inner.call(req),
+            header: Some((self.header.clone(), id)),
+        }
+    }
+}
+
+fn is_usable(value: &HeaderValue) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_INCOMING_LEN
+        && value.as_bytes().iter().all(u8::is_ascii_graphic)
+}
+
+pin_project! {
+    /// Response future for [`SetRequestId`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        header: Option<(HeaderName, RequestId)>,
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut res = ready!(this.inner.poll(cx)?).into_response();
+
+        let (header, id) = this.header.take().expect("future polled after completion");
+        if !res.headers().contains_key(&header) {
+            res.headers_mut().insert(header, id.0.clone());
+        }
+        res.extensions_mut().insert(id);
+
+        Poll::Ready(Ok(res))
+    }
+}
+
+/// The id of the current request, set by [`RequestIdLayer`].
+///
+/// Can be used as an extractor, and is also found in the extensions of the
+/// request and of the response, where logging middleware picks it up.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RequestId(HeaderValue);
+
+impl RequestId {
+    pub fn new(value: HeaderValue) -> Self {
+        Self(value)
+    }
+
+    pub fn header_value(&self) -> &HeaderValue {
+        &self.0
+    }
+
+    pub fn into_header_value(self) -> HeaderValue {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Incoming ids are checked to be visible ASCII, generated ones are hex.
+        self.0.to_str().unwrap_or_default()
+    }
+
+    fn generate() -> Self {
+        static PREFIX: OnceLock<u32> = OnceLock::new();
+
+        thread_local! {
+            static COUNTER: Cell<u64> = const { Cell::new(0) };
+        }
+
+        let prefix = *PREFIX.get_or_init(|| RandomState::new().hash_one(std::process::id()) as u32);
+        let core = util::core_id();
+        let count = COUNTER.with(|counter| {
+            let count = counter.get();
+            counter.set(count.wrapping_add(1));
+            count
+        });
+
+        let id = format!("{prefix:08x}-{core:x}-{count:x}");
+        Self(HeaderValue::from_str(&id).expect("hex digits are a valid header value"))
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<S> FromRequestParts<S> for RequestId {
+    type Rejection = MissingRequestId;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or(MissingRequestId)
+    }
+}
+
+/// Rejection used by [`RequestId`] when the request isn't behind a
+/// [`RequestIdLayer`].
+#[derive(Debug, Clone, Copy)]
+pub struct MissingRequestId;
+
+impl IntoResponse for MissingRequestId {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .detail(
+                "Missing request extension: `RequestId`. Is the route behind a `RequestIdLayer`?",
+            )
+            .into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    fn client(layer: RequestIdLayer) -> TestClient {
+        TestClient::new(
+            Router::new()
+                .route(
+                    "/",
+                    get(|id: RequestId| async move { id.as_str().to_owned() }),
+                )
+                .layer(layer),
+        )
+    }
+
+    #[monoio::test]
+    async fn generates_unique_ids() {
+        let client = client(RequestIdLayer::new());
+
+        let first = client.get("/").send().await;
+        let second = client.get("/").send().await;
+        let id = first.header(X_REQUEST_ID).unwrap();
+        first.assert_status(StatusCode::OK).assert_text(id);
+        assert_ne!(second.header(X_REQUEST_ID), Some(id));
+    }
+
+    #[monoio::test]
+    async fn keeps_usable_incoming_ids() {
+        let client = client(RequestIdLayer::new());
+
+        client
+            .get("/")
+            .header(X_REQUEST_ID, "abc-123")
+            .send()
+            .await
+            .assert_header(X_REQUEST_ID, "abc-123")
+            .assert_text("abc-123");
+
+        let long = "a".repeat(MAX_INCOMING_LEN + 1);
+        let res = client.get("/").header(X_REQUEST_ID, &long).send().await;
+        assert_ne!(res.header(X_REQUEST_ID), Some(long.as_str()));
+    }
+
+    #[monoio::test]
+    async fn uses_the_configured_header() {
+        let header = HeaderName::from_static("x-correlation-id");
+        client(RequestIdLayer::new().header_name(header.clone()))
+            .get("/")
+            .header(&header, "abc")
+            .send()
+            .await
+            .assert_header(&header, "abc")
+            .assert_text("abc");
+    }
+
+    #[monoio::test]
+    async fn is_missing_without_the_layer() {
+        TestClient::new(Router::new().route("/", get(|_: RequestId| async {})))
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
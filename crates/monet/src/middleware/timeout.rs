@@ -0,0 +1,254 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use http::{StatusCode, request::Parts};
+use monoio::time::{Instant, Timeout, timeout_at};
+use pin_project_lite::pin_project;
+
+use crate::{Problem, extract::FromRequestParts, prelude::*};
+
+/// Fail requests that take longer than a given duration to produce a response.
+///
+/// When the deadline passes the inner future is dropped, cancelling the handler,
+/// and the client gets `408 Request Timeout`, or the status set with
+/// [`TimeoutLayer::status_code`], `503 Service Unavailable` being the other
+/// common choice.
+///
+/// The deadline is recorded in the request's extensions as a [`Deadline`] so
+/// handlers and the services they call can budget their own work. Nested
+/// timeouts keep the earliest deadline.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use monet::{Router, get, middleware::TimeoutLayer};
+/// # async fn root() {}
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(TimeoutLayer::new(Duration::from_secs(10)));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+    status: StatusCode,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            status: StatusCode::REQUEST_TIMEOUT,
+        }
+    }
+
+    /// The status of the response sent when the deadline passes. Defaults to
+    /// `408 Request Timeout`.
+    pub fn status_code(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl<S> TowerLayer<S> for TimeoutLayer {
+    type Service = TimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimeoutService {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// Times requests out, see [`TimeoutLayer`].
+#[derive(Clone, Debug)]
+pub struct TimeoutService<S> {
+    inner: S,
+    layer: TimeoutLayer,
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for TimeoutService<S>
+where
+    S: TowerService<HttpRequest<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: HttpRequest<B>) -> Self::Future {
+        let deadline = Instant::now() + self.layer.timeout;
+
+        let budget = match req.extensions().get::<Deadline>() {
+            Some(outer) if outer.0 <= deadline => *outer,
+            _ => Deadline(deadline),
+        };
+        req.extensions_mut().insert(budget);
+
+        ResponseFuture {
+            inner: timeout_at(deadline, self.inner.call(req)),
+            status: self.layer.status,
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`TimeoutService`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: Timeout<F>,
+        status: StatusCode,
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        match ready!(this.inner.poll(cx)) {
+            Ok(res) => Poll::Ready(res.map(IntoResponse::into_response)),
+            Err(_elapsed) => Poll::Ready(Ok(Problem::new(*this.status).into_rejection())),
+        }
+    }
+}
+
+/// The point in time by which the response to the current request is due, set by
+/// [`TimeoutLayer`].
+///
+/// Can be used as an extractor, or read from the request's extensions by
+/// middleware and nested services.
+///
+/// ```no_run
+/// # use monet::{extract::query::Query, middleware::Deadline};
+/// # #[derive(serde::Deserialize)]
+/// # struct Search { q: String }
+/// # async fn backend(_: Search) -> String { String::new() }
+/// async fn search(deadline: Deadline, Query(q): Query<Search>) -> String {
+///     match monoio::time::timeout(deadline.remaining(), backend(q)).await {
+///         Ok(hits) => hits,
+///         Err(_) => "partial results".to_owned(),
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// The time left until the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.0 <= Instant::now()
+    }
+}
+
+impl<S> FromRequestParts<S> for Deadline {
+    type Rejection = MissingDeadline;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .copied()
+            .ok_or(MissingDeadline)
+    }
+}
+
+/// Rejection used by [`Deadline`] when the request isn't behind a [`TimeoutLayer`].
+#[derive(Debug, Clone, Copy)]
+pub struct MissingDeadline;
+
+impl IntoResponse for MissingDeadline {
+    fn into_response(self) -> HttpResponse {
+        Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .detail("Missing request extension: `Deadline`. Is the route behind a `TimeoutLayer`?")
+            .into_rejection()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    async fn slow() -> &'static str {
+        monoio::time::sleep(Duration::from_secs(5)).await;
+        "done"
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn cancels_slow_handlers() {
+        let app = Router::new()
+            .route("/slow", get(slow))
+            .route(
+                "/deadline",
+                get(|deadline: Deadline| async move {
+                    assert!(deadline.remaining() <= Duration::from_millis(50));
+                    "ok"
+                }),
+            )
+            .layer(TimeoutLayer::new(Duration::from_millis(50)));
+        let client = TestClient::new(app);
+
+        client
+            .get("/slow")
+            .send()
+            .await
+            .assert_status(StatusCode::REQUEST_TIMEOUT);
+        client
+            .get("/deadline")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("ok");
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn uses_the_configured_status() {
+        let app = Router::new().route("/slow", get(slow)).layer(
+            TimeoutLayer::new(Duration::from_millis(10))
+                .status_code(StatusCode::SERVICE_UNAVAILABLE),
+        );
+
+        TestClient::new(app)
+            .get("/slow")
+            .send()
+            .await
+            .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn nested_timeouts_keep_the_earliest_deadline() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|deadline: Deadline| async move {
+                    assert!(deadline.remaining() <= Duration::from_millis(20));
+                    "ok"
+                }),
+            )
+            .route_layer(TimeoutLayer::new(Duration::from_secs(60)))
+            .layer(TimeoutLayer::new(Duration::from_millis(20)));
+
+        TestClient::new(app).get("/").send().await.assert_text("ok");
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http::StatusCode;
+use pin_project_lite::pin_project;
+use tracing::{Level, Span, field};
+
+use super::request_id::RequestId;
+use crate::{Frame, extract::matched_path::MatchedPath, prelude::*, util};
+
+/// `tracing`'s macros need the level at compile time; pick the call by matching
+/// on a level chosen at runtime.
+macro_rules! with_level {
+    ($level:expr, $macro:ident!($($args:tt)*)) => {
+        match $level {
+            Level::ERROR => tracing::$macro!(Level::ERROR, $($args)*),
+            Level::WARN => tracing::$macro!(Level::WARN, $($args)*),
+            Level::INFO => tracing::$macro!(Level::INFO, $($args)*),
+            Level::DEBUG => tracing::$macro!(Level::DEBUG, $($args)*),
+            Level::TRACE => tracing::$macro!(Level::TRACE, $($args)*),
+        }
+    };
+}
+
+/// Log requests with [`tracing`].
+///
+/// Every request runs in a `request` span with the method, URI, HTTP version,
+/// [matched path](MatchedPath), [request id](RequestId) and the index of the core
+/// serving it. The status and the latency until the response head was produced
+/// are recorded on the span once known, and a single event is emitted when the
+/// response body has been sent, or dropped early, with the number of body bytes
+/// and the total duration. Server errors and failed bodies are reported at the
+/// failure level instead.
+///
+/// Connections get a `connection` span of their own from [`serve`](crate::serve())
+/// at the `DEBUG` level, with events when they are accepted and closed.
+///
+/// How, and whether, any of it is printed is up to the subscriber the application
+/// installs, for example `tracing_subscriber::fmt` with an `EnvFilter`.
+///
+/// ```no_run
+/// # use monet::{Router, get, middleware::{RequestIdLayer, TraceLayer}};
+/// # async fn root() {}
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .layer(TraceLayer::new())
+///     .layer(RequestIdLayer::new());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TraceLayer {
+    span_level: Level,
+    response_level: Level,
+    failure_level: Level,
+}
+
+impl Default for TraceLayer {
+    fn default() -> Self {
+        Self {
+            span_level: Level::INFO,
+            response_level: Level::INFO,
+            failure_level: Level::ERROR,
+        }
+    }
+}
+
+impl TraceLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The level of the `request` span. Defaults to `INFO`.
+    pub fn span_level(mut self, level: Level) -> Self {
+        self.span_level = level;
+        self
+    }
+
+    /// The level of the event emitted once a response has been sent. Defaults to
+    /// `INFO`.
+    pub fn response_level(mut self, level: Level) -> Self {
+        self.response_level = level;
+        self
+    }
+
+    /// The level used instead of the response level for `5xx` responses and
+    /// response bodies that fail. Defaults to `ERROR`.
+    pub fn failure_level(mut self, level: Level) -> Self {
+        self.failure_level = level;
+        self
+    }
+}
+
+impl<S> TowerLayer<S> for TraceLayer {
+    type Service = Trace<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Trace {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// Logs requests, see [`TraceLayer`].
+#[derive(Clone, Debug)]
+pub struct Trace<S> {
+    inner: S,
+    layer: TraceLayer,
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for Trace<S>
+where
+    S: TowerService<HttpRequest<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: HttpRequest<B>) -> Self::Future {
+        let start = Instant::now();
+        let span = with_level!(
+            self.layer.span_level,
+            span!(
+                "request",
+                method = %req.method(),
+                uri = %req.uri(),
+                version = ?req.version(),
+                matched_path = field::Empty,
+                request_id = field::Empty,
+                core = util::core_id(),
+                status = field::Empty,
+                latency = field::Empty,
+                bytes = field::Empty,
+            )
+        );
+        if let Some(path) = req.extensions().get::<MatchedPath>() {
+            span.record("matched_path", path.as_str());
+        }
+        if let Some(id) = req.extensions().get::<RequestId>() {
+            span.record("request_id", id.as_str());
+        }
+
+        let inner = span.in_scope(|| self.inner.call(req));
+        ResponseFuture {
+            inner,
+            span,
+            start,
+            layer: self.layer,
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`Trace`].
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        span: Span,
+        start: Instant,
+        layer: TraceLayer,
+    }
+}
+
+impl<F, T, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<HttpResponse, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _guard = this.span.enter();
+        let res = ready!(this.inner.poll(cx)?).into_response();
+
+        this.span.record("status", res.status().as_u16());
+        this.span
+            .record("latency", field::debug(this.start.elapsed()));
+
+        let span = this.span.clone();
+        let start = *this.start;
+        let status = res.status();
+        let layer = *this.layer;
+        Poll::Ready(Ok(res.map(|inner| {
+            Body::new(TraceBody {
+                inner,
+                span,
+                start,
+                status,
+                layer,
+                bytes: 0,
+                done: false,
+            })
+        })))
+    }
+}
+
+pin_project! {
+    /// Response body of [`Trace`], reports the response once it has been sent.
+    struct TraceBody {
+        #[pin]
+        inner: Body,
+        span: Span,
+        start: Instant,
+        status: StatusCode,
+        layer: TraceLayer,
+        bytes: u64,
+        done: bool,
+    }
+
+    impl PinnedDrop for TraceBody {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if *this.done {
+                return;
+            }
+            let _guard = this.span.enter();
+            this.span.record("bytes", *this.bytes);
+            // Bodies known to be empty are never polled.
+            let message = if this.inner.is_end_stream() {
+                "response sent"
+            } else {
+                "response body dropped before completion"
+            };
+            finished(this.layer, *this.status, *this.bytes, this.start.elapsed(), message);
+        }
+    }
+}
+
+impl HttpBody for TraceBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let _guard = this.span.enter();
+
+        let frame = ready!(this.inner.poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    *this.bytes += data.len() as u64;
+                }
+            }
+            Some(Err(err)) => {
+                *this.done = true;
+                this.span.record("bytes", *this.bytes);
+                let latency = this.start.elapsed();
+                with_level!(
+                    this.layer.failure_level,
+                    event!(
+                        status = this.status.as_u16(),
+                        bytes = *this.bytes,
+                        latency = ?latency,
+                        error = %err,
+                        "response body failed"
+                    )
+                );
+            }
+            None if !*this.done => {
+                *this.done = true;
+                this.span.record("bytes", *this.bytes);
+                let latency = this.start.elapsed();
+                finished(
+                    this.layer,
+                    *this.status,
+                    *this.bytes,
+                    latency,
+                    "response sent",
+                );
+            }
+            None => {}
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+fn finished(layer: &TraceLayer, status: StatusCode, bytes: u64, latency: Duration, message: &str) {
+    let level = if status.is_server_error() {
+        layer.failure_level
+    } else {
+        layer.response_level
+    };
+    with_level!(
+        level,
+        event!(
+            status = status.as_u16(),
+            bytes,
+            latency = ?latency,
+            "{message}"
+        )
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span,
+    };
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    type Fields = BTreeMap<String, String>;
+
+    /// Records spans and events, so tests can check what was logged.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        next_id: Arc<AtomicU64>,
+        spans: Arc<Mutex<BTreeMap<u64, (&'static str, Fields)>>>,
+        events: Arc<Mutex<Vec<(Level, Fields)>>>,
+    }
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl Visit for Visitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name().to_owned(), format!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_owned(), value.to_owned());
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut fields = Fields::new();
+            span.record(&mut Visitor(&mut fields));
+            self.spans
+                .lock()
+                .unwrap()
+                .insert(id, (span.metadata().name(), fields));
+            span::Id::from_u64(id)
+        }
+
+        fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            let (_, fields) = spans.get_mut(&span.into_u64()).unwrap();
+            values.record(&mut Visitor(fields));
+        }
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut Visitor(&mut fields));
+            self.events
+                .lock()
+                .unwrap()
+                .push((*event.metadata().level(), fields));
+        }
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    impl Recorder {
+        fn request_span(&self) -> Fields {
+            let spans = self.spans.lock().unwrap();
+            let mut requests = spans.values().filter(|(name, _)| *name == "request");
+            let (_, fields) = requests.next().expect("no request span");
+            assert!(requests.next().is_none(), "more than one request span");
+            fields.clone()
+        }
+
+        fn events(&self) -> Vec<(Level, Fields)> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/users/{id}", get(|| async { "hello" }))
+            .route(
+                "/error",
+                get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            )
+            .layer(TraceLayer::new())
+    }
+
+    #[monoio::test]
+    async fn records_the_request_and_response() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        TestClient::new(app())
+            .get("/users/7?full=true")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("hello");
+
+        let span = recorder.request_span();
+        assert_eq!(span["method"], "GET");
+        assert_eq!(span["uri"], "/users/7?full=true");
+        assert_eq!(span["matched_path"], "/users/{id}");
+        assert_eq!(span["status"], "200");
+        assert_eq!(span["bytes"], "5");
+
+        let events = recorder.events();
+        assert_eq!(events.len(), 1, "{events:?}");
+        let (level, fields) = &events[0];
+        assert_eq!(*level, Level::INFO);
+        assert_eq!(fields["message"], "response sent");
+        assert_eq!(fields["status"], "200");
+    }
+
+    #[monoio::test]
+    async fn reports_server_errors_at_the_failure_level() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        TestClient::new(app())
+            .get("/error")
+            .send()
+            .await
+            .assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let events = recorder.events();
+        assert_eq!(events.len(), 1, "{events:?}");
+        assert_eq!(events[0].0, Level::ERROR);
+        assert_eq!(events[0].1["status"], "500");
+    }
+}
@@ -0,0 +1,326 @@
+//! OpenAPI 3.1 documents generated from a [`Router`].
+//!
+//! Every route ends up in the document, with its path parameters. Routes added
+//! with [`openapi::get`](get) instead of [`routing::get`](crate::get) are also
+//! documented from the extractors of their handler, through [`OperationInput`],
+//! and [`get_with`] adds summaries, responses and the like on top. Parameter and
+//! body types describe themselves with [`JsonSchema`], which can be derived.
+//!
+//! [`Router::openapi`] serves the document, and optionally a Swagger UI or Redoc
+//! page for it.
+//!
+//! ```no_run
+//! # use http::StatusCode;
+//! # use monet::{Router, extract::{path::Path, query::Query}};
+//! # use monet::openapi::{self, JsonSchema, OpenApi};
+//! # use serde::{Deserialize, Serialize};
+//! # #[derive(Serialize, JsonSchema)]
+//! # struct User { id: u32 }
+//! # async fn show_user(Path(id): Path<u32>) -> String { todo!() }
+//! #[derive(Deserialize, JsonSchema)]
+//! struct Pagination {
+//!     /// The page to return, starting at 1.
+//!     page: Option<u32>,
+//!     per_page: Option<u32>,
+//! }
+//!
+//! async fn list_users(Query(pagination): Query<Pagination>) -> String { todo!() }
+//!
+//! let app: Router = Router::new()
+//!     .route("/users", openapi::get(list_users))
+//!     .route(
+//!         "/users/{id}",
+//!         openapi::get_with(show_user, |op| {
+//!             op.summary("Show a user")
+//!                 .response::<User>(StatusCode::OK, "The user")
+//!                 .empty_response(StatusCode::NOT_FOUND, "No such user")
+//!         }),
+//!     )
+//!     .openapi(OpenApi::new("Users", "1.0.0").swagger_ui("/docs"));
+//! ```
+
+use std::{collections::BTreeMap, rc::Rc};
+
+use http::{Method, header};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    Body, HttpResponse, Router,
+    handler::Handler,
+    routing::{
+        method_router::MethodRouter,
+        router::{Endpoint, NEST_TAIL_PARAM},
+    },
+};
+
+pub mod operation;
+pub mod schema;
+
+pub use self::{
+    operation::{Operation, OperationInput},
+    schema::{JsonSchema, Schema},
+};
+pub use monet_macros::JsonSchema;
+
+/// Route `GET` requests to `handler`, like [`routing::get`](crate::get), and
+/// document the operation from the handler's extractors.
+pub fn get<H, X, S>(handler: H) -> MethodRouter<S>
+where
+    H: Handler<X, S>,
+    X: OperationInput + 'static,
+    S: Clone + 'static,
+{
+    get_with(handler, |operation| operation)
+}
+
+/// Like [`get`], with `document` adding to what the extractors documented.
+pub fn get_with<H, X, S, F>(handler: H, document: F) -> MethodRouter<S>
+where
+    H: Handler<X, S>,
+    X: OperationInput + 'static,
+    S: Clone + 'static,
+    F: FnOnce(Operation) -> Operation,
+{
+    let operation = document(X::document(Operation::new()));
+    crate::get(handler).document(Method::GET, operation)
+}
+
+/// The Swagger UI release served by [`OpenApi::swagger_ui`].
+const SWAGGER_UI_VERSION: &str = "5.17.14";
+
+/// The Redoc release served by [`OpenApi::redoc`].
+const REDOC_VERSION: &str = "2.1.5";
+
+/// The top-level information of an OpenAPI document and where to serve it, see
+/// [`Router::openapi`].
+#[derive(Clone, Debug)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+    description: Option<String>,
+    servers: Vec<String>,
+    path: String,
+    swagger_ui: Option<String>,
+    redoc: Option<String>,
+}
+
+impl OpenApi {
+    /// A document for the API `title` at `version`, served at `/openapi.json`.
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            version: version.into(),
+            description: None,
+            servers: Vec::new(),
+            path: "/openapi.json".to_owned(),
+            swagger_ui: None,
+            redoc: None,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Add the URL of a server hosting the API.
+    pub fn server(mut self, url: impl Into<String>) -> Self {
+        self.servers.push(url.into());
+        self
+    }
+
+    /// Serve the document at `path` instead of `/openapi.json`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Serve a Swagger UI page for the document at `path`.
+    ///
+    /// The page loads Swagger UI from unpkg.com, pinned to a known version.
+    pub fn swagger_ui(mut self, path: impl Into<String>) -> Self {
+        self.swagger_ui = Some(path.into());
+        self
+    }
+
+    /// Serve a Redoc page for the document at `path`.
+    ///
+    /// The page loads Redoc from cdn.redoc.ly, pinned to a known version.
+    pub fn redoc(mut self, path: impl Into<String>) -> Self {
+        self.redoc = Some(path.into());
+        self
+    }
+
+    /// The document for the routes of `router`.
+    ///
+    /// Services added with [`Router::nest_service`] show up as path items without
+    /// operations, since they may answer any method. The rest of their path is the
+    /// `path` parameter. The fallback is left out.
+    pub fn document<S>(&self, router: &Router<S>) -> Value
+    where
+        S: Clone,
+    {
+        let mut paths = BTreeMap::new();
+        for (id, path) in &router.node.route_id_to_path {
+            let (path, item) = match router.routes.get(id.0) {
+                Some(Endpoint::MethodRouter(method_router)) => {
+                    let (path, params) = openapi_path(path);
+                    let item: Map<String, Value> = method_router
+                        .operations()
+                        .into_iter()
+                        .map(|(method, operation)| {
+                            (method.as_str().to_lowercase(), operation.to_value(&params))
+                        })
+                        .collect();
+                    (path, Value::Object(item))
+                }
+                Some(Endpoint::Route(_)) => nested_service_item(path),
+                None => continue,
+            };
+            paths.insert(path, item);
+        }
+
+        let mut info = json!({ "title": self.title, "version": self.version });
+        if let Some(description) = &self.description {
+            info["description"] = description.as_str().into();
+        }
+        let mut document = json!({
+            "openapi": "3.1.0",
+            "info": info,
+            "paths": paths,
+        });
+        if !self.servers.is_empty() {
+            let servers: Vec<Value> = self
+                .servers
+                .iter()
+                .map(|url| json!({ "url": url }))
+                .collect();
+            document["servers"] = servers.into();
+        }
+        document
+    }
+}
+
+impl<S> Router<S>
+where
+    S: Clone + 'static,
+{
+    /// Serve the OpenAPI document of this router as described by `api`.
+    ///
+    /// The document is generated once, here, so this should be called after all
+    /// routes have been added. The routes serving it are not documented
+    /// themselves; layers added after this call apply to them too.
+    pub fn openapi(self, api: OpenApi) -> Self {
+        let document: Rc<str> = api.document(&self).to_string().into();
+        let mut router = self.route(
+            &api.path,
+            crate::get(move || {
+                let document = document.clone();
+                async move { respond("application/json", document.to_string()) }
+            }),
+        );
+
+        let title = escape_html(&api.title);
+        // A JSON string is a valid JavaScript string literal.
+        let spec_url = Value::from(api.path.as_str()).to_string();
+        if let Some(path) = &api.swagger_ui {
+            let page: Rc<str> = format!(
+                r##"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@{SWAGGER_UI_VERSION}/swagger-ui.css">
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist@{SWAGGER_UI_VERSION}/swagger-ui-bundle.js"></script>
+<script>SwaggerUIBundle({{ url: {spec_url}, dom_id: "#swagger-ui" }});</script>
+</body>
+</html>
+"##
+            )
+            .into();
+            router = router.route(path, html_route(page));
+        }
+        if let Some(path) = &api.redoc {
+            let spec_url = escape_html(&api.path);
+            let page: Rc<str> = format!(
+                r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+</head>
+<body>
+<redoc spec-url="{spec_url}"></redoc>
+<script src="https://cdn.redoc.ly/redoc/v{REDOC_VERSION}/bundles/redoc.standalone.js"></script>
+</body>
+</html>
+"#
+            )
+            .into();
+            router = router.route(path, html_route(page));
+        }
+        router
+    }
+}
+
+fn html_route<S>(page: Rc<str>) -> MethodRouter<S>
+where
+    S: Clone + 'static,
+{
+    crate::get(move || {
+        let page = page.clone();
+        async move { respond("text/html; charset=utf-8", page.to_string()) }
+    })
+}
+
+fn respond(content_type: &'static str, body: String) -> HttpResponse {
+    let mut res = HttpResponse::new(Body::from(body));
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static(content_type),
+    );
+    res
+}
+
+/// The path item of a route that [`Router::nest_service`] added.
+fn nested_service_item(path: &str) -> (String, Value) {
+    let path = path.replace(&format!("{{*{NEST_TAIL_PARAM}}}"), "{path}");
+    let (path, params) = openapi_path(&path);
+    let mut item = json!({
+        "description": "Handled by a nested service, for any method.",
+    });
+    if !params.is_empty() {
+        item["parameters"] = Operation::new().to_value(&params)["parameters"].take();
+    }
+    (path, item)
+}
+
+/// The OpenAPI form of a route path, and the names of its parameters.
+///
+/// Both use `{name}` for parameters, catch-all `{*name}` parameters lose the `*`.
+fn openapi_path(path: &str) -> (String, Vec<&str>) {
+    let mut params = Vec::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + end];
+        // `{{` is an escaped brace, not a parameter.
+        if !name.starts_with('{') {
+            params.push(name.trim_start_matches('*'));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    (path.replace("{*", "{"), params)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
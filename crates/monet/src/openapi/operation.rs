@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+
+use http::{Method, StatusCode, Uri};
+use serde_json::{Map, Value, json};
+
+use super::schema::{JsonSchema, Schema};
+use crate::{
+    HttpRequest, State, TaskScope,
+    extract::{
+        auth::{ApiKey, Authenticated, BasicAuth, BearerToken},
+        connect_info::ConnectInfo,
+        form::Form,
+        json::Json,
+        matched_path::MatchedPath,
+        multipart::Multipart,
+        path::Path,
+        query::Query,
+        validated::Validated,
+        ws::WebSocketUpgrade,
+    },
+    middleware::{Deadline, RequestId},
+};
+
+/// The documentation of an operation, i.e. a method on a path.
+///
+/// Extractors fill in what they know through [`OperationInput`], the rest is
+/// added with [`get_with`](super::get_with).
+#[derive(Clone, Debug, Default)]
+pub struct Operation {
+    summary: Option<String>,
+    description: Option<String>,
+    operation_id: Option<String>,
+    tags: Vec<String>,
+    deprecated: bool,
+    parameters: Vec<Value>,
+    request_body: Option<Value>,
+    responses: BTreeMap<String, Value>,
+}
+
+impl Operation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn summary(mut self, summary: impl Into<String>) -> Self {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// A unique name for the operation, used e.g. by client generators.
+    pub fn operation_id(mut self, id: impl Into<String>) -> Self {
+        self.operation_id = Some(id.into());
+        self
+    }
+
+    /// Add a tag, which documentation UIs group operations by.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn deprecated(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+
+    /// Add a parameter of kind `location`: `"query"`, `"header"`, `"path"` or
+    /// `"cookie"`. Replaces any previous parameter of the same name and kind.
+    pub fn parameter(mut self, location: &str, name: &str, schema: Schema, required: bool) -> Self {
+        self.parameters
+            .retain(|param| param["name"] != name || param["in"] != location);
+        self.parameters.push(json!({
+            "name": name,
+            "in": location,
+            "required": required,
+            "schema": schema.into_value(),
+        }));
+        self
+    }
+
+    /// Add a query parameter.
+    pub fn query_parameter<T>(self, name: &str) -> Self
+    where
+        T: JsonSchema + ?Sized,
+    {
+        self.parameter("query", name, T::schema(), !T::optional())
+    }
+
+    /// Add a header parameter.
+    pub fn header_parameter<T>(self, name: &str) -> Self
+    where
+        T: JsonSchema + ?Sized,
+    {
+        self.parameter("header", name, T::schema(), !T::optional())
+    }
+
+    /// Set the request body, of the given content type.
+    pub fn body(mut self, content_type: &str, schema: Schema) -> Self {
+        self.request_body = Some(json!({
+            "required": true,
+            "content": { content_type: { "schema": schema.into_value() } },
+        }));
+        self
+    }
+
+    /// Set the request body to JSON of type `T`.
+    pub fn json_body<T>(self) -> Self
+    where
+        T: JsonSchema + ?Sized,
+    {
+        self.body("application/json", T::schema())
+    }
+
+    /// Add a response with a JSON body of type `T`.
+    pub fn response<T>(self, status: StatusCode, description: &str) -> Self
+    where
+        T: JsonSchema + ?Sized,
+    {
+        self.response_with(status, description, Some(("application/json", T::schema())))
+    }
+
+    /// Add a response without a body.
+    pub fn empty_response(self, status: StatusCode, description: &str) -> Self {
+        self.response_with(status, description, None)
+    }
+
+    /// Add a response with a body of any content type, or none.
+    pub fn response_with(
+        mut self,
+        status: StatusCode,
+        description: &str,
+        body: Option<(&str, Schema)>,
+    ) -> Self {
+        let mut response = json!({ "description": description });
+        if let Some((content_type, schema)) = body {
+            response["content"] = json!({ content_type: { "schema": schema.into_value() } });
+        }
+        self.responses.insert(status.as_str().to_owned(), response);
+        self
+    }
+
+    /// The OpenAPI operation object, adding a string parameter for each of
+    /// `path_params` that isn't documented yet.
+    pub(crate) fn to_value(&self, path_params: &[&str]) -> Value {
+        let mut parameters: Vec<Value> = path_params
+            .iter()
+            .filter(|name| {
+                !self
+                    .parameters
+                    .iter()
+                    .any(|param| param["in"] == "path" && param["name"] == **name)
+            })
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        parameters.extend(self.parameters.iter().cloned());
+
+        let mut operation = Map::new();
+        let mut set = |key: &str, value: Value| {
+            operation.insert(key.to_owned(), value);
+        };
+        if let Some(summary) = &self.summary {
+            set("summary", summary.as_str().into());
+        }
+        if let Some(description) = &self.description {
+            set("description", description.as_str().into());
+        }
+        if let Some(id) = &self.operation_id {
+            set("operationId", id.as_str().into());
+        }
+        if !self.tags.is_empty() {
+            set("tags", self.tags.clone().into());
+        }
+        if self.deprecated {
+            set("deprecated", true.into());
+        }
+        if !parameters.is_empty() {
+            set("parameters", parameters.into());
+        }
+        if let Some(body) = &self.request_body {
+            set("requestBody", body.clone());
+        }
+        if !self.responses.is_empty() {
+            set(
+                "responses",
+                Value::Object(self.responses.clone().into_iter().collect()),
+            );
+        }
+        Value::Object(operation)
+    }
+}
+
+/// Extractors that contribute to the documentation of the operations using them,
+/// e.g. [`Query`] adds its query parameters.
+///
+/// Extractors that don't show up in the API, like [`State`], keep the default,
+/// which adds nothing. Implemented for the extractor lists of handlers so
+/// [`openapi::get`](super::get) can document a handler from its signature.
+pub trait OperationInput {
+    fn document(operation: Operation) -> Operation {
+        operation
+    }
+}
+
+/// Adds a query parameter for each property of `T`.
+impl<T> OperationInput for Query<T>
+where
+    T: JsonSchema,
+{
+    fn document(mut operation: Operation) -> Operation {
+        let schema = T::schema();
+        for (name, property, required) in schema.properties() {
+            operation = operation.parameter("query", name, Schema::new(property.clone()), required);
+        }
+        operation
+    }
+}
+
+/// Types the path parameters named after the properties of `T`. Parameters
+/// extracted by position are documented from the route, as strings.
+impl<T> OperationInput for Path<T>
+where
+    T: JsonSchema,
+{
+    fn document(mut operation: Operation) -> Operation {
+        let schema = T::schema();
+        for (name, property, _) in schema.properties() {
+            operation = operation.parameter("path", name, Schema::new(property.clone()), true);
+        }
+        operation
+    }
+}
+
+/// Adds an `application/json` request body.
+impl<T> OperationInput for Json<T>
+where
+    T: JsonSchema,
+{
+    fn document(operation: Operation) -> Operation {
+        operation.json_body::<T>()
+    }
+}
+
+/// Adds an `application/x-www-form-urlencoded` request body.
+impl<T> OperationInput for Form<T>
+where
+    T: JsonSchema,
+{
+    fn document(operation: Operation) -> Operation {
+        operation.body("application/x-www-form-urlencoded", T::schema())
+    }
+}
+
+/// Adds a `multipart/form-data` request body.
+impl OperationInput for Multipart {
+    fn document(operation: Operation) -> Operation {
+        operation.body("multipart/form-data", Schema::of_type("object"))
+    }
+}
+
+impl<T, E> OperationInput for Result<T, E>
+where
+    T: OperationInput,
+{
+    fn document(operation: Operation) -> Operation {
+        T::document(operation)
+    }
+}
+
+impl<E> OperationInput for Validated<E>
+where
+    E: OperationInput,
+{
+    fn document(operation: Operation) -> Operation {
+        E::document(operation)
+    }
+}
+
+impl<T> OperationInput for State<T> {}
+impl<T> OperationInput for ConnectInfo<T> {}
+impl OperationInput for TaskScope {}
+impl OperationInput for MatchedPath {}
+impl OperationInput for WebSocketUpgrade {}
+impl OperationInput for RequestId {}
+impl OperationInput for Deadline {}
+impl OperationInput for Method {}
+impl OperationInput for Uri {}
+impl OperationInput for HttpRequest {}
+impl OperationInput for BasicAuth {}
+impl OperationInput for BearerToken {}
+impl OperationInput for ApiKey {}
+impl<P> OperationInput for Authenticated<P> {}
+
+/// Handlers without arguments.
+impl OperationInput for ((),) {}
+
+macro_rules! impl_operation_input {
+    (
+        [$($ty:ident),*], $last:ident
+    ) => {
+        impl<M, $($ty,)* $last> OperationInput for (M, $($ty,)* $last,)
+        where
+            $( $ty: OperationInput, )*
+            $last: OperationInput,
+        {
+            fn document(operation: Operation) -> Operation {
+                $( let operation = $ty::document(operation); )*
+                $last::document(operation)
+            }
+        }
+    };
+}
+
+impl_operation_input!([], T1);
+impl_operation_input!([T1], T2);
+impl_operation_input!([T1, T2], T3);
+all_the_tuples!(impl_operation_input);
@@ -0,0 +1,262 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+    sync::Arc,
+};
+
+use serde_json::{Map, Value, json};
+
+/// A JSON Schema, as used by OpenAPI 3.1 for parameters and bodies.
+///
+/// Usually produced by a [`JsonSchema`] implementation; the constructors are
+/// meant for those, whether written by hand or derived.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema(Value);
+
+impl Schema {
+    /// A schema from its JSON representation.
+    pub fn new(value: Value) -> Self {
+        Self(value)
+    }
+
+    /// A schema accepting any value.
+    pub fn any() -> Self {
+        Self(json!({}))
+    }
+
+    /// A schema of the given JSON `type`, e.g. `"string"`.
+    pub fn of_type(ty: &str) -> Self {
+        Self(json!({ "type": ty }))
+    }
+
+    /// An object without properties yet, see [`Schema::property`].
+    pub fn object() -> Self {
+        Self(json!({ "type": "object", "properties": {} }))
+    }
+
+    /// An array of `items`.
+    pub fn array(items: Schema) -> Self {
+        Self(json!({ "type": "array", "items": items.0 }))
+    }
+
+    /// A string that is one of `values`.
+    pub fn string_enum<I, V>(values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        let values: Vec<String> = values.into_iter().map(Into::into).collect();
+        Self(json!({ "type": "string", "enum": values }))
+    }
+
+    /// Add a property to an object schema, listing it as required unless
+    /// `required` is false.
+    ///
+    /// # Panics
+    ///
+    /// If the schema isn't an object.
+    pub fn property(mut self, name: &str, schema: Schema, required: bool) -> Self {
+        let object = self.0.as_object_mut().expect("not an object schema");
+        object
+            .entry("properties")
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .expect("`properties` of an object schema must be an object")
+            .insert(name.to_owned(), schema.0);
+        if required {
+            object
+                .entry("required")
+                .or_insert_with(|| Value::Array(Vec::new()))
+                .as_array_mut()
+                .expect("`required` of an object schema must be an array")
+                .push(name.into());
+        }
+        self
+    }
+
+    /// Set the `description`.
+    pub fn description(mut self, description: &str) -> Self {
+        self.set("description", description.into());
+        self
+    }
+
+    /// Set the `format`, e.g. `"int64"`.
+    pub fn format(mut self, format: &str) -> Self {
+        self.set("format", format.into());
+        self
+    }
+
+    /// Set `keyword` to `value`, for anything the other methods don't cover.
+    ///
+    /// # Panics
+    ///
+    /// If the schema isn't a JSON object, as schemas from the other constructors
+    /// always are.
+    pub fn set(&mut self, keyword: &str, value: Value) {
+        self.0
+            .as_object_mut()
+            .expect("not a schema object")
+            .insert(keyword.to_owned(), value);
+    }
+
+    /// The properties of an object schema and whether each is required.
+    pub(crate) fn properties(&self) -> impl Iterator<Item = (&str, &Value, bool)> {
+        let required: Vec<&str> = self.0["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        self.0["properties"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(move |(name, schema)| (name.as_str(), schema, required.contains(&name.as_str())))
+    }
+
+    pub fn as_value(&self) -> &Value {
+        &self.0
+    }
+
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+}
+
+impl From<Schema> for Value {
+    fn from(schema: Schema) -> Self {
+        schema.0
+    }
+}
+
+/// Types with a [`Schema`] describing their serialized form.
+///
+/// Implemented for primitives, strings, collections and smart pointers, and
+/// derivable with `#[derive(JsonSchema)]` for structs and field-less enums. The
+/// derive follows the `#[serde(rename, rename_all, skip, default)]` attributes
+/// and turns doc comments into descriptions.
+pub trait JsonSchema {
+    fn schema() -> Schema;
+
+    /// Whether the value may be absent when it is an object property or a
+    /// parameter, as for `Option`.
+    fn optional() -> bool {
+        false
+    }
+}
+
+macro_rules! impl_schema {
+    ($($ty:ty => $schema:expr),* $(,)?) => {
+        $(
+            impl JsonSchema for $ty {
+                fn schema() -> Schema {
+                    $schema
+                }
+            }
+        )*
+    };
+}
+
+impl_schema! {
+    bool => Schema::of_type("boolean"),
+    i8 => Schema::of_type("integer").format("int8"),
+    i16 => Schema::of_type("integer").format("int16"),
+    i32 => Schema::of_type("integer").format("int32"),
+    i64 => Schema::of_type("integer").format("int64"),
+    isize => Schema::of_type("integer").format("int64"),
+    u8 => Schema::of_type("integer").format("uint8"),
+    u16 => Schema::of_type("integer").format("uint16"),
+    u32 => Schema::of_type("integer").format("uint32"),
+    u64 => Schema::of_type("integer").format("uint64"),
+    usize => Schema::of_type("integer").format("uint64"),
+    f32 => Schema::of_type("number").format("float"),
+    f64 => Schema::of_type("number").format("double"),
+    char => Schema::of_type("string"),
+    str => Schema::of_type("string"),
+    String => Schema::of_type("string"),
+    () => Schema::of_type("null"),
+    Value => Schema::any(),
+}
+
+impl<T> JsonSchema for Option<T>
+where
+    T: JsonSchema,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T> JsonSchema for Vec<T>
+where
+    T: JsonSchema,
+{
+    fn schema() -> Schema {
+        Schema::array(T::schema())
+    }
+}
+
+impl<T> JsonSchema for [T]
+where
+    T: JsonSchema,
+{
+    fn schema() -> Schema {
+        Schema::array(T::schema())
+    }
+}
+
+impl<T> JsonSchema for HashMap<String, T>
+where
+    T: JsonSchema,
+{
+    fn schema() -> Schema {
+        Schema::new(json!({ "type": "object", "additionalProperties": T::schema().0 }))
+    }
+}
+
+impl<T> JsonSchema for BTreeMap<String, T>
+where
+    T: JsonSchema,
+{
+    fn schema() -> Schema {
+        HashMap::<String, T>::schema()
+    }
+}
+
+macro_rules! impl_schema_for_pointer {
+    ($($ptr:ident),*) => {
+        $(
+            impl<T> JsonSchema for $ptr<T>
+            where
+                T: JsonSchema + ?Sized,
+            {
+                fn schema() -> Schema {
+                    T::schema()
+                }
+
+                fn optional() -> bool {
+                    T::optional()
+                }
+            }
+        )*
+    };
+}
+
+impl_schema_for_pointer!(Box, Rc, Arc);
+
+impl<T> JsonSchema for &T
+where
+    T: JsonSchema + ?Sized,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+
+    fn optional() -> bool {
+        T::optional()
+    }
+}
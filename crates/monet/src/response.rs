@@ -4,6 +4,11 @@ use crate::HttpResponse;
 use std::borrow::Cow;
 use std::convert::Infallible;
 
+pub mod problem;
+pub mod sse;
+
+pub use self::{problem::Problem, sse::Sse};
+
 pub trait IntoResponse {
     /// Create a response.
     fn into_response(self) -> HttpResponse;
@@ -73,3 +78,16 @@ impl IntoResponse for Infallible {
         match self {}
     }
 }
+
+impl<T, E> IntoResponse for Result<T, E>
+where
+    T: IntoResponse,
+    E: IntoResponse,
+{
+    fn into_response(self) -> HttpResponse {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(err) => err.into_response(),
+        }
+    }
+}
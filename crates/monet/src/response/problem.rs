@@ -0,0 +1,267 @@
+use std::fmt;
+
+use http::{HeaderValue, StatusCode, header};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{HttpResponse, IntoResponse};
+
+/// An error response in the Problem Details format of RFC 9457, served as
+/// `application/problem+json`.
+///
+/// ```no_run
+/// # use http::StatusCode;
+/// # use monet::Problem;
+/// async fn withdraw() -> Result<String, Problem> {
+///     Err(Problem::new(StatusCode::FORBIDDEN)
+///         .with_type("https://example.com/probs/out-of-credit")
+///         .title("You do not have enough credit.")
+///         .detail("Your current balance is 30, but that costs 50.")
+///         .instance("/account/12345/msgs/abc")
+///         .extension("balance", 30))
+/// }
+/// ```
+///
+/// The rejections built into monet respond with plain text by default and carry
+/// their `Problem` in the response extensions;
+/// [`Router::problem_details`](crate::Router::problem_details) makes the router
+/// respond with it instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    type_uri: Option<String>,
+    title: Option<String>,
+    status: StatusCode,
+    detail: Option<String>,
+    instance: Option<String>,
+    extensions: Map<String, Value>,
+}
+
+impl Problem {
+    /// A problem with the given status, titled with its canonical reason phrase.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            type_uri: None,
+            title: status.canonical_reason().map(str::to_owned),
+            status,
+            detail: None,
+            instance: None,
+            extensions: Map::new(),
+        }
+    }
+
+    /// A URI identifying the type of problem. Defaults to `about:blank`, meaning
+    /// the problem is just what the status says.
+    pub fn with_type(mut self, uri: impl Into<String>) -> Self {
+        self.type_uri = Some(uri.into());
+        self
+    }
+
+    /// A short summary of the type of problem, the same for every occurrence.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// An explanation of this occurrence of the problem.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// A URI identifying this occurrence of the problem.
+    pub fn instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Add the extension member `name`. Names of the standard members are
+    /// ignored.
+    ///
+    /// # Panics
+    ///
+    /// If `value` can't be serialized to JSON.
+    pub fn extension(mut self, name: impl Into<String>, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).expect("failed to serialize problem extension");
+        self.extensions.insert(name.into(), value);
+        self
+    }
+
+    pub fn type_uri(&self) -> &str {
+        self.type_uri.as_deref().unwrap_or("about:blank")
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The problem details object.
+    pub fn to_json(&self) -> Value {
+        let mut object = self.extensions.clone();
+        object.insert("type".to_owned(), self.type_uri().into());
+        if let Some(title) = &self.title {
+            object.insert("title".to_owned(), title.as_str().into());
+        }
+        object.insert("status".to_owned(), self.status.as_u16().into());
+        if let Some(detail) = &self.detail {
+            object.insert("detail".to_owned(), detail.as_str().into());
+        }
+        if let Some(instance) = &self.instance {
+            object.insert("instance".to_owned(), instance.as_str().into());
+        }
+        Value::Object(object)
+    }
+
+    /// The response of a built-in rejection: the detail as plain text, with the
+    /// problem in the extensions.
+    pub(crate) fn into_rejection(self) -> HttpResponse {
+        let mut res = self.detail.clone().unwrap_or_default().into_response();
+        *res.status_mut() = self.status;
+        res.extensions_mut().insert(self);
+        res
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> HttpResponse {
+        let mut res = self.to_json().to_string().into_response();
+        *res.status_mut() = self.status;
+        res.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        res
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.status)?;
+        if let Some(text) = self.detail.as_ref().or(self.title.as_ref()) {
+            write!(f, ": {text}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Problem {}
+
+/// Replace a response carrying a [`Problem`] in its extensions with the problem
+/// details response, keeping its other headers and extensions.
+pub(crate) fn render_problem(mut res: HttpResponse) -> HttpResponse {
+    let Some(problem) = res.extensions_mut().remove::<Problem>() else {
+        return res;
+    };
+    let (mut parts, _body) = res.into_parts();
+    parts.headers.remove(header::CONTENT_TYPE);
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    let mut res = problem.into_response();
+    res.headers_mut().extend(parts.headers);
+    *res.extensions_mut() = parts.extensions;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Json, Router, extract::query::Query, get, test::TestClient};
+
+    #[test]
+    fn serializes_the_standard_and_extension_members() {
+        let problem = Problem::new(StatusCode::FORBIDDEN)
+            .with_type("https://example.com/probs/out-of-credit")
+            .detail("Your current balance is 30, but that costs 50.")
+            .instance("/account/12345/msgs/abc")
+            .extension("balance", 30)
+            .extension("status", 500);
+
+        assert_eq!(
+            problem.to_json(),
+            json!({
+                "type": "https://example.com/probs/out-of-credit",
+                "title": "Forbidden",
+                "status": 403,
+                "detail": "Your current balance is 30, but that costs 50.",
+                "instance": "/account/12345/msgs/abc",
+                "balance": 30,
+            })
+        );
+        assert_eq!(
+            Problem::new(StatusCode::NOT_FOUND).type_uri(),
+            "about:blank"
+        );
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/search",
+                get(|Query(query): Query<Vec<(String, u32)>>| async move { query.len().to_string() }),
+            )
+            .route(
+                "/users",
+                get(|Json(name): Json<String>| async move { name }),
+            )
+    }
+
+    #[monoio::test]
+    async fn rejections_are_plain_text_by_default() {
+        let res = TestClient::new(app())
+            .get("/search?page=first")
+            .send()
+            .await;
+        res.assert_status(StatusCode::BAD_REQUEST);
+        assert_ne!(
+            res.header(header::CONTENT_TYPE),
+            Some("application/problem+json")
+        );
+        assert!(res.text().starts_with("Failed to deserialize query string"));
+    }
+
+    #[monoio::test]
+    async fn problem_details_replace_rejections() {
+        let client = TestClient::new(app().problem_details(true));
+
+        for (method, uri, status) in [
+            (
+                http::Method::GET,
+                "/search?page=first",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                http::Method::GET,
+                "/users",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (http::Method::GET, "/nowhere", StatusCode::NOT_FOUND),
+            (
+                http::Method::DELETE,
+                "/users",
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+        ] {
+            let res = client.request(method, uri).send().await;
+            res.assert_status(status)
+                .assert_header(header::CONTENT_TYPE, "application/problem+json");
+            let body: Value = res.json();
+            assert_eq!(body["status"], status.as_u16(), "{uri}");
+            assert_eq!(body["type"], "about:blank", "{uri}");
+        }
+    }
+
+    #[monoio::test]
+    async fn problem_details_leave_handler_responses_alone() {
+        let app = Router::new()
+            .route("/", get(|| async { StatusCode::BAD_REQUEST }))
+            .problem_details(true);
+
+        let res = TestClient::new(app).get("/").send().await;
+        res.assert_status(StatusCode::BAD_REQUEST);
+        assert_ne!(
+            res.header(header::CONTENT_TYPE),
+            Some("application/problem+json")
+        );
+    }
+}
@@ -0,0 +1,468 @@
+//! Server-Sent Events (SSE) responses.
+//!
+//! ```no_run
+//! # use std::convert::Infallible;
+//! # use futures_util::{Stream, StreamExt};
+//! # use monet::response::sse::{Event, KeepAlive, Sse};
+//! async fn ticks() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+//!     let stream = futures_util::stream::iter(0..10)
+//!         .map(|n| Ok(Event::default().event("tick").data(n.to_string())));
+//!     Sse::new(stream).keep_alive(KeepAlive::default())
+//! }
+//! ```
+
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_core::{Stream, TryStream};
+use http::{HeaderValue, header};
+use monoio::time::{Instant, Interval, interval_at};
+use pin_project_lite::pin_project;
+
+use crate::{Body, BoxError, Frame, HttpBody, HttpResponse, IntoResponse};
+
+/// An SSE response wrapping a stream of [`Event`]s.
+///
+/// The response ends when the stream does. When the client goes away, hyper drops
+/// the response body and with it the stream, so producers notice the disconnect
+/// through their own drop handling.
+#[must_use]
+pub struct Sse<S> {
+    stream: S,
+    keep_alive: Option<KeepAlive>,
+}
+
+impl<S> Sse<S> {
+    pub fn new(stream: S) -> Self
+    where
+        S: TryStream<Ok = Event> + 'static,
+        S::Error: Into<BoxError>,
+    {
+        Sse {
+            stream,
+            keep_alive: None,
+        }
+    }
+
+    /// Send a comment to the client whenever no event went out for a while, so
+    /// proxies don't consider the connection idle.
+    pub fn keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+}
+
+impl<S> fmt::Debug for Sse<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sse")
+            .field("stream", &std::any::type_name::<S>())
+            .field("keep_alive", &self.keep_alive)
+            .finish()
+    }
+}
+
+impl<S, E> IntoResponse for Sse<S>
+where
+    S: Stream<Item = Result<Event, E>> + 'static,
+    E: Into<BoxError>,
+{
+    fn into_response(self) -> HttpResponse {
+        let keep_alive = self.keep_alive.map(|keep_alive| KeepAliveTimer {
+            interval: interval_at(Instant::now() + keep_alive.period, keep_alive.period),
+            comment: keep_alive.event.finalize(),
+            event_sent: false,
+        });
+
+        let mut res = Body::new(SseBody {
+            stream: self.stream,
+            keep_alive,
+        })
+        .into_response();
+
+        let headers = res.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        res
+    }
+}
+
+pin_project! {
+    struct SseBody<S> {
+        #[pin]
+        stream: S,
+        keep_alive: Option<KeepAliveTimer>,
+    }
+}
+
+struct KeepAliveTimer {
+    interval: Interval,
+    comment: Bytes,
+    event_sent: bool,
+}
+
+impl<S, E> HttpBody for SseBody<S>
+where
+    S: Stream<Item = Result<Event, E>>,
+    E: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = E;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+
+        match this.stream.poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                if let Some(keep_alive) = this.keep_alive {
+                    keep_alive.event_sent = true;
+                }
+                Poll::Ready(Some(Ok(Frame::data(event.finalize()))))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => {
+                let Some(keep_alive) = this.keep_alive else {
+                    return Poll::Pending;
+                };
+                loop {
+                    ready!(keep_alive.interval.poll_tick(cx));
+                    // Only ping connections that were quiet for a whole period.
+                    if !std::mem::take(&mut keep_alive.event_sent) {
+                        return Poll::Ready(Some(Ok(Frame::data(keep_alive.comment.clone()))));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Configures the keep-alive comments of an [`Sse`] response.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct KeepAlive {
+    event: Event,
+    period: Duration,
+}
+
+impl KeepAlive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long the stream may stay quiet before a keep-alive is sent. Defaults to
+    /// 15 seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn interval(mut self, period: Duration) -> Self {
+        assert!(!period.is_zero(), "keep-alive interval must be non-zero");
+        self.period = period;
+        self
+    }
+
+    /// The text of the keep-alive comment. Defaults to an empty comment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `text` contains a newline or carriage return.
+    pub fn text(mut self, text: impl AsRef<str>) -> Self {
+        self.event = Event::default().comment(text);
+        self
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            event: Event::default().comment(""),
+            period: Duration::from_secs(15),
+        }
+    }
+}
+
+/// A single server-sent event.
+///
+/// Every field is optional, but an event should carry at least one of them.
+#[derive(Debug, Default, Clone)]
+#[must_use]
+pub struct Event {
+    buffer: BytesMut,
+    has_data: bool,
+    has_event: bool,
+    has_id: bool,
+    has_retry: bool,
+}
+
+impl Event {
+    /// Set the `data` field. Multi-line data is sent as one `data:` line per line.
+    ///
+    /// # Panics
+    ///
+    /// Panics if data was already set.
+    pub fn data(mut self, data: impl AsRef<str>) -> Self {
+        assert!(!self.has_data, "called `Event::data` multiple times");
+        self.has_data = true;
+        for line in data
+            .as_ref()
+            .split("\r\n")
+            .flat_map(|line| line.split(['\n', '\r']))
+        {
+            self.field("data", line.as_bytes());
+        }
+        self
+    }
+
+    /// Set the `data` field to `data` serialized as JSON.
+    ///
+    /// # Panics
+    ///
+    /// Panics if data was already set.
+    pub fn json_data<T>(self, data: T) -> Result<Self, serde_json::Error>
+    where
+        T: serde::Serialize,
+    {
+        let json = serde_json::to_string(&data)?;
+        Ok(self.data(json))
+    }
+
+    /// Set the `event` field, the event type the client dispatches on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event type was already set, or contains a newline or carriage
+    /// return.
+    pub fn event(mut self, event: impl AsRef<str>) -> Self {
+        assert!(!self.has_event, "called `Event::event` multiple times");
+        self.has_event = true;
+        self.single_line_field("event", event.as_ref());
+        self
+    }
+
+    /// Set the `id` field, which the client sends back as `Last-Event-ID` when it
+    /// reconnects.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the id was already set, or contains a newline, carriage return or
+    /// null character.
+    pub fn id(mut self, id: impl AsRef<str>) -> Self {
+        assert!(!self.has_id, "called `Event::id` multiple times");
+        assert!(!id.as_ref().contains('\0'), "SSE id cannot contain nulls");
+        self.has_id = true;
+        self.single_line_field("id", id.as_ref());
+        self
+    }
+
+    /// Set the `retry` field, how long the client waits before reconnecting.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the retry timeout was already set.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        assert!(!self.has_retry, "called `Event::retry` multiple times");
+        self.has_retry = true;
+        self.field("retry", retry.as_millis().to_string().as_bytes());
+        self
+    }
+
+    /// Add a comment line. Clients ignore comments, they are mostly useful to keep
+    /// connections alive. May be called multiple times.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `comment` contains a newline or carriage return.
+    pub fn comment(mut self, comment: impl AsRef<str>) -> Self {
+        self.single_line_field("", comment.as_ref());
+        self
+    }
+
+    fn single_line_field(&mut self, name: &str, value: &str) {
+        assert!(
+            !value.contains(['\n', '\r']),
+            "SSE field `{name}` cannot contain newlines or carriage returns"
+        );
+        self.field(name, value.as_bytes());
+    }
+
+    fn field(&mut self, name: &str, value: &[u8]) {
+        self.buffer.put_slice(name.as_bytes());
+        self.buffer.put_u8(b':');
+        if !value.is_empty() {
+            self.buffer.put_u8(b' ');
+            self.buffer.put_slice(value);
+        }
+        self.buffer.put_u8(b'\n');
+    }
+
+    fn finalize(mut self) -> Bytes {
+        self.buffer.put_u8(b'\n');
+        self.buffer.freeze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use futures::channel::{mpsc, oneshot};
+    use futures_util::{StreamExt, stream};
+    use http::StatusCode;
+    use http_body_util::BodyExt;
+    use monoio::io::{AsyncReadRent, AsyncWriteRentExt};
+
+    use super::*;
+    use crate::{Router, get, test::TestClient};
+
+    async fn next_chunk(body: &mut Body) -> Bytes {
+        body.frame().await.unwrap().unwrap().into_data().unwrap()
+    }
+
+    #[test]
+    fn encodes_every_field() {
+        let event = Event::default()
+            .event("tick")
+            .id("7")
+            .retry(Duration::from_secs(3))
+            .comment("note")
+            .data("one\ntwo\r\nthree\rfour");
+
+        assert_eq!(
+            event.finalize(),
+            "event: tick\nid: 7\nretry: 3000\n: note\n\
+             data: one\ndata: two\ndata: three\ndata: four\n\n"
+        );
+    }
+
+    #[test]
+    fn encodes_empty_lines_without_a_space() {
+        let event = Event::default().comment("").data("a\n\nb");
+        assert_eq!(event.finalize(), ":\ndata: a\ndata:\ndata: b\n\n");
+    }
+
+    #[test]
+    fn encodes_json_data() {
+        let event = Event::default()
+            .json_data(serde_json::json!({ "n": 1 }))
+            .unwrap();
+        assert_eq!(event.finalize(), "data: {\"n\":1}\n\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "SSE field `event` cannot contain newlines")]
+    fn rejects_multi_line_event_types() {
+        let _ = Event::default().event("a\nb");
+    }
+
+    #[test]
+    #[should_panic(expected = "called `Event::data` multiple times")]
+    fn rejects_data_set_twice() {
+        let _ = Event::default().data("a").data("b");
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn streams_events_until_the_stream_ends() {
+        let app = Router::new().route(
+            "/",
+            get(|| async {
+                let events = stream::iter(["a", "b"])
+                    .map(|data| Ok::<_, Infallible>(Event::default().data(data)));
+                Sse::new(events)
+            }),
+        );
+
+        TestClient::new(app)
+            .get("/")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_header("content-type", "text/event-stream")
+            .assert_header("cache-control", "no-cache")
+            .assert_text("data: a\n\ndata: b\n\n");
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn keeps_quiet_streams_alive() {
+        let period = Duration::from_millis(30);
+        let (events, rx) = mpsc::unbounded::<Result<Event, Infallible>>();
+        let res = Sse::new(rx)
+            .keep_alive(KeepAlive::new().interval(period).text("ping"))
+            .into_response();
+        let mut body = res.into_body();
+
+        let start = Instant::now();
+        assert_eq!(next_chunk(&mut body).await, ": ping\n\n");
+        assert!(start.elapsed() >= period);
+
+        // The tick right after an event is skipped, the next keep-alive only
+        // follows a whole quiet period later.
+        events
+            .unbounded_send(Ok(Event::default().data("hi")))
+            .unwrap();
+        assert_eq!(next_chunk(&mut body).await, "data: hi\n\n");
+        let sent = Instant::now();
+        assert_eq!(next_chunk(&mut body).await, ": ping\n\n");
+        assert!(sent.elapsed() >= period * 3 / 2);
+
+        drop(events);
+        assert!(body.frame().await.is_none());
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn drops_the_stream_when_the_client_disconnects() {
+        /// Reports when the event stream is dropped.
+        struct DropSignal(Option<oneshot::Sender<()>>);
+
+        impl Drop for DropSignal {
+            fn drop(&mut self) {
+                let _ = self.0.take().unwrap().send(());
+            }
+        }
+
+        let (dropped, stream_dropped) = oneshot::channel();
+        let signal = std::rc::Rc::new(std::cell::RefCell::new(Some(DropSignal(Some(dropped)))));
+        let app = Router::new().route(
+            "/",
+            get(move || {
+                let signal = signal.borrow_mut().take().unwrap();
+                async move {
+                    let events = stream::pending::<Result<Event, Infallible>>().map(move |event| {
+                        let _signal = &signal;
+                        event
+                    });
+                    Sse::new(events)
+                        .keep_alive(KeepAlive::new().interval(Duration::from_millis(10)))
+                }
+            }),
+        );
+        let client = TestClient::loopback(app);
+
+        let mut stream = monoio::net::TcpStream::connect(client.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (written, _) = stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n".to_vec())
+            .await;
+        written.unwrap();
+        let (read, head) = stream.read(Vec::with_capacity(1024)).await;
+        read.unwrap();
+        assert!(head.starts_with(b"HTTP/1.1 200 OK"));
+
+        drop(stream);
+        monoio::time::timeout(Duration::from_secs(1), stream_dropped)
+            .await
+            .expect("the event stream outlived the connection")
+            .unwrap();
+    }
+}
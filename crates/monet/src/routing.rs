@@ -8,3 +8,5 @@ pub mod route_tower_impl;
 pub mod method_router;
 
 pub mod method_filter;
+
+pub(crate) mod strip_prefix;
@@ -1,7 +1,8 @@
 use crate::handler::Handler;
 use crate::prelude::*;
+use crate::response::Problem;
 use crate::routing::method_filter::MethodFilter;
-use crate::routing::route::{BoxedIntoRoute, ErasedIntoRoute, Route};
+use crate::routing::route::{BoxedIntoRoute, Route};
 use crate::routing::route_tower_impl::RouteFuture;
 use crate::routing::router::Fallback;
 use http::{Method, StatusCode};
@@ -17,6 +18,13 @@ where
     MethodRouter::new().get(handler)
 }
 
+/// Route requests matching `filter` to `svc`. Only [`MethodFilter::GET`] is
+/// supported so far.
+///
+/// # Panics
+///
+/// Panics if `filter` matches any other method.
+#[track_caller]
 pub fn get_service<T, S>(filter: MethodFilter, svc: T) -> MethodRouter<S, T::Error>
 where
     T: TowerService<HttpRequest> + Clone + 'static,
@@ -24,6 +32,10 @@ where
     T::Future: 'static,
     S: Clone,
 {
+    assert!(
+        MethodFilter::GET.contains(filter),
+        "`get_service` only supports `MethodFilter::GET`"
+    );
     MethodRouter::new().get_service(svc)
 }
 
@@ -38,6 +50,8 @@ pub struct MethodRouter<S = (), E = Infallible> {
     trace: MethodEndpoint<S, E>,
     connect: MethodEndpoint<S, E>,
     fallback: Fallback<S, E>,
+    #[cfg(feature = "openapi")]
+    operations: Vec<(Method, crate::openapi::Operation)>,
 }
 
 impl<S, E> fmt::Debug for MethodRouter<S, E> {
@@ -63,7 +77,7 @@ where
 {
     pub fn new() -> Self {
         let fallback = Route::new(service_fn(|_: HttpRequest| async {
-            Ok(StatusCode::METHOD_NOT_ALLOWED)
+            Ok(Problem::new(StatusCode::METHOD_NOT_ALLOWED).into_rejection())
         }));
         Self {
             get: MethodEndpoint::None,
@@ -76,6 +90,8 @@ where
             trace: MethodEndpoint::None,
             connect: MethodEndpoint::None,
             fallback: Fallback::Default(fallback),
+            #[cfg(feature = "openapi")]
+            operations: Vec::new(),
         }
     }
 
@@ -108,6 +124,8 @@ where
             trace: self.trace.with_state(&state),
             connect: self.connect.with_state(&state),
             fallback: self.fallback.with_state(state),
+            #[cfg(feature = "openapi")]
+            operations: self.operations,
         }
     }
 
@@ -170,6 +188,8 @@ where
             trace: self.trace.map(layer_fn.clone()),
             connect: self.connect.map(layer_fn.clone()),
             fallback: self.fallback.map(layer_fn),
+            #[cfg(feature = "openapi")]
+            operations: self.operations,
         }
     }
 
@@ -189,14 +209,14 @@ where
                 (MethodEndpoint::None, MethodEndpoint::None) => Ok(MethodEndpoint::None),
                 (pick, MethodEndpoint::None) => Ok(pick),
                 (MethodEndpoint::None, pick) => Ok(pick),
-                _ => {
-                    let error_message = if path.is_some() {
+                _ => Err(match path {
+                    Some(path) => format!(
                         "Overlapping method route. Handler for `{name} {path}` already exists"
-                    } else {
+                    ),
+                    None => format!(
                         "Overlapping method route. Cannot merge two method routes that both define `{name}`"
-                    };
-                    Err(format!("error_message").into())
-                }
+                    ),
+                }),
             }
         }
 
@@ -215,8 +235,49 @@ where
             .merge(other.fallback)
             .ok_or("Cannot merge two `MethodRouter`s that both have a fallback")?;
 
+        #[cfg(feature = "openapi")]
+        self.operations.extend(other.operations);
+
         Ok(self)
     }
+
+    /// Attach the documentation of the `method` endpoint.
+    #[cfg(feature = "openapi")]
+    pub(crate) fn document(mut self, method: Method, operation: crate::openapi::Operation) -> Self {
+        self.operations
+            .retain(|(documented, _)| *documented != method);
+        self.operations.push((method, operation));
+        self
+    }
+
+    /// The documentation of every method with an endpoint, empty for those
+    /// routed without it.
+    #[cfg(feature = "openapi")]
+    pub(crate) fn operations(&self) -> Vec<(Method, crate::openapi::Operation)> {
+        let endpoints = [
+            (Method::GET, &self.get),
+            (Method::HEAD, &self.head),
+            (Method::POST, &self.post),
+            (Method::PUT, &self.put),
+            (Method::PATCH, &self.patch),
+            (Method::DELETE, &self.delete),
+            (Method::OPTIONS, &self.options),
+            (Method::TRACE, &self.trace),
+        ];
+        endpoints
+            .into_iter()
+            .filter(|(_, endpoint)| endpoint.is_some())
+            .map(|(method, _)| {
+                let operation = self
+                    .operations
+                    .iter()
+                    .find(|(documented, _)| *documented == method)
+                    .map(|(_, operation)| operation.clone())
+                    .unwrap_or_default();
+                (method, operation)
+            })
+            .collect()
+    }
 }
 
 impl<S> MethodRouter<S, Infallible>
@@ -240,6 +301,15 @@ where
     }
 }
 
+impl<S, E> Default for MethodRouter<S, E>
+where
+    S: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<S, E> Clone for MethodRouter<S, E> {
     fn clone(&self) -> Self {
         Self {
@@ -253,6 +323,8 @@ impl<S, E> Clone for MethodRouter<S, E> {
             trace: self.trace.clone(),
             connect: self.connect.clone(),
             fallback: self.fallback.clone(),
+            #[cfg(feature = "openapi")]
+            operations: self.operations.clone(),
         }
     }
 }
@@ -313,3 +385,16 @@ impl<S, E> Clone for MethodEndpoint<S, E> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Router, get};
+
+    #[test]
+    #[should_panic(expected = "Handler for `GET /users` already exists")]
+    fn names_overlapping_routes() {
+        let _: Router = Router::new()
+            .route("/users", get(|| async { "a" }))
+            .route("/users", get(|| async { "b" }));
+    }
+}
@@ -39,6 +39,29 @@ impl<E> Route<E> {
     }
 }
 
+impl<B, E> TowerService<HttpRequest<B>> for Route<E>
+where
+    B: HttpBody<Data = bytes::Bytes> + 'static,
+    B::Error: Into<BoxError>,
+{
+    type Response = HttpResponse;
+    type Error = E;
+    type Future = RouteFuture<E>;
+
+    #[inline]
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn call(&mut self, req: HttpRequest<B>) -> Self::Future {
+        self.clone().call(req.map(Body::new))
+    }
+}
+
 impl<E> Clone for Route<E> {
     #[track_caller]
     fn clone(&self) -> Self {
@@ -133,7 +156,7 @@ where
         X: 'static,
     {
         Self(Box::new(ErasedHandler {
-            handler: handler,
+            handler,
             into_route_fn: |handler, state| Route::new(Handler::with_state(handler, state)),
         }))
     }
@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use crate::response::problem::render_problem;
 use http::Method;
 use pin_project_lite::pin_project;
 use std::{
@@ -7,18 +8,12 @@ use std::{
 };
 use tower::util::{Oneshot, ServiceExt};
 
-/// A local boxed [`Service`] trait object with `Clone`. Same with UnsyncBoxService
-/// Ref: https://github.com/tower-rs/tower/blob/tower-0.5.2/tower/src/util/boxed/unsync.rs#L12
+type LocalBoxFuture<U, E> = Pin<Box<dyn Future<Output = Result<U, E>>>>;
 
+/// A local boxed [`Service`](tower::Service) trait object with `Clone`. Same with UnsyncBoxService
+/// Ref: <https://github.com/tower-rs/tower/blob/tower-0.5.2/tower/src/util/boxed/unsync.rs#L12>
 pub struct LocalBoxCloneService<T, U, E>(
-    Box<
-        dyn ClonableService<
-                T,
-                Response = U,
-                Error = E,
-                Future = Pin<Box<dyn Future<Output = Result<U, E>>>>,
-            >,
-    >,
+    Box<dyn ClonableService<T, Response = U, Error = E, Future = LocalBoxFuture<U, E>>>,
 );
 
 impl<T, U, E> LocalBoxCloneService<T, U, E> {
@@ -44,7 +39,7 @@ impl<T, U, E> TowerService<T> for LocalBoxCloneService<T, U, E> {
 
     type Error = E;
 
-    type Future = Pin<Box<dyn Future<Output = Result<U, E>>>>;
+    type Future = LocalBoxFuture<U, E>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.0.poll_ready(cx)
@@ -117,6 +112,7 @@ pin_project! {
         #[pin]
         inner: Oneshot<LocalBoxCloneService<HttpRequest,HttpResponse,E> , HttpRequest>,
         method: Method,
+        problem_details: bool,
     }
 }
 
@@ -125,7 +121,18 @@ impl<E> RouteFuture<E> {
         method: Method,
         inner: Oneshot<LocalBoxCloneService<HttpRequest, HttpResponse, E>, HttpRequest>,
     ) -> Self {
-        Self { inner, method }
+        Self {
+            inner,
+            method,
+            problem_details: false,
+        }
+    }
+
+    /// Render responses of built-in rejections as problem details, see
+    /// [`Router::problem_details`](crate::Router::problem_details).
+    pub(crate) fn problem_details(mut self, enabled: bool) -> Self {
+        self.problem_details = enabled;
+        self
     }
 }
 
@@ -137,6 +144,9 @@ impl<E> Future for RouteFuture<E> {
 
         let resp = std::task::ready!(this.inner.poll(cx))?;
 
+        if *this.problem_details {
+            return Poll::Ready(Ok(render_problem(resp)));
+        }
         Poll::Ready(Ok(resp))
     }
 }
@@ -1,9 +1,13 @@
+use crate::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use crate::extract::matched_path::MatchedPath;
+use crate::extract::path::insert_url_params;
 use crate::prelude::*;
 use crate::routing::method_router::MethodRouter;
 use crate::routing::route_tower_impl::RouteFuture;
+use crate::routing::strip_prefix::StripPrefix;
 use crate::{handler::Handler, routing::route::BoxedIntoRoute};
 use matchit::MatchError;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::{collections::HashMap, convert::Infallible};
 
 #[must_use]
@@ -12,7 +16,8 @@ pub struct Router<S = ()> {
     pub routes: Vec<Endpoint<S>>,
     pub node: Node,
     pub default_fallback: bool,
-    pub catch_all_fallback: Fallback<S>,
+    pub(crate) catch_all_fallback: Fallback<S>,
+    pub problem_details: bool,
 }
 
 impl<S> fmt::Debug for Router<S> {
@@ -22,6 +27,7 @@ impl<S> fmt::Debug for Router<S> {
             .field("node", &self.node)
             .field("default_fallback", &self.default_fallback)
             .field("catch_all_fallback", &self.catch_all_fallback)
+            .field("problem_details", &self.problem_details)
             .finish()
     }
 }
@@ -38,6 +44,8 @@ where
 #[derive(Clone, Copy, Debug)]
 pub(super) struct NotFound;
 
+pub(crate) const NEST_TAIL_PARAM: &str = "__private__monet_nest_tail_param";
+
 impl<S> Router<S>
 where
     S: Clone + 'static,
@@ -48,15 +56,12 @@ where
             node: Default::default(),
             default_fallback: true,
             catch_all_fallback: Fallback::Default(Route::new(NotFound)),
+            problem_details: false,
         }
     }
 
-    pub fn chain(mut self, path: &str, method_router: MethodRouter<S>) -> Self {
-        todo!()
-    }
-
     pub fn route(mut self, path: &str, method_router: MethodRouter<S>) -> Self {
-        match (self.process_route(path, method_router)) {
+        match self.process_route(path, method_router) {
             Ok(x) => x,
             Err(err) => {
                 panic!("{err}")
@@ -97,6 +102,48 @@ where
             .map_err(|err| format!("Invalid route {path:?}: {err}"))
     }
 
+    /// Nest a service at `path`. Requests to `path` and everything below it are sent
+    /// to `service` with the prefix removed from the URI, so a service mounted at
+    /// `/assets` sees `/assets/app.css` as `/app.css`.
+    ///
+    /// ```no_run
+    /// # use monet::{Router, services::ServeDir};
+    /// let app: Router = Router::new().nest_service("/assets", ServeDir::new("public"));
+    /// ```
+    #[track_caller]
+    pub fn nest_service<T>(mut self, path: &str, service: T) -> Self
+    where
+        T: TowerService<HttpRequest, Error = Infallible> + Clone + 'static,
+        T::Response: IntoResponse + 'static,
+        T::Future: 'static,
+    {
+        if !path.starts_with('/') {
+            panic!("Nesting paths must start with a `/`");
+        }
+        if path.contains(['{', '}', '*']) {
+            panic!("Invalid nesting path {path:?}: nested services cannot capture parameters");
+        }
+
+        let prefix = path.trim_end_matches('/');
+        let route = Route::new(StripPrefix::new(service, prefix));
+
+        let paths = if prefix.is_empty() {
+            vec!["/".to_owned(), format!("/{{*{NEST_TAIL_PARAM}}}")]
+        } else {
+            vec![
+                prefix.to_owned(),
+                format!("{prefix}/"),
+                format!("{prefix}/{{*{NEST_TAIL_PARAM}}}"),
+            ]
+        };
+        for path in paths {
+            if let Err(err) = self.new_route(&path, Endpoint::Route(route.clone())) {
+                panic!("{err}");
+            }
+        }
+        self
+    }
+
     pub fn merge<R>(self, other: R) -> Self
     where
         R: Into<Self>,
@@ -137,8 +184,9 @@ where
         Router {
             routes: this.routes,
             node: this.node,
-            default_fallback: default_fallback,
-            catch_all_fallback: catch_all_fallback,
+            default_fallback,
+            catch_all_fallback,
+            problem_details: this.problem_details || other.problem_details,
         }
     }
 
@@ -186,7 +234,20 @@ where
         self
     }
 
-    pub fn with_state<S2>(mut self, state: S) -> Router<S2> {
+    /// Respond to the rejections built into monet, such as those of the
+    /// [`Query`](crate::extract::query::Query), [`Json`](crate::Json) and
+    /// [`Path`](crate::extract::path::Path) extractors, bodies over the
+    /// [`DefaultBodyLimit`](crate::middleware::DefaultBodyLimit) and the `404` and
+    /// `405` responses, with [`Problem`](crate::Problem) details served as
+    /// `application/problem+json` instead of plain text.
+    ///
+    /// Applies to every route and the fallback, whether added before or after.
+    pub fn problem_details(mut self, enabled: bool) -> Self {
+        self.problem_details = enabled;
+        self
+    }
+
+    pub fn with_state<S2>(self, state: S) -> Router<S2> {
         let routes = self
             .routes
             .into_iter()
@@ -203,14 +264,13 @@ where
             node: self.node,
             default_fallback: self.default_fallback,
             catch_all_fallback: self.catch_all_fallback.with_state(state),
+            problem_details: self.problem_details,
         }
     }
 
     pub(crate) fn call_with_state(&self, req: HttpRequest, state: S) -> RouteFuture<Infallible> {
         let (mut parts, body) = req.into_parts();
 
-        println!("{:?}", &self);
-
         match self.node.at(parts.uri.path()) {
             Ok(matched) => {
                 let route_id = matched.value;
@@ -219,6 +279,22 @@ where
                     "It is granted a valid route for id. Please file an issue if it is not",
                 );
 
+                let path = self
+                    .node
+                    .route_id_to_path
+                    .get(route_id)
+                    .expect("no path for route id. Please file an issue");
+                let matched_path = match endpoint {
+                    Endpoint::MethodRouter(_) => path.clone(),
+                    Endpoint::Route(_) => nested_at(path),
+                };
+                set_matched_path(matched_path, &mut parts.extensions);
+                insert_url_params(
+                    &mut parts.extensions,
+                    matched.params.iter(),
+                    NEST_TAIL_PARAM,
+                );
+
                 let req = HttpRequest::from_parts(parts, body);
 
                 match endpoint {
@@ -227,15 +303,54 @@ where
                     }
                     Endpoint::Route(route) => route.clone().call_owned(req),
                 }
+                .problem_details(self.problem_details)
             }
             Err(MatchError::NotFound) => {
                 let req = HttpRequest::from_parts(parts, body);
-                self.catch_all_fallback.clone().call_with_state(req, state)
+                self.catch_all_fallback
+                    .clone()
+                    .call_with_state(req, state)
+                    .problem_details(self.problem_details)
             }
         }
     }
 }
 
+/// The path a service was nested at, given one of the paths
+/// [`Router::nest_service`] registered for it.
+fn nested_at(path: &str) -> Arc<str> {
+    let prefix = path
+        .strip_suffix(&format!("/{{*{NEST_TAIL_PARAM}}}"))
+        .unwrap_or(path)
+        .trim_end_matches('/');
+    if prefix.is_empty() {
+        "/".into()
+    } else {
+        prefix.into()
+    }
+}
+
+/// Record the matched path, joined to the one of the router this router is nested
+/// in, if any.
+fn set_matched_path(path: Arc<str>, extensions: &mut http::Extensions) {
+    let path = match extensions.get::<MatchedPath>() {
+        Some(outer) if path.as_ref() == "/" => outer.0.clone(),
+        Some(outer) => format!("{}{path}", outer.as_str().trim_end_matches('/')).into(),
+        None => path,
+    };
+    extensions.insert(MatchedPath(path));
+}
+
+impl Router {
+    /// Convert the router into a make-service that records [`ConnectInfo`] of type `C`
+    /// for every connection, e.g. the client's `SocketAddr`.
+    ///
+    /// [`ConnectInfo`]: crate::extract::connect_info::ConnectInfo
+    pub fn into_make_service_with_connect_info<C>(self) -> IntoMakeServiceWithConnectInfo<Self, C> {
+        IntoMakeServiceWithConnectInfo::new(self)
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 pub enum Endpoint<S> {
     MethodRouter(MethodRouter<S>),
@@ -287,7 +402,7 @@ pub struct RouteId(pub usize);
 #[derive(Clone, Default)]
 pub struct Node {
     pub inner: matchit::Router<RouteId>,
-    pub route_id_to_path: HashMap<RouteId, String>,
+    pub route_id_to_path: HashMap<RouteId, Arc<str>>,
     pub path_to_route_id: HashMap<String, RouteId>,
 }
 
@@ -309,7 +424,7 @@ impl Node {
 
         self.inner.insert(&path, val)?;
 
-        self.route_id_to_path.insert(val, path.clone());
+        self.route_id_to_path.insert(val, path.as_str().into());
         self.path_to_route_id.insert(path, val);
 
         Ok(())
@@ -1,5 +1,5 @@
 use crate::{
-    Body, BoxError, HttpBody, HttpRequest, HttpResponse, IntoResponse, TowerService,
+    Body, BoxError, HttpBody, HttpRequest, HttpResponse, Problem, TowerService,
     routing::{
         route_tower_impl::RouteFuture,
         router::{NotFound, Router},
@@ -22,11 +22,11 @@ where
 
     type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
 
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: IncomingStream<'_, L>) -> Self::Future {
+    fn call(&mut self, _req: IncomingStream<'_, L>) -> Self::Future {
         std::future::ready(Ok(self.clone().with_state(())))
     }
 }
@@ -42,7 +42,7 @@ where
 
     type Future = RouteFuture<Infallible>;
 
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
@@ -66,6 +66,8 @@ where
     }
 
     fn call(&mut self, _req: HttpRequest<B>) -> Self::Future {
-        ready(Ok(http::StatusCode::NOT_FOUND.into_response()))
+        ready(Ok(
+            Problem::new(http::StatusCode::NOT_FOUND).into_rejection()
+        ))
     }
 }
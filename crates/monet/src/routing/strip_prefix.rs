@@ -0,0 +1,73 @@
+use std::{
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use http::{Uri, uri::PathAndQuery};
+
+use crate::prelude::*;
+
+/// Removes the path a service is nested at from the request URI before calling it,
+/// see [`Router::nest_service`](crate::Router::nest_service).
+#[derive(Clone, Debug)]
+pub(crate) struct StripPrefix<S> {
+    inner: S,
+    prefix: Rc<str>,
+}
+
+/// The request URI before any prefix was stripped, recorded in the extensions by
+/// the outermost [`StripPrefix`].
+#[derive(Clone, Debug)]
+pub(crate) struct OriginalUri(pub(crate) Uri);
+
+impl<S> StripPrefix<S> {
+    pub(crate) fn new(inner: S, prefix: &str) -> Self {
+        Self {
+            inner,
+            prefix: prefix.trim_end_matches('/').into(),
+        }
+    }
+}
+
+impl<S, B> TowerService<HttpRequest<B>> for StripPrefix<S>
+where
+    S: TowerService<HttpRequest<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: HttpRequest<B>) -> Self::Future {
+        if req.extensions().get::<OriginalUri>().is_none() {
+            let original = OriginalUri(req.uri().clone());
+            req.extensions_mut().insert(original);
+        }
+        if let Some(uri) = strip_prefix(req.uri(), &self.prefix) {
+            *req.uri_mut() = uri;
+        }
+        self.inner.call(req)
+    }
+}
+
+fn strip_prefix(uri: &Uri, prefix: &str) -> Option<Uri> {
+    let path = uri.path().strip_prefix(prefix)?;
+    // Only strip whole segments, `/assets` must not turn `/assetsfoo` into `foo`.
+    if !path.is_empty() && !path.starts_with('/') {
+        return None;
+    }
+    let path = if path.is_empty() { "/" } else { path };
+
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_owned(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
@@ -1,18 +1,42 @@
+use futures_core::future::LocalBoxFuture;
+use futures_util::future::{self, Either};
 use hyper::body::Incoming;
 use hyper_util::service::TowerToHyperService;
+use std::cell::Cell;
 use std::convert::Infallible;
 use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::pin::pin;
+use std::rc::Rc;
+use std::time::Duration;
 use tower::ServiceExt;
+use tracing::Instrument;
 
-use hyper::server::conn::http1;
-use monoio_compat::hyper::MonoioIo;
+use hyper_util::server::conn::auto;
+use monoio_compat::hyper::{MonoioExecutor, MonoioIo, MonoioTimer};
 use monoio_compat::{AsyncRead, AsyncWrite, TcpStreamCompat, UnixStreamCompat};
 
 use crate::Body;
 use crate::HttpBody;
+use crate::extract::task_scope::TaskScopeService;
+use crate::middleware::metrics::MetricsRegistry;
+use crate::util;
 use crate::{BoxError, HttpRequest, HttpResponse, TowerService};
 
+use self::limit::{ConnectionLimit, LoadShed};
+use self::upgrade::UpgradeIo;
+
+mod limit;
+mod unix;
+mod upgrade;
+
+pub use self::unix::{UnixPeer, UnixSocketListener};
+
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+// Connections are accepted and served on a single core, so `accept` doesn't need
+// to be `Send`.
+#[allow(async_fn_in_trait)]
 pub trait Listener: 'static {
     type Io: AsyncRead + AsyncWrite + Unpin;
 
@@ -32,7 +56,7 @@ impl Listener for monoio::net::TcpListener {
         loop {
             match Self::accept(self).await {
                 Ok((stream, addr)) => return (TcpStreamCompat::new(stream), addr),
-                Err(e) => todo!(), // handle error
+                Err(err) => handle_accept_error(err).await,
             }
         }
     }
@@ -51,7 +75,7 @@ impl Listener for monoio::net::UnixListener {
         loop {
             match Self::accept(self).await {
                 Ok((stream, addr)) => return (UnixStreamCompat::new(stream), addr),
-                Err(e) => todo!(), // handle error
+                Err(err) => handle_accept_error(err).await,
             }
         }
     }
@@ -61,6 +85,40 @@ impl Listener for monoio::net::UnixListener {
     }
 }
 
+/// How long the accept loop pauses after an error that is not about a single
+/// connection, such as running out of file descriptors.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Deal with an error from `accept`, without giving up on the listener.
+///
+/// Errors about a connection that was aborted before it could be accepted only
+/// concern that connection, the next one can be accepted right away. Anything
+/// else, like `EMFILE` or `ENFILE`, would most likely fail again immediately, so
+/// the core waits a moment to let connections close before it retries.
+pub(crate) async fn handle_accept_error(err: std::io::Error) {
+    if is_connection_error(&err) {
+        tracing::debug!(error = %err, "accepting a connection failed");
+        return;
+    }
+
+    tracing::error!(
+        error = %err,
+        backoff = ?ACCEPT_ERROR_BACKOFF,
+        core = util::core_id(),
+        "accept failed, pausing the listener",
+    );
+    monoio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+}
+
+fn is_connection_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionReset
+    )
+}
+
 #[derive(Debug)]
 pub struct IncomingStream<'a, L>
 where
@@ -70,6 +128,21 @@ where
     remote_addr: L::Addr,
 }
 
+impl<L> IncomingStream<'_, L>
+where
+    L: Listener,
+{
+    /// The IO of the accepted connection.
+    pub fn io(&self) -> &L::Io {
+        self.io
+    }
+
+    /// The peer address of the accepted connection.
+    pub fn remote_addr(&self) -> &L::Addr {
+        &self.remote_addr
+    }
+}
+
 pub fn serve<L, M, S, B>(listener: L, make_service: M) -> Serve<L, M, S, B>
 where
     L: Listener,
@@ -81,16 +154,90 @@ where
     Serve {
         listener,
         make_service,
+        max_connections: None,
+        overload_threshold: None,
+        retry_after: DEFAULT_RETRY_AFTER,
+        metrics: None,
+        shutdown: None,
         _marker: PhantomData,
     }
 }
 
+/// Serve over a Unix domain socket at `path` that only the current user can connect
+/// to, see [`UnixSocketListener`] for how the socket file is managed and
+/// [`UnixSocketListener::bind_with_mode`] for other permissions. Use
+/// `Router::into_make_service_with_connect_info::<UCred>()` to give handlers the
+/// credentials of the connecting process.
+pub fn serve_unix<M, S, B>(
+    path: impl AsRef<std::path::Path>,
+    make_service: M,
+) -> io::Result<Serve<UnixSocketListener, M, S, B>>
+where
+    M: for<'a> TowerService<
+            IncomingStream<'a, UnixSocketListener>,
+            Response = S,
+            Error = Infallible,
+        >,
+    S: TowerService<HttpRequest, Response = HttpResponse<B>, Error = Infallible> + Clone + 'static,
+    B: HttpBody + 'static,
+    B::Error: Into<BoxError>,
+{
+    let listener = UnixSocketListener::bind(path)?;
+    Ok(serve(listener, make_service))
+}
+
 pub struct Serve<L, M, S, B> {
     listener: L,
     make_service: M,
+    max_connections: Option<usize>,
+    overload_threshold: Option<usize>,
+    retry_after: Duration,
+    metrics: Option<MetricsRegistry>,
+    shutdown: Option<LocalBoxFuture<'static, ()>>,
     _marker: PhantomData<fn(B) -> S>,
 }
 
+impl<L, M, S, B> Serve<L, M, S, B> {
+    /// Limit the number of connections open at once on this core. Once the limit
+    /// is reached the core stops accepting until a connection closes, leaving new
+    /// connections in the listen backlog for the kernel or other cores to handle.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Answer new requests with `503 Service Unavailable` while this core already
+    /// has `threshold` requests in flight.
+    pub fn overload_threshold(mut self, threshold: usize) -> Self {
+        self.overload_threshold = Some(threshold);
+        self
+    }
+
+    /// The `Retry-After` sent with overload responses. Defaults to 1 second.
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Count the connections of this core in `registry`.
+    pub fn metrics(mut self, registry: MetricsRegistry) -> Self {
+        self.metrics = Some(registry);
+        self
+    }
+
+    /// Stop accepting connections once `signal` completes. The listener is dropped
+    /// then, which for a [`UnixSocketListener`] removes the socket file, and the
+    /// serve future resolves. Connections that were already accepted are served
+    /// until they close.
+    pub fn with_graceful_shutdown<F>(mut self, signal: F) -> Self
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        self.shutdown = Some(Box::pin(signal));
+        self
+    }
+}
+
 impl<L, M, S, B> Serve<L, M, S, B>
 where
     L: Listener,
@@ -100,15 +247,40 @@ where
     B: HttpBody + 'static,
     B::Error: Into<BoxError>,
 {
-    async fn run(self) -> ! {
+    async fn run(self) {
         let Self {
             mut listener,
             mut make_service,
+            max_connections,
+            overload_threshold,
+            retry_after,
+            metrics,
+            shutdown,
             _marker,
         } = self;
 
+        let connections = ConnectionLimit::new(max_connections);
+        let in_flight = Rc::new(Cell::new(0));
+        let metrics = metrics.map(|registry| registry.core());
+        let mut shutdown = shutdown.unwrap_or_else(|| Box::pin(future::pending()));
+
         loop {
-            let (io, remote_addr) = listener.accept().await;
+            let accept = async {
+                let connection = connections.acquire().await;
+                let (io, remote_addr) = listener.accept().await;
+                (connection, io, remote_addr)
+            };
+            let (connection, io, remote_addr) =
+                match future::select(pin!(accept), shutdown.as_mut()).await {
+                    Either::Left((accepted, _)) => accepted,
+                    Either::Right(((), _)) => break,
+                };
+            let counted = metrics.as_ref().map(|core| core.connection_opened());
+            let span = tracing::debug_span!(
+                "connection",
+                remote_addr = ?remote_addr,
+                core = util::core_id(),
+            );
 
             let io = monoio_compat::hyper::MonoioIo::new(io);
 
@@ -126,18 +298,35 @@ where
                 .unwrap_or_else(|err| match err {})
                 .map_request(|req: HttpRequest<Incoming>| req.map(Body::new));
 
+            let tower_service = LoadShed::new(
+                TaskScopeService::new(tower_service),
+                in_flight.clone(),
+                overload_threshold,
+                retry_after,
+            );
+
             let hyper_service = TowerToHyperService::new(tower_service);
 
-            monoio::spawn_without_static(async move {
-                println!("Task started on thread {:?}", std::thread::current().id());
-                if let Err(err) = http1::Builder::new()
-                    .timer(monoio_compat::hyper::MonoioTimer)
-                    .serve_connection(io, hyper_service)
-                    .await
-                {
-                    println!("Error serving connection: {:?}", err);
+            monoio::spawn_without_static(
+                async move {
+                    tracing::debug!("connection accepted");
+                    let mut builder = auto::Builder::new(MonoioExecutor);
+                    builder.http1().timer(MonoioTimer);
+                    builder.http2().timer(MonoioTimer);
+
+                    let io = UpgradeIo::new(io);
+                    match builder
+                        .serve_connection_with_upgrades(io, hyper_service)
+                        .await
+                    {
+                        Ok(()) => tracing::debug!("connection closed"),
+                        Err(err) => tracing::debug!(error = %err, "connection closed with error"),
+                    }
+                    drop(connection);
+                    drop(counted);
                 }
-            });
+                .instrument(span),
+            );
         }
     }
 }
@@ -156,7 +345,10 @@ where
     type IntoFuture = ServeFuture;
 
     fn into_future(self) -> Self::IntoFuture {
-        ServeFuture(Box::pin(async move { self.run().await }))
+        ServeFuture(Box::pin(async move {
+            self.run().await;
+            Ok(())
+        }))
     }
 }
 
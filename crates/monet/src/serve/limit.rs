@@ -0,0 +1,346 @@
+use std::{
+    cell::{Cell, RefCell},
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker, ready},
+    time::Duration,
+};
+
+use http::{HeaderValue, StatusCode, header::RETRY_AFTER};
+use http_body_util::{Either, Empty};
+use pin_project_lite::pin_project;
+
+use crate::{HttpBody, HttpResponse, TowerService};
+
+/// Counts the open connections of one core. Every core runs its own accept loop,
+/// so the counter is a plain `Cell` without any cross-thread coordination.
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionLimit {
+    max: Option<usize>,
+    open: Cell<usize>,
+    waiter: RefCell<Option<Waker>>,
+}
+
+impl ConnectionLimit {
+    pub(crate) fn new(max: Option<usize>) -> Rc<Self> {
+        Rc::new(Self {
+            max,
+            ..Default::default()
+        })
+    }
+
+    /// Wait until the core has room for another connection, then claim it.
+    /// Accepting is paused while this is pending.
+    pub(crate) async fn acquire(self: &Rc<Self>) -> ConnectionGuard {
+        std::future::poll_fn(|cx| match self.max {
+            Some(max) if self.open.get() >= max => {
+                *self.waiter.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            _ => Poll::Ready(()),
+        })
+        .await;
+
+        self.open.set(self.open.get() + 1);
+        ConnectionGuard(self.clone())
+    }
+}
+
+/// Releases a connection slot when the connection task ends.
+#[derive(Debug)]
+pub(crate) struct ConnectionGuard(Rc<ConnectionLimit>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let limit = &self.0;
+        limit.open.set(limit.open.get() - 1);
+        if let Some(waker) = limit.waiter.borrow_mut().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Sheds requests with `503 Service Unavailable` once the number of requests in
+/// flight on the current core passes the threshold.
+#[derive(Debug, Clone)]
+pub(crate) struct LoadShed<S> {
+    inner: S,
+    in_flight: Rc<Cell<usize>>,
+    threshold: Option<usize>,
+    retry_after: Duration,
+}
+
+impl<S> LoadShed<S> {
+    pub(crate) fn new(
+        inner: S,
+        in_flight: Rc<Cell<usize>>,
+        threshold: Option<usize>,
+        retry_after: Duration,
+    ) -> Self {
+        Self {
+            inner,
+            in_flight,
+            threshold,
+            retry_after,
+        }
+    }
+
+    fn overloaded<B>(&self) -> HttpResponse<Either<InFlightBody<B>, Empty<B::Data>>>
+    where
+        B: HttpBody,
+    {
+        let mut res = HttpResponse::new(Either::Right(Empty::new()));
+        *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        res.headers_mut().insert(
+            RETRY_AFTER,
+            HeaderValue::from(self.retry_after.as_secs().max(1)),
+        );
+        res
+    }
+}
+
+impl<S, R, B> TowerService<R> for LoadShed<S>
+where
+    S: TowerService<R, Response = HttpResponse<B>, Error = Infallible>,
+    B: HttpBody,
+{
+    type Response = HttpResponse<Either<InFlightBody<B>, Empty<B::Data>>>;
+
+    type Error = Infallible;
+
+    type Future = LoadShedFuture<S::Future, B>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        if let Some(threshold) = self.threshold
+            && self.in_flight.get() >= threshold
+        {
+            return LoadShedFuture {
+                state: State::Overloaded {
+                    response: Some(self.overloaded()),
+                },
+            };
+        }
+
+        self.in_flight.set(self.in_flight.get() + 1);
+        LoadShedFuture {
+            state: State::Called {
+                future: self.inner.call(req),
+                guard: Some(InFlightGuard(self.in_flight.clone())),
+            },
+        }
+    }
+}
+
+/// Decrements the in-flight counter once the response body has been sent, or when
+/// the request is dropped before that.
+struct InFlightGuard(Rc<Cell<usize>>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() - 1);
+    }
+}
+
+pin_project! {
+    pub(crate) struct LoadShedFuture<F, B>
+    where
+        B: HttpBody,
+    {
+        #[pin]
+        state: State<F, B>,
+    }
+}
+
+pin_project! {
+    #[project = StateProj]
+    enum State<F, B>
+    where
+        B: HttpBody,
+    {
+        Called {
+            #[pin]
+            future: F,
+            guard: Option<InFlightGuard>,
+        },
+        Overloaded {
+            response: Option<HttpResponse<Either<InFlightBody<B>, Empty<B::Data>>>>,
+        },
+    }
+}
+
+impl<F, B> Future for LoadShedFuture<F, B>
+where
+    F: Future<Output = Result<HttpResponse<B>, Infallible>>,
+    B: HttpBody,
+{
+    type Output = Result<HttpResponse<Either<InFlightBody<B>, Empty<B::Data>>>, Infallible>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().state.project() {
+            StateProj::Called { future, guard } => {
+                let res = ready!(future.poll(cx)).unwrap_or_else(|err| match err {});
+                let guard = guard.take();
+                Poll::Ready(Ok(
+                    res.map(|body| Either::Left(InFlightBody { body, guard }))
+                ))
+            }
+            StateProj::Overloaded { response } => {
+                Poll::Ready(Ok(response.take().expect("polled after completion")))
+            }
+        }
+    }
+}
+
+pin_project! {
+    /// Response body that counts its request as in flight until the last frame has
+    /// been sent, so streaming responses count towards the overload threshold.
+    pub(crate) struct InFlightBody<B> {
+        #[pin]
+        body: B,
+        guard: Option<InFlightGuard>,
+    }
+}
+
+impl<B> HttpBody for InFlightBody<B>
+where
+    B: HttpBody,
+{
+    type Data = B::Data;
+
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.body.poll_frame(cx));
+        if frame.is_none() {
+            this.guard.take();
+        }
+        Poll::Ready(frame)
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.body.size_hint()
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, net::SocketAddr};
+
+    use bytes::Bytes;
+    use futures::channel::mpsc;
+    use http::Request;
+    use http_body::Frame;
+    use http_body_util::{BodyExt, StreamBody};
+    use hyper::client::conn::http1::SendRequest;
+    use monoio::net::TcpListener;
+    use monoio_compat::{TcpStreamCompat, hyper::MonoioIo};
+
+    use super::*;
+    use crate::{Body, Router, get, serve};
+
+    type Frames = mpsc::UnboundedReceiver<Result<Frame<Bytes>, Infallible>>;
+
+    fn listen() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    async fn connect(addr: SocketAddr) -> SendRequest<Body> {
+        let stream = monoio::net::TcpStream::connect(addr).await.unwrap();
+        let io = MonoioIo::new(TcpStreamCompat::new(stream));
+        let (sender, connection) = hyper::client::conn::http1::handshake(io).await.unwrap();
+        monoio::spawn(async move {
+            let _ = connection.await;
+        });
+        sender
+    }
+
+    fn get_request(uri: &str) -> Request<Body> {
+        Request::get(uri)
+            .header("host", "localhost")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn stops_accepting_at_max_connections() {
+        let (listener, addr) = listen();
+        let app = Router::new().route("/", get(|| async { "hello" }));
+        monoio::spawn(serve(listener, app).max_connections(1).into_future());
+
+        let mut first = connect(addr).await;
+        let res = first.send_request(get_request("/")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // The second connection waits in the backlog while the first one is open.
+        let mut second = connect(addr).await;
+        let waiting = second.send_request(get_request("/"));
+        let mut waiting = std::pin::pin!(waiting);
+        let timed_out = monoio::time::timeout(Duration::from_millis(50), waiting.as_mut()).await;
+        assert!(timed_out.is_err());
+
+        drop(first);
+        let res = waiting.await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn sheds_load_until_response_bodies_are_sent() {
+        let (listener, addr) = listen();
+        let (frames, rx) = mpsc::unbounded();
+        let rx: Rc<RefCell<Option<Frames>>> = Rc::new(RefCell::new(Some(rx)));
+        let app = Router::new()
+            .route(
+                "/stream",
+                get(move || {
+                    let rx = rx.borrow_mut().take().unwrap();
+                    async move { Body::new(StreamBody::new(rx)) }
+                }),
+            )
+            .route("/", get(|| async { "hello" }));
+        let server = serve(listener, app)
+            .overload_threshold(1)
+            .retry_after(Duration::from_secs(2));
+        monoio::spawn(server.into_future());
+
+        let mut streaming = connect(addr).await;
+        let res = streaming
+            .send_request(get_request("/stream"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let mut body = res.into_body();
+
+        // The handler has returned, but its body is still being sent.
+        let mut other = connect(addr).await;
+        let res = other.send_request(get_request("/")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()[RETRY_AFTER], "2");
+
+        frames
+            .unbounded_send(Ok(Frame::data(Bytes::from_static(b"done"))))
+            .unwrap();
+        drop(frames);
+        let sent = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(sent, "done");
+        assert!(body.frame().await.is_none());
+
+        let res = other.send_request(get_request("/")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    fs::{DirBuilder, Permissions},
+    io,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use monoio::net::{
+    UnixListener,
+    unix::{SocketAddr, UCred},
+};
+use monoio_compat::UnixStreamCompat;
+
+use crate::serve::{Listener, handle_accept_error};
+
+/// A [`UnixListener`] that owns its socket file.
+///
+/// Binding replaces a stale socket file left behind by a previous run, and the file
+/// is unlinked again when the listener is dropped, i.e. when the server shuts down
+/// (see [`Serve::with_graceful_shutdown`](crate::serve::Serve::with_graceful_shutdown)).
+/// Handlers can get the peer credentials of the connecting process through
+/// `ConnectInfo<UCred>`.
+#[derive(Debug)]
+pub struct UnixSocketListener {
+    inner: UnixListener,
+    addr: SocketAddr,
+    path: PathBuf,
+    /// Device and inode of the socket file, so dropping the listener doesn't
+    /// unlink a socket someone else bound at `path` in the meantime.
+    file_id: (u64, u64),
+}
+
+/// The permission bits of sockets bound with [`UnixSocketListener::bind`].
+const DEFAULT_SOCKET_MODE: u32 = 0o600;
+
+impl UnixSocketListener {
+    /// Bind a socket at `path` with mode `0o600`, so only processes of the current
+    /// user can connect to it, see [`bind_with_mode`](Self::bind_with_mode).
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::bind_with_mode(path, DEFAULT_SOCKET_MODE)
+    }
+
+    /// Bind a socket at `path` that only processes allowed by the permission bits
+    /// `mode` can connect to, e.g. `0o660` for the owner and its group.
+    ///
+    /// An existing socket file that nobody listens on anymore is removed first. If
+    /// another process still accepts on it, or `path` is not a socket, binding fails
+    /// instead of taking over the path.
+    ///
+    /// The socket is bound in a private directory next to `path`, given its mode
+    /// there and only then linked to `path`, so it is never reachable with looser
+    /// permissions, whatever the umask. Linking fails if another process created
+    /// `path` after the stale socket was removed, rather than replacing its socket.
+    pub fn bind_with_mode(path: impl AsRef<Path>, mode: u32) -> io::Result<Self> {
+        let path = path.as_ref();
+        remove_stale_socket(path)?;
+
+        let private = PrivateDir::create(path)?;
+        let staged = private.path.join("sock");
+        let inner = UnixListener::bind(&staged)?;
+        std::fs::set_permissions(&staged, Permissions::from_mode(mode))?;
+        publish(&staged, path)?;
+        let metadata = std::fs::symlink_metadata(path)?;
+
+        Ok(Self {
+            inner,
+            addr: SocketAddr::from_pathname(path)?,
+            path: path.to_path_buf(),
+            file_id: (metadata.dev(), metadata.ino()),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// A directory only the current user can enter, removed with whatever is left
+/// in it when dropped.
+struct PrivateDir {
+    path: PathBuf,
+}
+
+impl PrivateDir {
+    /// Create a directory next to `sibling`, on the same file system so files can
+    /// be renamed out of it.
+    fn create(sibling: &Path) -> io::Result<Self> {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+
+        let parent = match sibling.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let name = format!(
+            ".monet-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = parent.join(name);
+        DirBuilder::new().mode(0o700).create(&path)?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Give the socket file at `staged` the name `path`, failing with `AlreadyExists`
+/// instead of replacing whatever is at `path`. The staged name goes away with its
+/// private directory.
+fn publish(staged: &Path, path: &Path) -> io::Result<()> {
+    std::fs::hard_link(staged, path).map_err(|err| {
+        if err.kind() == io::ErrorKind::AlreadyExists {
+            io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} was created while binding", path.display()),
+            )
+        } else {
+            err
+        }
+    })
+}
+
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another process", path.display()),
+        )),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => std::fs::remove_file(path),
+        Err(err) => Err(err),
+    }
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let ours = std::fs::symlink_metadata(&self.path)
+            .is_ok_and(|metadata| (metadata.dev(), metadata.ino()) == self.file_id);
+        if ours {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The address and credentials of the process on the other end of a Unix domain
+/// socket connection. `cred` is always set for accepted connections and `None`
+/// for [`Listener::local_addr`].
+#[derive(Debug, Clone)]
+pub struct UnixPeer {
+    pub addr: SocketAddr,
+    pub cred: Option<UCred>,
+}
+
+impl Listener for UnixSocketListener {
+    type Io = UnixStreamCompat;
+
+    type Addr = UnixPeer;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            let (stream, addr) = match self.inner.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    handle_accept_error(err).await;
+                    continue;
+                }
+            };
+            // The peer may already be gone, there is nothing to serve then.
+            let Ok(cred) = stream.peer_cred() else {
+                continue;
+            };
+
+            let peer = UnixPeer {
+                addr,
+                cred: Some(cred),
+            };
+            return (UnixStreamCompat::new(stream), peer);
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(UnixPeer {
+            addr: self.addr.clone(),
+            cred: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::channel::oneshot;
+    use http::{Request, StatusCode};
+    use http_body_util::{BodyExt, Empty};
+    use monoio::net::{UnixStream, unix::UCred};
+    use monoio_compat::hyper::MonoioIo;
+
+    use super::*;
+    use crate::{Router, extract::connect_info::ConnectInfo, get, serve::serve_unix};
+
+    fn temp_dir() -> PathBuf {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "monet-unix-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::symlink_metadata(path).unwrap().mode() & 0o777
+    }
+
+    fn entries(dir: &Path) -> usize {
+        std::fs::read_dir(dir).unwrap().count()
+    }
+
+    #[monoio::test]
+    async fn binds_with_the_mode_and_unlinks_on_drop() {
+        let dir = temp_dir();
+        let path = dir.join("app.sock");
+
+        let listener = UnixSocketListener::bind(&path).unwrap();
+        assert!(
+            std::fs::symlink_metadata(&path)
+                .unwrap()
+                .file_type()
+                .is_socket()
+        );
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(entries(&dir), 1);
+        drop(listener);
+        assert!(!path.exists());
+
+        let listener = UnixSocketListener::bind_with_mode(&path, 0o660).unwrap();
+        assert_eq!(mode(&path), 0o660);
+        drop(listener);
+
+        assert_eq!(entries(&dir), 0);
+        std::fs::remove_dir(dir).unwrap();
+    }
+
+    #[monoio::test]
+    async fn only_replaces_stale_sockets() {
+        let dir = temp_dir();
+        let path = dir.join("app.sock");
+
+        // Left behind by a server that didn't shut down cleanly.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let listener = UnixSocketListener::bind(&path).unwrap();
+
+        let err = UnixSocketListener::bind(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        drop(listener);
+
+        std::fs::write(&path, "not a socket").unwrap();
+        let err = UnixSocketListener::bind(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+
+        assert_eq!(entries(&dir), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn never_replaces_a_socket_bound_in_between() {
+        let dir = temp_dir();
+        let path = dir.join("app.sock");
+
+        let private = PrivateDir::create(&path).unwrap();
+        let staged = private.path.join("sock");
+        let _ours = std::os::unix::net::UnixListener::bind(&staged).unwrap();
+        let _theirs = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let theirs = std::fs::symlink_metadata(&path).unwrap().ino();
+
+        let err = publish(&staged, &path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::symlink_metadata(&path).unwrap().ino(), theirs);
+
+        drop(private);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[monoio::test]
+    async fn leaves_the_socket_of_a_successor_alone() {
+        let dir = temp_dir();
+        let path = dir.join("app.sock");
+
+        let listener = UnixSocketListener::bind(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let _successor = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        drop(listener);
+        assert!(path.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[monoio::test]
+    async fn plain_listeners_report_their_address() {
+        let dir = temp_dir();
+        let path = dir.join("plain.sock");
+
+        let listener = UnixListener::bind(&path).unwrap();
+        let addr = Listener::local_addr(&listener).unwrap();
+        assert_eq!(addr.as_pathname(), Some(path.as_path()));
+
+        drop(listener);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn serves_peer_credentials_and_removes_the_socket_on_shutdown() {
+        let dir = temp_dir();
+        let path = dir.join("admin.sock");
+        let app = Router::new().route(
+            "/whoami",
+            get(|ConnectInfo(cred): ConnectInfo<UCred>| async move { cred.uid().to_string() }),
+        );
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = serve_unix(&path, app.into_make_service_with_connect_info::<UCred>())
+            .unwrap()
+            .with_graceful_shutdown(async {
+                let _ = stopped.await;
+            });
+        let server = monoio::spawn(server.into_future());
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (mut sender, connection) =
+            hyper::client::conn::http1::handshake(MonoioIo::new(UnixStreamCompat::new(stream)))
+                .await
+                .unwrap();
+        monoio::spawn(async move {
+            let _ = connection.await;
+        });
+        let req = Request::get("/whoami")
+            .header("host", "localhost")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let uid = res.into_body().collect().await.unwrap().to_bytes();
+        let owner = std::fs::metadata(&dir).unwrap().uid();
+        assert_eq!(uid, owner.to_string());
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+        assert!(!path.exists());
+        assert!(UnixStream::connect(&path).await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::util::ThreadBound;
+
+/// Connection IO that hyper can hand out as a [`hyper::upgrade::Upgraded`] stream.
+///
+/// Hyper only upgrades `Send` IO, while monoio IO is bound to the core that
+/// accepted it. Any handler holding the request can take the upgraded stream with
+/// `hyper::upgrade::on` and move it to another thread, so the IO is kept in a
+/// [`ThreadBound`]: using it anywhere but on the accepting core panics, and
+/// dropping it elsewhere leaks it, instead of touching a core-bound file
+/// descriptor from the wrong thread.
+#[derive(Debug)]
+pub(crate) struct UpgradeIo<T> {
+    inner: ThreadBound<T>,
+}
+
+impl<T> UpgradeIo<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self {
+            inner: ThreadBound::new(inner),
+        }
+    }
+
+    fn io(&self) -> &T {
+        self.inner.get().expect(WRONG_THREAD)
+    }
+
+    fn io_mut(self: Pin<&mut Self>) -> Pin<&mut T>
+    where
+        T: Unpin,
+    {
+        Pin::new(self.get_mut().inner.get_mut().expect(WRONG_THREAD))
+    }
+}
+
+const WRONG_THREAD: &str = "an upgraded connection was used off the core that accepted it";
+
+impl<T> hyper::rt::Read for UpgradeIo<T>
+where
+    T: hyper::rt::Read + Unpin,
+{
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.io_mut().poll_read(cx, buf)
+    }
+}
+
+impl<T> hyper::rt::Write for UpgradeIo<T>
+where
+    T: hyper::rt::Write + Unpin,
+{
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.io_mut().poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.io_mut().poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.io_mut().poll_shutdown(cx)
+    }
+
+    #[inline]
+    fn is_write_vectored(&self) -> bool {
+        self.io().is_write_vectored()
+    }
+
+    #[inline]
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.io_mut().poll_write_vectored(cx, bufs)
+    }
+}
@@ -0,0 +1,6 @@
+//! Ready-made services, mountable with
+//! [`Router::nest_service`](crate::Router::nest_service).
+
+pub mod fs;
+
+pub use self::fs::{ServeDir, ServeFile};
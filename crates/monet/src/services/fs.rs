@@ -0,0 +1,829 @@
+//! Serve files from disk with [`ServeDir`] and [`ServeFile`].
+//!
+//! Files are streamed with `read_at` on the io_uring driver. Both services answer
+//! `GET` and `HEAD` with `ETag`/`Last-Modified` validators, conditional requests
+//! (`304 Not Modified`), byte ranges (`206 Partial Content`, including
+//! `multipart/byteranges`) and optionally precompressed `.br`/`.gz` variants.
+
+use std::{
+    collections::{VecDeque, hash_map::RandomState},
+    convert::Infallible,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    io,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, header, request::Parts};
+use monoio::fs::File;
+
+use crate::{Problem, prelude::*, routing::strip_prefix::OriginalUri};
+
+use self::{
+    body::{Segment, file_body},
+    range::ParsedRanges,
+};
+
+mod body;
+mod range;
+
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Serves files from a directory.
+///
+/// The request path is resolved below the root directory. Paths that would leave
+/// it, through `..` or otherwise, are answered with `404 Not Found`. Requests for
+/// a directory are served its `index.html`, and directory paths without a trailing
+/// slash are redirected to the path with one so relative links keep working.
+///
+/// ```no_run
+/// # use monet::{Router, get, services::ServeDir};
+/// # async fn root() {}
+/// let app: Router = Router::new()
+///     .route("/", get(root))
+///     .nest_service("/assets", ServeDir::new("public").precompressed_br());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ServeDir {
+    root: Rc<Path>,
+    config: Config,
+}
+
+impl ServeDir {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().into(),
+            config: Config::default(),
+        }
+    }
+
+    /// Serve `index.html` for requests to a directory. Defaults to `true`; when
+    /// disabled, directories are answered with `404 Not Found`.
+    pub fn append_index_html_on_directories(mut self, append: bool) -> Self {
+        self.config.append_index_html = append;
+        self
+    }
+
+    /// Serve `<file>.gz` instead of `<file>` to clients that accept gzip, if it
+    /// exists.
+    pub fn precompressed_gzip(mut self) -> Self {
+        self.config.gzip = true;
+        self
+    }
+
+    /// Serve `<file>.br` instead of `<file>` to clients that accept brotli, if it
+    /// exists. Brotli is preferred over gzip.
+    pub fn precompressed_br(mut self) -> Self {
+        self.config.br = true;
+        self
+    }
+
+    /// How much of a file is read at once. Defaults to 64 KiB.
+    pub fn with_buf_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.config.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    async fn serve(self, parts: Parts) -> HttpResponse {
+        if let Some(res) = check_method(&parts.method) {
+            return res;
+        }
+
+        let Some(relative) = sanitize_path(parts.uri.path()) else {
+            return Problem::new(StatusCode::NOT_FOUND).into_rejection();
+        };
+        let mut path = self.root.join(relative);
+        // When nested, `/assets` reaches us as `/`: look at what the client sent.
+        let original = match parts.extensions.get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri,
+            None => &parts.uri,
+        };
+        let trailing_slash = original.path().ends_with('/');
+
+        match monoio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_dir() => {
+                if !self.config.append_index_html {
+                    return Problem::new(StatusCode::NOT_FOUND).into_rejection();
+                }
+                if !trailing_slash {
+                    return redirect_to_directory(original);
+                }
+                path.push("index.html");
+            }
+            Ok(_) if trailing_slash => return Problem::new(StatusCode::NOT_FOUND).into_rejection(),
+            Ok(_) => {}
+            Err(err) => return io_error_response(err),
+        }
+
+        serve_path(&path, &parts, &self.config).await
+    }
+}
+
+/// Serves a single file, whatever the request path.
+///
+/// ```no_run
+/// # use monet::{Router, services::ServeFile};
+/// let app: Router = Router::new().nest_service("/favicon.ico", ServeFile::new("favicon.ico"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ServeFile {
+    path: Rc<Path>,
+    config: Config,
+}
+
+impl ServeFile {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            config: Config::default(),
+        }
+    }
+
+    /// See [`ServeDir::precompressed_gzip`].
+    pub fn precompressed_gzip(mut self) -> Self {
+        self.config.gzip = true;
+        self
+    }
+
+    /// See [`ServeDir::precompressed_br`].
+    pub fn precompressed_br(mut self) -> Self {
+        self.config.br = true;
+        self
+    }
+
+    /// See [`ServeDir::with_buf_chunk_size`].
+    pub fn with_buf_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.config.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    async fn serve(self, parts: Parts) -> HttpResponse {
+        if let Some(res) = check_method(&parts.method) {
+            return res;
+        }
+        serve_path(&self.path, &parts, &self.config).await
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Config {
+    append_index_html: bool,
+    gzip: bool,
+    br: bool,
+    chunk_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            append_index_html: true,
+            gzip: false,
+            br: false,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl Config {
+    fn precompressed(&self) -> bool {
+        self.gzip || self.br
+    }
+}
+
+macro_rules! impl_service {
+    ($ty:ident) => {
+        impl<B> TowerService<HttpRequest<B>> for $ty {
+            type Response = HttpResponse;
+            type Error = Infallible;
+            type Future = ResponseFuture;
+
+            #[inline]
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: HttpRequest<B>) -> Self::Future {
+                let (parts, _body) = req.into_parts();
+                let this = self.clone();
+                ResponseFuture::new(Box::pin(async move { Ok(this.serve(parts).await) }))
+            }
+        }
+    };
+}
+
+impl_service!(ServeDir);
+impl_service!(ServeFile);
+
+opaque_future! {
+    /// Response future for [`ServeDir`] and [`ServeFile`].
+    pub type ResponseFuture =
+        Pin<Box<dyn Future<Output = Result<HttpResponse, Infallible>>>>;
+}
+
+fn check_method(method: &Method) -> Option<HttpResponse> {
+    if method == Method::GET || method == Method::HEAD {
+        return None;
+    }
+    let mut res = Problem::new(StatusCode::METHOD_NOT_ALLOWED).into_rejection();
+    res.headers_mut()
+        .insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+    Some(res)
+}
+
+/// Turn a request path into a relative path that can only point below the root.
+fn sanitize_path(path: &str) -> Option<PathBuf> {
+    let decoded = percent_encoding::percent_decode_str(path)
+        .decode_utf8()
+        .ok()?;
+
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/') {
+        if segment.contains(['\\', '\0']) {
+            return None;
+        }
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (None, _) | (Some(Component::CurDir), None) => {}
+            (Some(Component::Normal(name)), None) => relative.push(name),
+            _ => return None,
+        }
+    }
+    Some(relative)
+}
+
+fn redirect_to_directory(uri: &Uri) -> HttpResponse {
+    // A relative reference also works behind proxies that add a prefix of their own.
+    let name = uri.path().rsplit('/').next().unwrap_or_default();
+    let location = match uri.query() {
+        Some(query) => format!("{name}/?{query}"),
+        None => format!("{name}/"),
+    };
+
+    let mut res = StatusCode::TEMPORARY_REDIRECT.into_response();
+    if let Ok(location) = HeaderValue::from_str(&location) {
+        res.headers_mut().insert(header::LOCATION, location);
+    }
+    res
+}
+
+fn io_error_response(err: io::Error) -> HttpResponse {
+    let status = match err.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    Problem::new(status).into_rejection()
+}
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Br,
+    Gzip,
+}
+
+impl Encoding {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Br => "br",
+            Self::Gzip => "gz",
+        }
+    }
+
+    fn header_value(self) -> HeaderValue {
+        match self {
+            Self::Br => HeaderValue::from_static("br"),
+            Self::Gzip => HeaderValue::from_static("gzip"),
+        }
+    }
+}
+
+/// The precompressed encodings the client accepts, in the order we prefer them.
+fn accepted_encodings(headers: &HeaderMap, config: &Config) -> Vec<Encoding> {
+    // A coding listed by name overrides `*`, so `br;q=0, *` excludes brotli.
+    let accepts = |encoding: &str| {
+        let mut wildcard = None;
+        for item in headers
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+        {
+            let mut params = item.split(';');
+            let coding = params.next().unwrap_or_default().trim();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if coding.eq_ignore_ascii_case(encoding) {
+                return q > 0.0;
+            }
+            if coding == "*" {
+                wildcard = Some(q);
+            }
+        }
+        wildcard.is_some_and(|q| q > 0.0)
+    };
+
+    let mut encodings = Vec::new();
+    if config.br && accepts("br") {
+        encodings.push(Encoding::Br);
+    }
+    if config.gzip && accepts("gzip") {
+        encodings.push(Encoding::Gzip);
+    }
+    encodings
+}
+
+async fn open_file(path: &Path) -> io::Result<Option<(File, u64, SystemTime)>> {
+    let file = File::open(path).await?;
+    let metadata = file.metadata().await?;
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+    Ok(Some((file, metadata.len(), modified)))
+}
+
+async fn serve_path(path: &Path, parts: &Parts, config: &Config) -> HttpResponse {
+    let mut encoding = None;
+    let mut opened = None;
+    for candidate in accepted_encodings(&parts.headers, config) {
+        let mut compressed = path.as_os_str().to_owned();
+        compressed.push(".");
+        compressed.push(candidate.extension());
+        if let Ok(Some(file)) = open_file(Path::new(&compressed)).await {
+            encoding = Some(candidate);
+            opened = Some(file);
+            break;
+        }
+    }
+
+    let (file, len, modified) = match opened {
+        Some(opened) => opened,
+        None => match open_file(path).await {
+            Ok(Some(opened)) => opened,
+            Ok(None) => return Problem::new(StatusCode::NOT_FOUND).into_rejection(),
+            Err(err) => return io_error_response(err),
+        },
+    };
+
+    let content_type = mime_guess::from_path(path)
+        .first_raw()
+        .map(HeaderValue::from_static)
+        .unwrap_or_else(|| HeaderValue::from_static("application/octet-stream"));
+    let validators = Validators::new(len, modified, encoding);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, validators.etag.clone());
+    headers.insert(header::LAST_MODIFIED, validators.last_modified.clone());
+    if config.precompressed() {
+        headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+
+    if validators.not_modified(&parts.headers) {
+        let mut res = StatusCode::NOT_MODIFIED.into_response();
+        res.headers_mut().extend(headers);
+        return res;
+    }
+
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some(encoding) = encoding {
+        headers.insert(header::CONTENT_ENCODING, encoding.header_value());
+    }
+
+    let ranges = parts
+        .headers
+        .get(header::RANGE)
+        .filter(|_| validators.if_range_matches(&parts.headers))
+        .and_then(|range| range.to_str().ok())
+        .map(|range| range::parse(range, len))
+        .unwrap_or(ParsedRanges::Ignore);
+
+    let (status, segments) = match ranges {
+        ParsedRanges::Ignore => {
+            headers.insert(header::CONTENT_TYPE, content_type);
+            (
+                StatusCode::OK,
+                VecDeque::from([Segment::File { offset: 0, len }]),
+            )
+        }
+        ParsedRanges::Unsatisfiable => {
+            let mut res = Problem::new(StatusCode::RANGE_NOT_SATISFIABLE).into_rejection();
+            headers.insert(header::CONTENT_RANGE, content_range(None, len));
+            res.headers_mut().extend(headers);
+            return res;
+        }
+        ParsedRanges::Satisfiable(ranges) if ranges.len() == 1 => {
+            let range = &ranges[0];
+            headers.insert(header::CONTENT_TYPE, content_type);
+            headers.insert(header::CONTENT_RANGE, content_range(Some(range), len));
+            let segment = Segment::File {
+                offset: range.start,
+                len: range.end - range.start,
+            };
+            (StatusCode::PARTIAL_CONTENT, VecDeque::from([segment]))
+        }
+        ParsedRanges::Satisfiable(ranges) => {
+            let boundary = boundary();
+            let multipart = format!("multipart/byteranges; boundary={boundary}");
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(&multipart).expect("boundary is a valid header value"),
+            );
+            let segments = multipart_segments(&ranges, len, &content_type, &boundary);
+            (StatusCode::PARTIAL_CONTENT, segments)
+        }
+    };
+
+    let content_length: u64 = segments.iter().map(Segment::len).sum();
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+
+    let body = if parts.method == Method::HEAD {
+        Body::empty()
+    } else {
+        file_body(file, segments, config.chunk_size)
+    };
+
+    let mut res = body.into_response();
+    *res.status_mut() = status;
+    res.headers_mut().extend(headers);
+    res
+}
+
+fn content_range(range: Option<&std::ops::Range<u64>>, len: u64) -> HeaderValue {
+    let value = match range {
+        Some(range) => format!("bytes {}-{}/{len}", range.start, range.end - 1),
+        None => format!("bytes */{len}"),
+    };
+    HeaderValue::from_str(&value).expect("content range is a valid header value")
+}
+
+fn boundary() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64,
+    );
+    format!("monet-{:016x}", hasher.finish())
+}
+
+fn multipart_segments(
+    ranges: &[std::ops::Range<u64>],
+    len: u64,
+    content_type: &HeaderValue,
+    boundary: &str,
+) -> VecDeque<Segment> {
+    let content_type = content_type.to_str().unwrap_or("application/octet-stream");
+
+    let mut segments = VecDeque::with_capacity(ranges.len() * 2 + 1);
+    for range in ranges {
+        let part_headers = format!(
+            "\r\n--{boundary}\r\ncontent-type: {content_type}\r\ncontent-range: bytes {}-{}/{len}\r\n\r\n",
+            range.start,
+            range.end - 1,
+        );
+        segments.push_back(Segment::Bytes(Bytes::from(part_headers)));
+        segments.push_back(Segment::File {
+            offset: range.start,
+            len: range.end - range.start,
+        });
+    }
+    segments.push_back(Segment::Bytes(Bytes::from(format!(
+        "\r\n--{boundary}--\r\n"
+    ))));
+    segments
+}
+
+/// The `ETag` and `Last-Modified` validators of a file.
+struct Validators {
+    etag: HeaderValue,
+    last_modified: HeaderValue,
+    modified: SystemTime,
+}
+
+impl Validators {
+    fn new(len: u64, modified: SystemTime, encoding: Option<Encoding>) -> Self {
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        // Precompressed variants have different bytes and so need their own tag.
+        let suffix = encoding.map(Encoding::extension).unwrap_or_default();
+        let etag = format!(
+            "\"{:x}-{:x}-{len:x}{suffix}\"",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos()
+        );
+
+        Self {
+            etag: HeaderValue::from_str(&etag).expect("etag is a valid header value"),
+            last_modified: HeaderValue::from_str(&httpdate::fmt_http_date(modified))
+                .expect("http date is a valid header value"),
+            modified,
+        }
+    }
+
+    fn etag(&self) -> &str {
+        self.etag.to_str().unwrap_or_default()
+    }
+
+    /// Whether a `GET` or `HEAD` can be answered with `304 Not Modified`.
+    fn not_modified(&self, headers: &HeaderMap) -> bool {
+        // `If-None-Match` takes precedence, RFC 9110 section 13.1.3.
+        if headers.contains_key(header::IF_NONE_MATCH) {
+            return headers
+                .get_all(header::IF_NONE_MATCH)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak_eq(tag, self.etag()));
+        }
+
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok())
+            .is_some_and(|since| truncate_to_secs(self.modified) <= since)
+    }
+
+    /// Whether a `Range` header applies, i.e. there is no `If-Range` header or it
+    /// still matches the file.
+    fn if_range_matches(&self, headers: &HeaderMap) -> bool {
+        let Some(if_range) = headers
+            .get(header::IF_RANGE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return true;
+        };
+
+        if if_range.starts_with('"') {
+            // Strong comparison, weak tags never match.
+            if_range == self.etag()
+        } else {
+            httpdate::parse_http_date(if_range)
+                .is_ok_and(|date| date == truncate_to_secs(self.modified))
+        }
+    }
+}
+
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    UNIX_EPOCH + std::time::Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::{Router, test::TestClient};
+
+    /// A directory with a few files, removed when dropped.
+    struct Fixture {
+        dir: PathBuf,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            static NEXT: AtomicU32 = AtomicU32::new(0);
+
+            let dir = std::env::temp_dir().join(format!(
+                "monet-fs-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let files: [(&str, &[u8]); 5] = [
+                ("public/hello.txt", b"hello world"),
+                ("public/hello.txt.br", b"brotli bytes"),
+                ("public/hello.txt.gz", b"gzip bytes"),
+                ("public/index.html", b"<h1>home</h1>"),
+                ("secret.txt", b"secret"),
+            ];
+            for (name, contents) in files {
+                let path = dir.join(name);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, contents).unwrap();
+            }
+            std::fs::create_dir_all(dir.join("public/docs")).unwrap();
+            std::fs::write(dir.join("public/docs/index.html"), "docs").unwrap();
+            Self { dir }
+        }
+
+        fn serve_dir(&self) -> ServeDir {
+            ServeDir::new(self.dir.join("public"))
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn client(service: ServeDir) -> TestClient {
+        TestClient::new(Router::new().nest_service("/assets", service))
+    }
+
+    #[monoio::test]
+    async fn serves_files_and_not_modified() {
+        let fixture = Fixture::new();
+        let client = client(fixture.serve_dir());
+
+        let res = client.get("/assets/hello.txt").send().await;
+        res.assert_status(StatusCode::OK)
+            .assert_header(header::CONTENT_TYPE, "text/plain")
+            .assert_header(header::CONTENT_LENGTH, "11")
+            .assert_text("hello world");
+        let etag = res.header(header::ETAG).unwrap().to_owned();
+        let last_modified = res.header(header::LAST_MODIFIED).unwrap().to_owned();
+
+        client
+            .get("/assets/hello.txt")
+            .header(header::IF_NONE_MATCH, format!("\"other\", W/{etag}"))
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED)
+            .assert_header(header::ETAG, &etag)
+            .assert_text("");
+        client
+            .get("/assets/hello.txt")
+            .header(header::IF_MODIFIED_SINCE, &last_modified)
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED);
+        // `If-None-Match` wins over `If-Modified-Since`.
+        client
+            .get("/assets/hello.txt")
+            .header(header::IF_NONE_MATCH, "\"other\"")
+            .header(header::IF_MODIFIED_SINCE, &last_modified)
+            .send()
+            .await
+            .assert_status(StatusCode::OK);
+    }
+
+    #[monoio::test]
+    async fn serves_byte_ranges() {
+        let fixture = Fixture::new();
+        let client = client(fixture.serve_dir());
+
+        client
+            .get("/assets/hello.txt")
+            .header(header::RANGE, "bytes=6-")
+            .send()
+            .await
+            .assert_status(StatusCode::PARTIAL_CONTENT)
+            .assert_header(header::CONTENT_RANGE, "bytes 6-10/11")
+            .assert_text("world");
+
+        let res = client
+            .get("/assets/hello.txt")
+            .header(header::RANGE, "bytes=0-4, 6-10")
+            .send()
+            .await;
+        res.assert_status(StatusCode::PARTIAL_CONTENT);
+        let content_type = res.header(header::CONTENT_TYPE).unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        assert_eq!(
+            res.text(),
+            format!(
+                "\r\n--{boundary}\r\ncontent-type: text/plain\r\ncontent-range: bytes 0-4/11\r\n\r\nhello\
+                 \r\n--{boundary}\r\ncontent-type: text/plain\r\ncontent-range: bytes 6-10/11\r\n\r\nworld\
+                 \r\n--{boundary}--\r\n"
+            )
+        );
+        assert_eq!(
+            res.header(header::CONTENT_LENGTH),
+            Some(res.bytes().len().to_string().as_str())
+        );
+
+        client
+            .get("/assets/hello.txt")
+            .header(header::RANGE, "bytes=20-")
+            .send()
+            .await
+            .assert_status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .assert_header(header::CONTENT_RANGE, "bytes */11");
+    }
+
+    #[monoio::test]
+    async fn serves_precompressed_variants() {
+        let fixture = Fixture::new();
+        let client = client(fixture.serve_dir().precompressed_br().precompressed_gzip());
+        let get = |accept_encoding: &'static str| {
+            client
+                .get("/assets/hello.txt")
+                .header(header::ACCEPT_ENCODING, accept_encoding)
+                .send()
+        };
+
+        get("gzip, br")
+            .await
+            .assert_header(header::CONTENT_ENCODING, "br")
+            .assert_header(header::CONTENT_TYPE, "text/plain")
+            .assert_header(header::VARY, "accept-encoding")
+            .assert_text("brotli bytes");
+        get("gzip")
+            .await
+            .assert_header(header::CONTENT_ENCODING, "gzip")
+            .assert_text("gzip bytes");
+        get("br;q=0, *")
+            .await
+            .assert_header(header::CONTENT_ENCODING, "gzip")
+            .assert_text("gzip bytes");
+        let res = get("*;q=1, br;q=0, gzip;q=0").await;
+        res.assert_text("hello world");
+        assert_eq!(res.header(header::CONTENT_ENCODING), None);
+
+        // Variants have tags of their own.
+        let plain = get("identity").await;
+        let br = get("br").await;
+        assert_ne!(plain.header(header::ETAG), br.header(header::ETAG));
+    }
+
+    #[monoio::test]
+    async fn stays_inside_the_root() {
+        let fixture = Fixture::new();
+        let client = client(fixture.serve_dir());
+
+        for path in [
+            "/assets/../secret.txt",
+            "/assets/%2e%2e/secret.txt",
+            "/assets/docs/..%2f..%2fsecret.txt",
+            "/assets/..%5csecret.txt",
+            "/assets/%00",
+        ] {
+            client
+                .get(path)
+                .send()
+                .await
+                .assert_status(StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[monoio::test]
+    async fn redirects_directories_to_their_trailing_slash() {
+        let fixture = Fixture::new();
+        let client = client(fixture.serve_dir());
+
+        client
+            .get("/assets")
+            .send()
+            .await
+            .assert_status(StatusCode::TEMPORARY_REDIRECT)
+            .assert_header(header::LOCATION, "assets/");
+        client
+            .get("/assets/")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("<h1>home</h1>");
+        client
+            .get("/assets/docs?page=2")
+            .send()
+            .await
+            .assert_status(StatusCode::TEMPORARY_REDIRECT)
+            .assert_header(header::LOCATION, "docs/?page=2");
+        client
+            .get("/assets/docs/")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text("docs");
+        client
+            .get("/assets/hello.txt/")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[monoio::test]
+    async fn only_answers_get_and_head() {
+        let fixture = Fixture::new();
+        let client = client(fixture.serve_dir());
+
+        let res = client.head("/assets/hello.txt").send().await;
+        res.assert_status(StatusCode::OK)
+            .assert_header(header::CONTENT_LENGTH, "11")
+            .assert_text("");
+        client
+            .post("/assets/hello.txt")
+            .send()
+            .await
+            .assert_status(StatusCode::METHOD_NOT_ALLOWED)
+            .assert_header(header::ALLOW, "GET, HEAD");
+    }
+}
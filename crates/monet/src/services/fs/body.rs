@@ -0,0 +1,62 @@
+use std::{collections::VecDeque, io};
+
+use bytes::Bytes;
+use futures_util::stream;
+use http_body_util::StreamBody;
+use monoio::fs::File;
+
+use crate::{Body, Frame};
+
+/// A piece of a file response: literal bytes, such as multipart headers, or a
+/// region of the file.
+pub(super) enum Segment {
+    Bytes(Bytes),
+    File { offset: u64, len: u64 },
+}
+
+impl Segment {
+    pub(super) fn len(&self) -> u64 {
+        match self {
+            Self::Bytes(bytes) => bytes.len() as u64,
+            Self::File { len, .. } => *len,
+        }
+    }
+}
+
+/// Stream `segments` with `read_at`, reading at most `chunk_size` bytes of the file
+/// at a time.
+pub(super) fn file_body(file: File, segments: VecDeque<Segment>, chunk_size: usize) -> Body {
+    let stream = stream::unfold((file, segments), move |(file, mut segments)| async move {
+        loop {
+            match segments.pop_front()? {
+                Segment::Bytes(bytes) => {
+                    return Some((Ok(Frame::data(bytes)), (file, segments)));
+                }
+                Segment::File { len: 0, .. } => continue,
+                Segment::File { offset, len } => {
+                    let want = len.min(chunk_size as u64) as usize;
+                    let (res, buf) = file.read_at(Vec::with_capacity(want), offset).await;
+                    let read = match res {
+                        Ok(0) => {
+                            let err = io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "file shrunk while it was being served",
+                            );
+                            return Some((Err(err), (file, VecDeque::new())));
+                        }
+                        Ok(read) => read as u64,
+                        Err(err) => return Some((Err(err), (file, VecDeque::new()))),
+                    };
+
+                    segments.push_front(Segment::File {
+                        offset: offset + read,
+                        len: len - read,
+                    });
+                    return Some((Ok(Frame::data(Bytes::from(buf))), (file, segments)));
+                }
+            }
+        }
+    });
+
+    Body::new(StreamBody::new(stream))
+}
@@ -0,0 +1,146 @@
+use std::ops::Range;
+
+/// More ranges than this in one request are treated like no `Range` header at all,
+/// so clients can't make us seek all over a file for a single request.
+const MAX_RANGES: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum ParsedRanges {
+    /// The header is malformed or uses another unit; serve the whole file.
+    Ignore,
+    /// None of the ranges overlap the file.
+    Unsatisfiable,
+    Satisfiable(Vec<Range<u64>>),
+}
+
+/// Parse a `Range` header against a file of `len` bytes, see RFC 9110 section 14.2.
+pub(super) fn parse(header: &str, len: u64) -> ParsedRanges {
+    let Some(specs) = header.trim().strip_prefix("bytes=") else {
+        return ParsedRanges::Ignore;
+    };
+
+    let mut ranges = Vec::new();
+    for spec in specs.split(',') {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            continue;
+        }
+        let Some((start, end)) = spec.split_once('-') else {
+            return ParsedRanges::Ignore;
+        };
+        let (start, end) = (start.trim(), end.trim());
+
+        let range = if start.is_empty() {
+            // A suffix range, the last `end` bytes.
+            let Ok(suffix) = end.parse::<u64>() else {
+                return ParsedRanges::Ignore;
+            };
+            if suffix == 0 {
+                continue;
+            }
+            len.saturating_sub(suffix)..len
+        } else {
+            let Ok(start) = start.parse::<u64>() else {
+                return ParsedRanges::Ignore;
+            };
+            let end = if end.is_empty() {
+                len
+            } else {
+                match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.saturating_add(1).min(len),
+                    _ => return ParsedRanges::Ignore,
+                }
+            };
+            if start >= len {
+                continue;
+            }
+            start..end
+        };
+
+        ranges.push(range);
+        if ranges.len() > MAX_RANGES {
+            return ParsedRanges::Ignore;
+        }
+    }
+
+    if ranges.is_empty() {
+        ParsedRanges::Unsatisfiable
+    } else {
+        ParsedRanges::Satisfiable(ranges)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+
+    fn satisfiable(ranges: &[Range<u64>]) -> ParsedRanges {
+        ParsedRanges::Satisfiable(ranges.to_vec())
+    }
+
+    #[test]
+    fn single_ranges() {
+        assert_eq!(parse("bytes=0-499", 1000), satisfiable(&[0..500]));
+        assert_eq!(parse("bytes=500-", 1000), satisfiable(&[500..1000]));
+        assert_eq!(parse("bytes=-200", 1000), satisfiable(&[800..1000]));
+        assert_eq!(parse(" bytes= 10 - 19 ", 1000), satisfiable(&[10..20]));
+    }
+
+    #[test]
+    fn ranges_are_clamped_to_the_file() {
+        assert_eq!(parse("bytes=900-5000", 1000), satisfiable(&[900..1000]));
+        assert_eq!(parse("bytes=-5000", 1000), satisfiable(&[0..1000]));
+        assert_eq!(
+            parse("bytes=0-18446744073709551615", 10),
+            satisfiable(&[0..10])
+        );
+    }
+
+    #[test]
+    fn multiple_ranges() {
+        assert_eq!(
+            parse("bytes=0-9, 20-29,-5", 100),
+            satisfiable(&[0..10, 20..30, 95..100])
+        );
+        // Ranges past the end are dropped, the others still served.
+        assert_eq!(parse("bytes=0-9,200-300", 100), satisfiable(&[0..10]));
+        assert_eq!(parse("bytes=0-9,,", 100), satisfiable(&[0..10]));
+    }
+
+    #[test]
+    fn unsatisfiable_ranges() {
+        assert_eq!(parse("bytes=1000-", 1000), ParsedRanges::Unsatisfiable);
+        assert_eq!(parse("bytes=1000-2000", 1000), ParsedRanges::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), ParsedRanges::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), ParsedRanges::Unsatisfiable);
+    }
+
+    #[test]
+    fn malformed_headers_are_ignored() {
+        for header in [
+            "",
+            "items=0-1",
+            "bytes 0-1",
+            "bytes=1",
+            "bytes=a-b",
+            "bytes=5-1",
+            "bytes=-",
+            "bytes=--1",
+            "bytes=0-1,x",
+        ] {
+            assert_eq!(parse(header, 1000), ParsedRanges::Ignore, "{header:?}");
+        }
+    }
+
+    #[test]
+    fn too_many_ranges_are_ignored() {
+        let header = format!("bytes={}", vec!["0-0"; MAX_RANGES].join(","));
+        assert!(
+            matches!(parse(&header, 10), ParsedRanges::Satisfiable(ranges) if ranges.len() == MAX_RANGES)
+        );
+
+        let header = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert_eq!(parse(&header, 10), ParsedRanges::Ignore);
+    }
+}
@@ -0,0 +1,440 @@
+//! Drive a [`Router`] from tests without writing any client code.
+//!
+//! [`TestClient::new`] calls the router in-process, with the same per-request
+//! setup as [`serve`](crate::serve()), such as the [`TaskScope`](crate::TaskScope).
+//! [`TestClient::loopback`] instead serves the router on a loopback socket and
+//! talks HTTP/1.1 to it, for tests that should also cover connection handling.
+//!
+//! Only available with the `test-util` feature, which is meant to be enabled for
+//! dev-dependencies:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! monet = { version = "0.1", features = ["test-util"] }
+//! ```
+//!
+//! ```no_run
+//! # use http::StatusCode;
+//! # use monet::{Router, get, test::TestClient};
+//! #[monoio::test(timer_enabled = true)]
+//! async fn hello() {
+//!     let client = TestClient::new(Router::new().route("/", get(|| async { "hello" })));
+//!
+//!     client
+//!         .get("/")
+//!         .header("accept", "text/plain")
+//!         .send()
+//!         .await
+//!         .assert_status(StatusCode::OK)
+//!         .assert_text("hello");
+//! }
+//! ```
+
+use std::{fmt, net::SocketAddr};
+
+use bytes::Bytes;
+use futures_util::future::{AbortHandle, Abortable};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header, request};
+use http_body_util::BodyExt;
+use monoio_compat::{TcpStreamCompat, hyper::MonoioIo};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    Body, HttpRequest, Router, TowerService, extract::task_scope::TaskScopeService, serve,
+};
+
+/// Sends requests to a [`Router`], see the [module docs](self).
+///
+/// Must be used from within a monoio runtime.
+pub struct TestClient {
+    transport: Transport,
+}
+
+enum Transport {
+    Service(Box<TaskScopeService<Router>>),
+    Loopback {
+        addr: SocketAddr,
+        server: AbortHandle,
+    },
+}
+
+impl TestClient {
+    /// Call `router` directly, without any sockets.
+    pub fn new(router: Router) -> Self {
+        Self {
+            transport: Transport::Service(Box::new(TaskScopeService::new(router))),
+        }
+    }
+
+    /// Serve `router` on a loopback port on the current core, for as long as the
+    /// client lives, and send requests to it over TCP.
+    ///
+    /// # Panics
+    ///
+    /// If no loopback port can be bound.
+    pub fn loopback(router: Router) -> Self {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0")
+            .expect("failed to bind a loopback port for the test server");
+        let addr = listener
+            .local_addr()
+            .expect("failed to get the address of the test server");
+
+        let (server, registration) = AbortHandle::new_pair();
+        let serve = Abortable::new(serve(listener, router).into_future(), registration);
+        monoio::spawn(serve);
+
+        Self {
+            transport: Transport::Loopback { addr, server },
+        }
+    }
+
+    /// The address of the test server, if the client was built with
+    /// [`TestClient::loopback`].
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.transport {
+            Transport::Service(_) => None,
+            Transport::Loopback { addr, .. } => Some(*addr),
+        }
+    }
+
+    pub fn request(&self, method: Method, uri: &str) -> RequestBuilder<'_> {
+        RequestBuilder {
+            client: self,
+            builder: request::Builder::new().method(method).uri(uri),
+            body: Body::empty(),
+        }
+    }
+
+    pub fn get(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request(Method::GET, uri)
+    }
+
+    pub fn head(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request(Method::HEAD, uri)
+    }
+
+    pub fn post(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request(Method::POST, uri)
+    }
+
+    pub fn put(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request(Method::PUT, uri)
+    }
+
+    pub fn patch(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request(Method::PATCH, uri)
+    }
+
+    pub fn delete(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request(Method::DELETE, uri)
+    }
+
+    pub fn options(&self, uri: &str) -> RequestBuilder<'_> {
+        self.request(Method::OPTIONS, uri)
+    }
+
+    async fn send(&self, req: HttpRequest) -> TestResponse {
+        match &self.transport {
+            Transport::Service(service) => {
+                let res = service
+                    .clone()
+                    .call(req)
+                    .await
+                    .unwrap_or_else(|err| match err {});
+                TestResponse::collect(res).await
+            }
+            Transport::Loopback { addr, .. } => send_over_tcp(*addr, req).await,
+        }
+    }
+}
+
+impl Drop for TestClient {
+    fn drop(&mut self) {
+        if let Transport::Loopback { server, .. } = &self.transport {
+            server.abort();
+        }
+    }
+}
+
+impl fmt::Debug for TestClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestClient")
+            .field("local_addr", &self.local_addr())
+            .finish_non_exhaustive()
+    }
+}
+
+async fn send_over_tcp(addr: SocketAddr, mut req: HttpRequest) -> TestResponse {
+    if !req.headers().contains_key(header::HOST) {
+        let host = HeaderValue::from_str(&addr.to_string()).expect("socket addresses are valid");
+        req.headers_mut().insert(header::HOST, host);
+    }
+
+    let stream = monoio::net::TcpStream::connect(addr)
+        .await
+        .expect("failed to connect to the test server");
+    let io = MonoioIo::new(TcpStreamCompat::new(stream));
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(io)
+        .await
+        .expect("HTTP handshake with the test server failed");
+    monoio::spawn(async move {
+        let _ = connection.await;
+    });
+
+    let res = sender
+        .send_request(req)
+        .await
+        .expect("failed to send the request to the test server");
+    TestResponse::collect(res).await
+}
+
+/// A request being built by a [`TestClient`].
+#[must_use = "requests do nothing until `send` is awaited"]
+pub struct RequestBuilder<'a> {
+    client: &'a TestClient,
+    builder: request::Builder,
+    body: Body,
+}
+
+impl RequestBuilder<'_> {
+    /// Append a header.
+    ///
+    /// # Panics
+    ///
+    /// When the request is sent, if the name or value was invalid.
+    pub fn header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.builder = self.builder.header(name, value);
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Body>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Send `value` as a JSON body, with `Content-Type: application/json`.
+    pub fn json<T>(self, value: &T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        let body = serde_json::to_vec(value).expect("failed to serialize the JSON body");
+        self.header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+    }
+
+    /// Send `value` as a URL encoded form, with
+    /// `Content-Type: application/x-www-form-urlencoded`.
+    pub fn form<T>(self, value: &T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        let body = serde_urlencoded::to_string(value).expect("failed to serialize the form");
+        self.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body)
+    }
+
+    /// Send the request and read the whole response.
+    ///
+    /// # Panics
+    ///
+    /// If the request is invalid or, with [`TestClient::loopback`], the server
+    /// can't be reached.
+    pub async fn send(self) -> TestResponse {
+        let req = self.builder.body(self.body).expect("invalid test request");
+        self.client.send(req).await
+    }
+}
+
+impl fmt::Debug for RequestBuilder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBuilder")
+            .field("method", &self.builder.method_ref())
+            .field("uri", &self.builder.uri_ref())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A response received by a [`TestClient`], with its body read in full.
+///
+/// The `assert_*` methods panic with a description of the mismatch and return the
+/// response, so they can be chained.
+#[derive(Debug)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TestResponse {
+    async fn collect<B>(res: http::Response<B>) -> Self
+    where
+        B: http_body::Body<Data = Bytes>,
+        B::Error: fmt::Debug,
+    {
+        let (parts, body) = res.into_parts();
+        let body = body
+            .collect()
+            .await
+            .expect("failed to read the response body")
+            .to_bytes();
+        Self {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The first value of the header `name`, if it is valid UTF-8.
+    pub fn header(&self, name: impl header::AsHeaderName) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    pub fn bytes(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// The body as text.
+    ///
+    /// # Panics
+    ///
+    /// If the body isn't valid UTF-8.
+    #[track_caller]
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("response body is not valid UTF-8")
+    }
+
+    /// The body deserialized from JSON.
+    ///
+    /// # Panics
+    ///
+    /// If the body isn't valid JSON for `T`.
+    #[track_caller]
+    pub fn json<T>(&self) -> T
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|err| panic!("response body is not the expected JSON: {err}"))
+    }
+
+    #[track_caller]
+    pub fn assert_status(&self, status: StatusCode) -> &Self {
+        assert_eq!(
+            self.status,
+            status,
+            "unexpected status, response body: {:?}",
+            String::from_utf8_lossy(&self.body)
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_header(&self, name: impl header::AsHeaderName, value: &str) -> &Self {
+        let name = name.as_str().to_owned();
+        let actual = self.headers.get(&name).map(|value| value.to_str());
+        assert!(
+            matches!(actual, Some(Ok(actual)) if actual == value),
+            "expected header `{name}: {value}`, got {actual:?}"
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_text(&self, text: &str) -> &Self {
+        assert_eq!(self.text(), text, "unexpected response body");
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_json<T>(&self, expected: &T) -> &Self
+    where
+        T: DeserializeOwned + PartialEq + fmt::Debug,
+    {
+        assert_eq!(&self.json::<T>(), expected, "unexpected JSON response body");
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{Json, get};
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/host",
+                get(|req: HttpRequest| async move {
+                    req.headers()[header::HOST].to_str().unwrap().to_owned()
+                }),
+            )
+            .fallback(|Json(value): Json<Value>| async move { Json(value) })
+    }
+
+    #[monoio::test]
+    async fn calls_the_router_in_process() {
+        let client = TestClient::new(app());
+        assert_eq!(client.local_addr(), None);
+
+        client
+            .post("/")
+            .json(&json!({ "a": [1, 2] }))
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_json(&json!({ "a": [1, 2] }));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn loopback_sends_requests_over_tcp() {
+        let client = TestClient::loopback(app());
+        let addr = client
+            .local_addr()
+            .expect("loopback clients have an address");
+        assert!(addr.ip().is_loopback());
+
+        client
+            .get("/host")
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_text(&addr.to_string());
+        client
+            .post("/")
+            .json(&json!({ "a": [1, 2] }))
+            .send()
+            .await
+            .assert_status(StatusCode::OK)
+            .assert_header(header::CONTENT_TYPE, "application/json")
+            .assert_json(&json!({ "a": [1, 2] }));
+        client
+            .post("/")
+            .body("not json")
+            .send()
+            .await
+            .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        // Dropping the client stops the server.
+        drop(client);
+        monoio::time::sleep(Duration::from_millis(10)).await;
+        assert!(monoio::net::TcpStream::connect(addr).await.is_err());
+    }
+}
@@ -0,0 +1,545 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, RwLock},
+    task::{Context, Poll, ready},
+    time::{Duration, SystemTime},
+};
+
+use monoio::time::{Timeout, timeout};
+use monoio_compat::{AsyncRead, AsyncWrite};
+use rustls::{
+    InconsistentKeys, ServerConfig,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert, ServerConnection},
+    sign::CertifiedKey,
+};
+use tokio_rustls::{Accept, TlsAcceptor, server};
+
+use crate::serve::Listener;
+
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Certificates, ALPN protocols and handshake settings shared by [`TlsListener`]s.
+///
+/// The config is cheap to clone and can be moved to every core: all clones share
+/// the same certificate store, so [`TlsConfig::reload`] on one core is seen by all.
+#[derive(Clone)]
+pub struct TlsConfig {
+    server_config: Arc<ServerConfig>,
+    resolver: Arc<CertResolver>,
+    handshake_timeout: Duration,
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("resolver", &self.resolver)
+            .field("alpn_protocols", &self.server_config.alpn_protocols)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .finish()
+    }
+}
+
+impl TlsConfig {
+    /// Create a config from an in-memory PEM certificate chain and private key.
+    pub fn from_pem(cert: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> io::Result<Self> {
+        let cert = CertSource::from_pem(cert.as_ref(), key.as_ref(), None)?;
+        Ok(Self::new(CertResolver::new(cert)))
+    }
+
+    /// Create a config from PEM files on disk. The files can later be re-read by
+    /// [`TlsConfig::reload`] or [`TlsConfig::watch`].
+    pub async fn from_pem_file(
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let cert = CertSource::from_pem_file(cert_path.as_ref(), key_path.as_ref()).await?;
+        Ok(Self::new(CertResolver::new(cert)))
+    }
+
+    fn new(resolver: CertResolver) -> Self {
+        let resolver = Arc::new(resolver);
+
+        let mut server_config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .expect("the ring provider supports the default protocol versions")
+                .with_no_client_auth()
+                .with_cert_resolver(resolver.clone());
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Self {
+            server_config: Arc::new(server_config),
+            resolver,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    /// Serve `server_name` with its own in-memory certificate. Clients whose SNI does
+    /// not match any name get the default certificate.
+    pub fn add_sni_pem(
+        &self,
+        server_name: impl Into<String>,
+        cert: impl AsRef<[u8]>,
+        key: impl AsRef<[u8]>,
+    ) -> io::Result<()> {
+        let cert = CertSource::from_pem(cert.as_ref(), key.as_ref(), None)?;
+        self.resolver.insert_sni(server_name.into(), cert);
+        Ok(())
+    }
+
+    /// Serve `server_name` with a certificate loaded from PEM files on disk.
+    pub async fn add_sni_pem_file(
+        &self,
+        server_name: impl Into<String>,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        let cert = CertSource::from_pem_file(cert_path.as_ref(), key_path.as_ref()).await?;
+        self.resolver.insert_sni(server_name.into(), cert);
+        Ok(())
+    }
+
+    /// Set the ALPN protocols offered to clients, in order of preference.
+    ///
+    /// Defaults to `h2` and `http/1.1`.
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        Arc::make_mut(&mut self.server_config).alpn_protocols = protocols;
+        self
+    }
+
+    /// Set how long a client has to complete the TLS handshake before the
+    /// connection is dropped. Defaults to 10 seconds.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Re-read every file backed certificate whose files changed since they were
+    /// last loaded. New handshakes pick up the new certificates, established
+    /// connections are left untouched.
+    ///
+    /// A certificate that fails to load, or whose key doesn't match it, keeps the
+    /// one currently in use, without holding up the others. All failures are
+    /// reported together.
+    pub async fn reload(&self) -> Result<(), ReloadError> {
+        let mut failures = Vec::new();
+        for (server_name, cert_path, key_path, modified) in self.resolver.file_sources() {
+            let cert = match reload_source(&cert_path, &key_path, modified).await {
+                Ok(Some(cert)) => cert,
+                Ok(None) => continue,
+                Err(err) => {
+                    failures.push((server_name, err));
+                    continue;
+                }
+            };
+            match server_name {
+                Some(server_name) => self.resolver.insert_sni(server_name, cert),
+                None => self.resolver.set_default(cert),
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(ReloadError { failures })
+        }
+    }
+
+    /// Spawn a task on the current core that calls [`TlsConfig::reload`] every `period`.
+    ///
+    /// The certificate store is shared by all clones, so it is enough to watch
+    /// from a single core. Failed reloads keep the certificates currently in use.
+    pub fn watch(&self, period: Duration) -> monoio::task::JoinHandle<()> {
+        let config = self.clone();
+        monoio::spawn(async move {
+            let mut interval = monoio::time::interval(period);
+            loop {
+                interval.tick().await;
+                if let Err(err) = config.reload().await {
+                    tracing::warn!(error = %err, "failed to reload TLS certificates");
+                }
+            }
+        })
+    }
+}
+
+/// The certificate files changed since `modified`, if they did.
+async fn reload_source(
+    cert_path: &Path,
+    key_path: &Path,
+    modified: Option<SystemTime>,
+) -> io::Result<Option<CertSource>> {
+    if modified.is_some() && modified == last_modified(cert_path, key_path).await? {
+        return Ok(None);
+    }
+    CertSource::from_pem_file(cert_path, key_path)
+        .await
+        .map(Some)
+}
+
+/// The certificates [`TlsConfig::reload`] failed to reload.
+#[derive(Debug)]
+pub struct ReloadError {
+    failures: Vec<(Option<String>, io::Error)>,
+}
+
+impl ReloadError {
+    /// The SNI server name of each failed certificate, `None` for the default one,
+    /// with the reason it failed.
+    pub fn failures(&self) -> impl Iterator<Item = (Option<&str>, &io::Error)> {
+        self.failures
+            .iter()
+            .map(|(server_name, err)| (server_name.as_deref(), err))
+    }
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("failed to reload TLS certificates")?;
+        for (i, (server_name, err)) in self.failures().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            let server_name = server_name.unwrap_or("default");
+            write!(f, "{separator}{server_name}: {err}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+struct CertSource {
+    key: Arc<CertifiedKey>,
+    paths: Option<(PathBuf, PathBuf)>,
+    modified: Option<SystemTime>,
+}
+
+impl CertSource {
+    fn from_pem(cert: &[u8], key: &[u8], paths: Option<(PathBuf, PathBuf)>) -> io::Result<Self> {
+        let certs = CertificateDer::pem_slice_iter(cert)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid_data)?;
+        if certs.is_empty() {
+            return Err(invalid_data("no certificate found in PEM input"));
+        }
+
+        let key = PrivateKeyDer::from_pem_slice(key).map_err(invalid_data)?;
+        let signing_key =
+            rustls::crypto::ring::sign::any_supported_type(&key).map_err(invalid_data)?;
+
+        // Catches a certificate and key from different pairs, e.g. read while a
+        // renewal was halfway through writing them.
+        let key = CertifiedKey::new(certs, signing_key);
+        if let Err(err @ rustls::Error::InconsistentKeys(InconsistentKeys::KeyMismatch)) =
+            key.keys_match()
+        {
+            return Err(invalid_data(err));
+        }
+
+        Ok(Self {
+            key: Arc::new(key),
+            paths,
+            modified: None,
+        })
+    }
+
+    async fn from_pem_file(cert_path: &Path, key_path: &Path) -> io::Result<Self> {
+        let modified = last_modified(cert_path, key_path).await?;
+        let cert = monoio::fs::read(cert_path).await?;
+        let key = monoio::fs::read(key_path).await?;
+
+        let paths = Some((cert_path.to_path_buf(), key_path.to_path_buf()));
+        let mut source = Self::from_pem(&cert, &key, paths)?;
+        source.modified = modified;
+        Ok(source)
+    }
+}
+
+/// The most recent modification time of the certificate and key files.
+async fn last_modified(cert_path: &Path, key_path: &Path) -> io::Result<Option<SystemTime>> {
+    let cert = monoio::fs::metadata(cert_path).await?.modified().ok();
+    let key = monoio::fs::metadata(key_path).await?.modified().ok();
+    Ok(cert.max(key))
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Picks a certificate by the SNI server name of the client hello.
+struct CertResolver {
+    certs: RwLock<CertStore>,
+}
+
+struct CertStore {
+    default: CertSource,
+    sni: HashMap<String, CertSource>,
+}
+
+impl CertResolver {
+    fn new(default: CertSource) -> Self {
+        Self {
+            certs: RwLock::new(CertStore {
+                default,
+                sni: HashMap::new(),
+            }),
+        }
+    }
+
+    fn set_default(&self, cert: CertSource) {
+        self.certs.write().unwrap().default = cert;
+    }
+
+    fn insert_sni(&self, server_name: String, cert: CertSource) {
+        let server_name = server_name.to_ascii_lowercase();
+        self.certs.write().unwrap().sni.insert(server_name, cert);
+    }
+
+    /// Paths of every file backed certificate, keyed by SNI name (`None` for the
+    /// default certificate).
+    fn file_sources(&self) -> Vec<(Option<String>, PathBuf, PathBuf, Option<SystemTime>)> {
+        let certs = self.certs.read().unwrap();
+        let default = std::iter::once((None, &certs.default));
+        let sni = certs
+            .sni
+            .iter()
+            .map(|(name, cert)| (Some(name.clone()), cert));
+
+        default
+            .chain(sni)
+            .filter_map(|(name, cert)| {
+                let (cert_path, key_path) = cert.paths.clone()?;
+                Some((name, cert_path, key_path, cert.modified))
+            })
+            .collect()
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certs = self.certs.read().unwrap();
+        let cert = client_hello
+            .server_name()
+            .and_then(|name| certs.sni.get(&name.to_ascii_lowercase()))
+            .unwrap_or(&certs.default);
+        Some(cert.key.clone())
+    }
+}
+
+impl fmt::Debug for CertResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let certs = self.certs.read().unwrap();
+        f.debug_struct("CertResolver")
+            .field("sni", &certs.sni.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`Listener`] that terminates TLS on top of another listener, by default a
+/// [`monoio::net::TcpListener`].
+///
+/// Accepting never waits for the handshake: it runs on the connection task the
+/// first time the stream is read or written, so a slow client can't stall the
+/// accept loop of its core.
+pub struct TlsListener<L = monoio::net::TcpListener> {
+    inner: L,
+    config: TlsConfig,
+    acceptor: TlsAcceptor,
+}
+
+impl<L> TlsListener<L> {
+    pub fn new(inner: L, config: TlsConfig) -> Self {
+        let acceptor = TlsAcceptor::from(config.server_config.clone());
+        Self {
+            inner,
+            config,
+            acceptor,
+        }
+    }
+
+    pub fn config(&self) -> &TlsConfig {
+        &self.config
+    }
+
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+impl<L> fmt::Debug for TlsListener<L>
+where
+    L: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsListener")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<L> Listener for TlsListener<L>
+where
+    L: Listener,
+{
+    type Io = TlsStream<L::Io>;
+
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        let (io, addr) = self.inner.accept().await;
+        let handshake = timeout(self.config.handshake_timeout, self.acceptor.accept(io));
+        (TlsStream::new(handshake), addr)
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+/// A server side TLS stream whose handshake completes lazily on first use.
+pub struct TlsStream<IO> {
+    state: TlsState<IO>,
+}
+
+// Established streams are read and written far more often than they are set up,
+// so only the handshake is boxed.
+#[allow(clippy::large_enum_variant)]
+enum TlsState<IO> {
+    Handshaking(Pin<Box<Timeout<Accept<IO>>>>),
+    Streaming(server::TlsStream<IO>),
+    Failed,
+}
+
+impl<IO> TlsStream<IO> {
+    fn new(handshake: Timeout<Accept<IO>>) -> Self {
+        Self {
+            state: TlsState::Handshaking(Box::pin(handshake)),
+        }
+    }
+
+    /// The rustls connection, once the handshake has completed.
+    pub fn connection(&self) -> Option<&ServerConnection> {
+        match &self.state {
+            TlsState::Streaming(stream) => Some(stream.get_ref().1),
+            _ => None,
+        }
+    }
+
+    /// The protocol negotiated through ALPN, once the handshake has completed.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.connection()?.alpn_protocol()
+    }
+
+    /// The SNI server name sent by the client, once the handshake has completed.
+    pub fn server_name(&self) -> Option<&str> {
+        self.connection()?.server_name()
+    }
+}
+
+impl<IO> TlsStream<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_handshake(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<&mut server::TlsStream<IO>>> {
+        if let TlsState::Handshaking(handshake) = &mut self.state {
+            let result = match ready!(handshake.as_mut().poll(cx)) {
+                Ok(result) => result,
+                Err(_elapsed) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "TLS handshake timed out",
+                )),
+            };
+
+            match result {
+                Ok(stream) => self.state = TlsState::Streaming(stream),
+                Err(err) => {
+                    self.state = TlsState::Failed;
+                    return Poll::Ready(Err(err));
+                }
+            }
+        }
+
+        match &mut self.state {
+            TlsState::Streaming(stream) => Poll::Ready(Ok(stream)),
+            TlsState::Failed => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "TLS handshake failed",
+            ))),
+            TlsState::Handshaking(_) => unreachable!(),
+        }
+    }
+}
+
+impl<IO> fmt::Debug for TlsStream<IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            TlsState::Handshaking(_) => "Handshaking",
+            TlsState::Streaming(_) => "Streaming",
+            TlsState::Failed => "Failed",
+        };
+        f.debug_struct("TlsStream").field("state", &state).finish()
+    }
+}
+
+impl<IO> AsyncRead for TlsStream<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let stream = ready!(self.get_mut().poll_handshake(cx))?;
+        Pin::new(stream).poll_read(cx, buf)
+    }
+}
+
+impl<IO> AsyncWrite for TlsStream<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let stream = ready!(self.get_mut().poll_handshake(cx))?;
+        Pin::new(stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let stream = ready!(self.get_mut().poll_handshake(cx))?;
+        Pin::new(stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().state {
+            TlsState::Streaming(stream) => Pin::new(stream).poll_shutdown(cx),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let stream = ready!(self.get_mut().poll_handshake(cx))?;
+        Pin::new(stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicU32, Ordering},
+    thread::{self, ThreadId},
+};
+
+/// A small number identifying the core, i.e. the runtime thread, the caller runs
+/// on. Cores are numbered in the order they first ask.
+pub(crate) fn core_id() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+
+    thread_local! {
+        static CORE_ID: u32 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+
+    CORE_ID.with(|id| *id)
+}
+
+/// Lets a `!Send` value ride along in [`http::Extensions`], or in hyper's
+/// upgraded connections, which require `Send + Sync`.
+///
+/// Requests never leave the core that accepted them, but the type system can't
+/// know that. The value is only handed out on the thread that created it; dropped
+/// anywhere else, it is leaked rather than touched.
+pub(crate) struct ThreadBound<T> {
+    value: ManuallyDrop<T>,
+    thread: ThreadId,
+}
+
+// SAFETY: the value is only ever accessed, cloned or dropped on `thread`. Every
+// way to reach it, `get`, `get_mut`, `Clone` and `Drop`, checks the current
+// thread first, so moving or sharing the wrapper across threads can at worst leak
+// the value, never touch it from another thread.
+unsafe impl<T> Send for ThreadBound<T> {}
+unsafe impl<T> Sync for ThreadBound<T> {}
+
+impl<T> ThreadBound<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            thread: thread::current().id(),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<&T> {
+        (thread::current().id() == self.thread).then(|| &*self.value)
+    }
+
+    pub(crate) fn get_mut(&mut self) -> Option<&mut T> {
+        (thread::current().id() == self.thread).then(|| &mut *self.value)
+    }
+}
+
+impl<T> std::fmt::Debug for ThreadBound<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadBound")
+            .field("thread", &self.thread)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Clone for ThreadBound<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        let value = self
+            .get()
+            .expect("a thread bound request extension was cloned on another thread");
+        Self::new(value.clone())
+    }
+}
+
+impl<T> Drop for ThreadBound<T> {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            // SAFETY: the value is never used again.
+            unsafe { ManuallyDrop::drop(&mut self.value) }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use http::StatusCode;
+use monet::{FromRef, Router, State, get, test::TestClient};
+
+#[derive(Clone, FromRef)]
+struct AppState {
+    greeting: &'static str,
+    visits: u32,
+}
+
+#[monoio::test]
+async fn state_extracts_substates() {
+    let app = Router::new()
+        .route(
+            "/",
+            get(
+                |State(greeting): State<&'static str>, State(visits): State<u32>| async move {
+                    format!("{greeting} #{visits}")
+                },
+            ),
+        )
+        .with_state(AppState {
+            greeting: "hello",
+            visits: 3,
+        });
+
+    TestClient::new(app)
+        .get("/")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .assert_text("hello #3");
+}
@@ -0,0 +1,157 @@
+use http::StatusCode;
+use monet::{
+    Router,
+    extract::{json::Json, path::Path, query::Query},
+    get,
+    openapi::{self, JsonSchema, OpenApi},
+    services::ServeDir,
+    test::TestClient,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct User {
+    id: u32,
+    name: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct Pagination {
+    /// The page to return, starting at 1.
+    page: Option<u32>,
+}
+
+async fn list_users(Query(pagination): Query<Pagination>) -> Json<Vec<User>> {
+    let _ = pagination.page;
+    Json(Vec::new())
+}
+
+async fn show_user(Path(id): Path<u32>) -> Json<User> {
+    Json(User {
+        id,
+        name: "ada".to_owned(),
+    })
+}
+
+fn app() -> Router {
+    Router::new()
+        .route("/users", openapi::get(list_users))
+        .route(
+            "/users/{id}",
+            openapi::get_with(show_user, |op| {
+                op.summary("Show a user")
+                    .response::<User>(StatusCode::OK, "The user")
+                    .empty_response(StatusCode::NOT_FOUND, "No such user")
+            }),
+        )
+        .route("/files/{*rest}", get(|| async { "" }))
+        .route("/health", get(|| async { "ok" }))
+        .nest_service("/assets", ServeDir::new("public"))
+}
+
+fn api() -> OpenApi {
+    OpenApi::new("Users", "1.0.0")
+        .description("Manages users")
+        .server("https://api.example.com")
+}
+
+#[test]
+fn documents_every_route() {
+    let string = || json!({ "type": "string" });
+    let nested = "Handled by a nested service, for any method.";
+
+    assert_eq!(
+        api().document(&app()),
+        json!({
+            "openapi": "3.1.0",
+            "info": {
+                "title": "Users",
+                "version": "1.0.0",
+                "description": "Manages users",
+            },
+            "servers": [{ "url": "https://api.example.com" }],
+            "paths": {
+                "/users": {
+                    "get": {
+                        "parameters": [{
+                            "name": "page",
+                            "in": "query",
+                            "required": false,
+                            "schema": {
+                                "type": "integer",
+                                "format": "uint32",
+                                "description": "The page to return, starting at 1.",
+                            },
+                        }],
+                    },
+                },
+                "/users/{id}": {
+                    "get": {
+                        "summary": "Show a user",
+                        "parameters": [
+                            { "name": "id", "in": "path", "required": true, "schema": string() },
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "The user",
+                                "content": {
+                                    "application/json": {
+                                        "schema": {
+                                            "type": "object",
+                                            "properties": {
+                                                "id": { "type": "integer", "format": "uint32" },
+                                                "name": string(),
+                                            },
+                                            "required": ["id", "name"],
+                                        },
+                                    },
+                                },
+                            },
+                            "404": { "description": "No such user" },
+                        },
+                    },
+                },
+                "/files/{rest}": {
+                    "get": {
+                        "parameters": [
+                            { "name": "rest", "in": "path", "required": true, "schema": string() },
+                        ],
+                    },
+                },
+                "/health": { "get": {} },
+                "/assets": { "description": nested },
+                "/assets/": { "description": nested },
+                "/assets/{path}": {
+                    "description": nested,
+                    "parameters": [
+                        { "name": "path", "in": "path", "required": true, "schema": string() },
+                    ],
+                },
+            },
+        })
+    );
+}
+
+#[monoio::test]
+async fn serves_the_document_and_pinned_pages() {
+    let client = TestClient::new(app().openapi(api().swagger_ui("/docs").redoc("/redoc")));
+
+    let res = client.get("/openapi.json").send().await;
+    res.assert_status(StatusCode::OK)
+        .assert_header("content-type", "application/json")
+        .assert_json(&api().document(&app()));
+
+    let swagger = client.get("/docs").send().await;
+    swagger
+        .assert_status(StatusCode::OK)
+        .assert_header("content-type", "text/html; charset=utf-8");
+    let swagger = swagger.text();
+    assert!(swagger.contains("https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui-bundle.js"));
+    assert!(swagger.contains(r#"url: "/openapi.json""#));
+
+    let redoc = client.get("/redoc").send().await;
+    let redoc = redoc.text();
+    assert!(redoc.contains("https://cdn.redoc.ly/redoc/v2.1.5/bundles/redoc.standalone.js"));
+    assert!(redoc.contains(r#"spec-url="/openapi.json""#));
+}
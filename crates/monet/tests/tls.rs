@@ -0,0 +1,316 @@
+use std::{
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use monet::{
+    Router, get, serve,
+    tls::{TlsConfig, TlsListener},
+};
+use monoio::{io::AsyncReadRent, net::TcpStream, time::Instant};
+use monoio_compat::TcpStreamCompat;
+use rcgen::CertifiedKey;
+use rustls::{
+    ClientConfig, RootCertStore,
+    pki_types::{CertificateDer, ServerName},
+};
+use tokio_rustls::{TlsConnector, client::TlsStream};
+
+struct TestCert {
+    cert_pem: String,
+    key_pem: String,
+    der: CertificateDer<'static>,
+}
+
+fn generate(name: &str) -> TestCert {
+    let CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec![name.to_owned()]).unwrap();
+    TestCert {
+        cert_pem: cert.pem(),
+        key_pem: signing_key.serialize_pem(),
+        der: cert.der().clone(),
+    }
+}
+
+/// A temporary directory holding certificate files.
+struct CertDir {
+    dir: PathBuf,
+}
+
+impl CertDir {
+    fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "monet-tls-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&dir).unwrap();
+        Self { dir }
+    }
+
+    fn cert_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.crt"))
+    }
+
+    fn key_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.key"))
+    }
+
+    /// Write the files of `name`, with a modification time later than any before,
+    /// however coarse the file system's timestamps.
+    fn write(&self, name: &str, cert_pem: &str, key_pem: &str) {
+        static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+        let modified =
+            SystemTime::now() + Duration::from_secs(GENERATION.fetch_add(1, Ordering::Relaxed));
+        for (path, pem) in [
+            (self.cert_path(name), cert_pem),
+            (self.key_path(name), key_pem),
+        ] {
+            std::fs::write(&path, pem).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(modified).unwrap();
+        }
+    }
+
+    fn install(&self, name: &str, cert: &TestCert) {
+        self.write(name, &cert.cert_pem, &cert.key_pem);
+    }
+
+    async fn load(&self, name: &str) -> TlsConfig {
+        TlsConfig::from_pem_file(self.cert_path(name), self.key_path(name))
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for CertDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Serve `config` on a loopback port of the current core.
+fn spawn_server(config: TlsConfig) -> std::net::SocketAddr {
+    let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/", get(|| async { "hello" }));
+    monoio::spawn(serve(TlsListener::new(listener, config), app).into_future());
+    addr
+}
+
+async fn connect(
+    addr: std::net::SocketAddr,
+    server_name: &str,
+    trusted: &[&TestCert],
+    alpn: &[&[u8]],
+) -> TlsStream<TcpStreamCompat> {
+    let mut roots = RootCertStore::empty();
+    for cert in trusted {
+        roots.add(cert.der.clone()).unwrap();
+    }
+    let mut config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    TlsConnector::from(Arc::new(config))
+        .connect(
+            ServerName::try_from(server_name.to_owned()).unwrap(),
+            TcpStreamCompat::new(stream),
+        )
+        .await
+        .unwrap()
+}
+
+/// The certificate the server presents to a client asking for `server_name`.
+async fn presented(
+    addr: std::net::SocketAddr,
+    server_name: &str,
+    trusted: &[&TestCert],
+) -> CertificateDer<'static> {
+    let stream = connect(addr, server_name, trusted, &[]).await;
+    stream.get_ref().1.peer_certificates().unwrap()[0].clone()
+}
+
+#[monoio::test(timer_enabled = true)]
+async fn alpn_negotiates_h2_or_http1() {
+    let cert = generate("localhost");
+    let addr = spawn_server(TlsConfig::from_pem(&cert.cert_pem, &cert.key_pem).unwrap());
+
+    let h2 = connect(addr, "localhost", &[&cert], &[b"h2", b"http/1.1"]).await;
+    assert_eq!(h2.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+    let http1 = connect(addr, "localhost", &[&cert], &[b"http/1.1"]).await;
+    assert_eq!(http1.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+}
+
+#[monoio::test(timer_enabled = true)]
+async fn alpn_protocols_can_be_restricted() {
+    let cert = generate("localhost");
+    let config = TlsConfig::from_pem(&cert.cert_pem, &cert.key_pem)
+        .unwrap()
+        .alpn_protocols(vec![b"http/1.1".to_vec()]);
+    let addr = spawn_server(config);
+
+    let stream = connect(addr, "localhost", &[&cert], &[b"h2", b"http/1.1"]).await;
+    assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+}
+
+#[monoio::test(timer_enabled = true)]
+async fn sni_selects_the_certificate() {
+    let default = generate("localhost");
+    let api = generate("api.example.test");
+    let www = generate("www.example.test");
+    let config = TlsConfig::from_pem(&default.cert_pem, &default.key_pem).unwrap();
+    config
+        .add_sni_pem("api.example.test", &api.cert_pem, &api.key_pem)
+        .unwrap();
+    config
+        .add_sni_pem("www.example.test", &www.cert_pem, &www.key_pem)
+        .unwrap();
+    let addr = spawn_server(config);
+    let trusted = [&default, &api, &www];
+
+    for (name, expected) in [
+        ("api.example.test", &api),
+        ("www.example.test", &www),
+        ("localhost", &default),
+    ] {
+        let stream = connect(addr, name, &trusted, &[]).await;
+        let presented = stream.get_ref().1.peer_certificates().unwrap();
+        assert_eq!(presented[0], expected.der, "wrong certificate for {name}");
+    }
+}
+
+#[monoio::test(timer_enabled = true)]
+async fn handshake_timeout_drops_silent_clients() {
+    let cert = generate("localhost");
+    let config = TlsConfig::from_pem(&cert.cert_pem, &cert.key_pem)
+        .unwrap()
+        .handshake_timeout(Duration::from_millis(200));
+    let addr = spawn_server(config);
+
+    let mut silent = TcpStream::connect(addr).await.unwrap();
+    let started = Instant::now();
+    let (read, _) = monoio::time::timeout(Duration::from_secs(5), silent.read(vec![0; 64]))
+        .await
+        .expect("the server kept the silent client connected");
+
+    assert!(matches!(read, Ok(0) | Err(_)), "unexpected data: {read:?}");
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn mismatched_key_is_rejected() {
+    let cert = generate("localhost");
+    let other = generate("localhost");
+
+    let err = TlsConfig::from_pem(&cert.cert_pem, &other.key_pem).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[monoio::test(timer_enabled = true)]
+async fn reload_serves_swapped_certificates() {
+    let old = generate("localhost");
+    let new = generate("localhost");
+    let certs = CertDir::new();
+    certs.install("default", &old);
+    let config = certs.load("default").await;
+    let addr = spawn_server(config.clone());
+    let trusted = [&old, &new];
+
+    config.reload().await.unwrap();
+    assert_eq!(presented(addr, "localhost", &trusted).await, old.der);
+
+    certs.install("default", &new);
+    assert_eq!(presented(addr, "localhost", &trusted).await, old.der);
+    config.reload().await.unwrap();
+    assert_eq!(presented(addr, "localhost", &trusted).await, new.der);
+}
+
+#[monoio::test(timer_enabled = true)]
+async fn reload_keeps_certificates_that_fail_to_load() {
+    let default = generate("localhost");
+    let api = generate("api.example.test");
+    let www = generate("www.example.test");
+    let certs = CertDir::new();
+    certs.install("default", &default);
+    certs.install("api", &api);
+    certs.install("www", &www);
+    let config = certs.load("default").await;
+    for name in ["api", "www"] {
+        config
+            .add_sni_pem_file(
+                format!("{name}.example.test"),
+                certs.cert_path(name),
+                certs.key_path(name),
+            )
+            .await
+            .unwrap();
+    }
+    let addr = spawn_server(config.clone());
+
+    // A renewal caught halfway, with the new certificate but the old key.
+    let renewed = generate("localhost");
+    certs.write("default", &renewed.cert_pem, &default.key_pem);
+    std::fs::remove_file(certs.key_path("www")).unwrap();
+    let new_api = generate("api.example.test");
+    certs.install("api", &new_api);
+
+    let err = config.reload().await.unwrap_err();
+    let mut failures: Vec<_> = err
+        .failures()
+        .map(|(server_name, err)| (server_name.map(str::to_owned), err.kind()))
+        .collect();
+    failures.sort();
+    assert_eq!(
+        failures,
+        [
+            (None, ErrorKind::InvalidData),
+            (Some("www.example.test".to_owned()), ErrorKind::NotFound),
+        ]
+    );
+
+    let trusted = [&default, &renewed, &api, &new_api, &www];
+    assert_eq!(presented(addr, "localhost", &trusted).await, default.der);
+    assert_eq!(presented(addr, "www.example.test", &trusted).await, www.der);
+    assert_eq!(
+        presented(addr, "api.example.test", &trusted).await,
+        new_api.der
+    );
+}
+
+#[monoio::test(timer_enabled = true)]
+async fn watch_picks_up_new_certificates() {
+    let old = generate("localhost");
+    let new = generate("localhost");
+    let certs = CertDir::new();
+    certs.install("default", &old);
+    let config = certs.load("default").await;
+    let addr = spawn_server(config.clone());
+    let trusted = [&old, &new];
+
+    let _watch = config.watch(Duration::from_millis(20));
+    certs.install("default", &new);
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while presented(addr, "localhost", &trusted).await != new.der {
+        assert!(
+            Instant::now() < deadline,
+            "the new certificate was never served"
+        );
+        monoio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
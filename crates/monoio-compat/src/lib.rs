@@ -19,6 +19,7 @@ pub type UnixStreamCompat = StreamWrapper<monoio::net::UnixStream>;
 
 #[cfg(test)]
 mod tests {
+    use super::*;
 
     #[monoio::test_all]
     async fn test_rw() {
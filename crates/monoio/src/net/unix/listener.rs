@@ -5,7 +5,10 @@ use std::{
     path::Path,
 };
 
-use super::{socket_addr::SocketAddr, UnixStream};
+use super::{
+    socket_addr::{local_addr, SocketAddr},
+    UnixStream,
+};
 use crate::{
     driver::{op::Op, shared_fd::SharedFd},
     io::{stream::Stream, CancelHandle},
@@ -135,6 +138,11 @@ impl UnixListener {
         op.wait().await
     }
 
+    /// Returns the socket address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        local_addr(self.as_raw_fd())
+    }
+
     /// Creates new `UnixListener` from a `std::os::unix::net::UnixListener`.
     pub fn from_std(sys_listener: std::os::unix::net::UnixListener) -> io::Result<Self> {
         match SharedFd::new::<false>(sys_listener.as_raw_fd()) {
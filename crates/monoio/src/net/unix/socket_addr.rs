@@ -91,6 +91,16 @@ impl SocketAddr {
         (self.sockaddr, self.socklen)
     }
 
+    /// Creates an address for the socket file at `path`.
+    ///
+    /// Documentation reflected in [`SocketAddr::from_pathname`]
+    ///
+    /// [`SocketAddr::from_pathname`]: std::os::unix::net::SocketAddr::from_pathname
+    pub fn from_pathname<P: AsRef<Path>>(path: P) -> io::Result<SocketAddr> {
+        let (sockaddr, socklen) = socket_addr(path.as_ref())?;
+        Ok(SocketAddr::from_parts(sockaddr, socklen))
+    }
+
     /// Returns `true` if the address is unnamed.
     ///
     /// Documentation reflected in [`SocketAddr`]